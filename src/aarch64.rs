@@ -0,0 +1,392 @@
+use crate::codegen::{align_to, reloc_expr, split_data, Target};
+use crate::kind::Kind;
+use crate::kind::Reloc;
+use crate::types::Type;
+
+// 引数の入るレジスタの数(x0〜x7)
+const ARG_REGISTER_NUM: usize = 8;
+
+//AArch64(AAPCS64)向けのコード生成
+//スタックポインタは常に16の倍数でなければならないため、スタックマシンの値は1つにつき16バイト使う
+//va_listはSystem V ABIの配置のままでAAPCS64のものと異なるため、va_listや構造体は関数に渡せない
+pub struct AArch64 {
+    //生成したアセンブリの各行
+    lines: Vec<String>,
+    //生成中の関数名(returnのジャンプ先のラベルに使う)
+    func_name: String,
+}
+
+impl AArch64 {
+    pub fn new() -> Self {
+        AArch64 {
+            lines: Vec::new(),
+            func_name: String::new(),
+        }
+    }
+
+    fn emit(&mut self, line: String) {
+        self.lines.push(line);
+    }
+
+    fn push(&mut self, reg: &str) {
+        self.emit(format!("  str {reg}, [sp, -16]!"));
+    }
+
+    fn pop_to(&mut self, reg: &str) {
+        self.emit(format!("  ldr {reg}, [sp], 16"));
+    }
+
+    //64ビットの即値をレジスタに入れる(16ビットずつ組み立てる)
+    fn mov_imm(&mut self, reg: &str, value: u64) {
+        self.emit(format!("  movz {reg}, {}", value & 0xffff));
+        for shift in [16, 32, 48] {
+            let chunk = (value >> shift) & 0xffff;
+            if chunk != 0 {
+                self.emit(format!("  movk {reg}, {chunk}, lsl {shift}"));
+            }
+        }
+    }
+
+    //フレームの先頭からoffsetバイト下のアドレスをレジスタに入れる
+    //subの即値は12ビットまでなので、それより大きければ一度レジスタに入れる
+    fn local_address(&mut self, reg: &str, offset: usize) {
+        if offset < 4096 {
+            self.emit(format!("  sub {reg}, x29, {offset}"));
+        } else {
+            self.mov_imm(reg, offset as u64);
+            self.emit(format!("  sub {reg}, x29, {reg}"));
+        }
+    }
+
+    //レジスタxnの下位sizeバイトをaddrのアドレスに書き込む
+    fn store_sized(&mut self, n: usize, addr: &str, size: usize) {
+        match size {
+            1 => self.emit(format!("  strb w{n}, [{addr}]")),
+            2 => self.emit(format!("  strh w{n}, [{addr}]")),
+            4 => self.emit(format!("  str w{n}, [{addr}]")),
+            _ => self.emit(format!("  str x{n}, [{addr}]")),
+        }
+    }
+
+    //x0とx1を比較し、条件を満たせば1、満たさなければ0をx0に入れる
+    fn compare(&mut self, cond: &str) {
+        self.emit("  cmp x0, x1".to_string());
+        self.emit(format!("  cset x0, {cond}"));
+    }
+
+    //x0とx1の浮動小数点数を比較し、条件を満たせば1、満たさなければ0をx0に入れる
+    //順序付けできない(NaNを含む)場合はneだけを満たすように条件を選ぶ
+    fn fcompare(&mut self, r: char, cond: &str) {
+        self.emit(format!("  fcmp {r}0, {r}1"));
+        self.emit(format!("  cset x0, {cond}"));
+    }
+}
+
+//浮動小数点数のレジスタの名前の先頭(floatはs、doubleはd)と、同じ大きさの汎用レジスタの名前の先頭
+fn fp_regs(ty: &Type) -> (char, char) {
+    match ty {
+        Type::Float => ('s', 'w'),
+        _ => ('d', 'x'),
+    }
+}
+
+impl Target for AArch64 {
+    fn data(&mut self, name: &str, align: usize, init: &[u8], relocs: &[Reloc], is_static: bool) {
+        self.emit(".data".to_string());
+        if !is_static {
+            self.emit(format!(".globl {name}"));
+        }
+        self.emit(format!(".balign {align}"));
+        self.emit(format!("{name}:"));
+        // 初期値がすべて0であれば.zeroでまとめて確保する
+        if relocs.is_empty() && init.iter().all(|byte| *byte == 0) {
+            self.emit(format!(".zero {}", init.len()));
+            return;
+        }
+        for (bytes, reloc) in split_data(init, relocs) {
+            if !bytes.is_empty() {
+                let bytes: Vec<String> = bytes.iter().map(u8::to_string).collect();
+                self.emit(format!(".byte {}", bytes.join(", ")));
+            }
+            if let Some(reloc) = reloc {
+                self.emit(format!(".quad {}", reloc_expr(&reloc.label, reloc.addend)));
+            }
+        }
+    }
+
+    fn prologue(&mut self, name: &str, stack_size: usize, is_static: bool) {
+        // フレームポインタとリンクレジスタを退避し、ローカル変数の領域を確保する
+        // subの即値は12ビットまでなので、一度x9に入れる
+        let stack_size = align_to(stack_size, 16);
+        self.func_name = name.to_string();
+        self.emit(".text".to_string());
+        if !is_static {
+            self.emit(format!(".globl {name}"));
+        }
+        self.emit(format!("{name}:"));
+        self.emit("  stp x29, x30, [sp, -16]!".to_string());
+        self.emit("  mov x29, sp".to_string());
+        self.mov_imm("x9", stack_size as u64);
+        self.emit("  sub sp, sp, x9".to_string());
+    }
+
+    fn store_param(&mut self, index: usize, offset: usize, ty: &Type) {
+        if ty.is_flonum() {
+            assert!(
+                index < ARG_REGISTER_NUM,
+                "浮動小数点数の仮引数は{}個以下にして下さい。プログラムを終了します。",
+                ARG_REGISTER_NUM
+            );
+            let (r, _) = fp_regs(ty);
+            self.local_address("x9", offset);
+            self.emit(format!("  str {r}{index}, [x9]"));
+            return;
+        }
+        assert!(
+            index < ARG_REGISTER_NUM,
+            "仮引数はレジスタの数である{}個以下にして下さい。プログラムを終了します。",
+            ARG_REGISTER_NUM
+        );
+        self.local_address("x9", offset);
+        self.store_sized(index, "x9", ty.size());
+    }
+
+    fn save_va_area(&mut self, offset: usize) {
+        self.local_address("x9", offset);
+        for index in 0..ARG_REGISTER_NUM {
+            self.emit(format!("  str x{index}, [x9, {}]", 8 * index));
+        }
+        for index in 0..ARG_REGISTER_NUM {
+            self.emit(format!(
+                "  str d{index}, [x9, {}]",
+                8 * (ARG_REGISTER_NUM + index)
+            ));
+        }
+    }
+
+    fn va_layout(&self) -> (usize, Option<usize>) {
+        (ARG_REGISTER_NUM, Some(8))
+    }
+
+    fn epilogue(&mut self) {
+        self.emit(format!(".Lreturn.{}:", self.func_name));
+        self.emit("  mov sp, x29".to_string());
+        self.emit("  ldp x29, x30, [sp], 16".to_string());
+        self.emit("  ret".to_string());
+    }
+
+    fn push_num(&mut self, value: i64) {
+        // 負の数はビット列をそのまま組み立てる
+        #[allow(clippy::cast_sign_loss)]
+        self.mov_imm("x0", value as u64);
+        self.push("x0");
+    }
+
+    fn push_local_address(&mut self, offset: usize) {
+        self.local_address("x0", offset);
+        self.push("x0");
+    }
+
+    fn push_global_address(&mut self, name: &str) {
+        // ページのアドレスとページ内のオフセットに分けて求める
+        self.emit(format!("  adrp x0, {name}"));
+        self.emit(format!("  add x0, x0, :lo12:{name}"));
+        self.push("x0");
+    }
+
+    fn push_stack_args_address(&mut self) {
+        // 退避したフレームポインタとリンクレジスタの上にある
+        self.emit("  add x0, x29, 16".to_string());
+        self.push("x0");
+    }
+
+    fn load(&mut self, ty: &Type) {
+        if ty.is_struct() {
+            return;
+        }
+        self.pop_to("x0");
+        // wレジスタへの読み込みは上位32ビットを0にする(floatのビット列も同様)
+        let inst = match (ty.size(), ty.is_unsigned() || ty.is_flonum()) {
+            (1, true) => "ldrb w0",
+            (1, false) => "ldrsb x0",
+            (2, true) => "ldrh w0",
+            (2, false) => "ldrsh x0",
+            (4, true) => "ldr w0",
+            (4, false) => "ldrsw x0",
+            _ => "ldr x0",
+        };
+        self.emit(format!("  {inst}, [x0]"));
+        self.push("x0");
+    }
+
+    fn store(&mut self, ty: &Type) {
+        self.pop_to("x1");
+        self.pop_to("x0");
+        if ty.is_struct() {
+            // 入力元(x1)から出力先(x0)へ1バイトずつコピーする
+            self.emit("  mov x3, x0".to_string());
+            for _ in 0..ty.size() {
+                self.emit("  ldrb w2, [x1], 1".to_string());
+                self.emit("  strb w2, [x3], 1".to_string());
+            }
+            self.push("x0");
+            return;
+        }
+        self.store_sized(1, "x0", ty.size());
+        self.push("x1");
+    }
+
+    fn pop(&mut self) {
+        self.pop_to("x0");
+    }
+
+    fn binary(&mut self, kind: &Kind, ty: &Type) {
+        self.pop_to("x1");
+        self.pop_to("x0");
+        if ty.is_flonum() {
+            let (r, x) = fp_regs(ty);
+            self.emit(format!("  fmov {r}0, {x}0"));
+            self.emit(format!("  fmov {r}1, {x}1"));
+            match kind {
+                Kind::Add => self.emit(format!("  fadd {r}0, {r}0, {r}1")),
+                Kind::Sub => self.emit(format!("  fsub {r}0, {r}0, {r}1")),
+                Kind::Mul => self.emit(format!("  fmul {r}0, {r}0, {r}1")),
+                Kind::Div => self.emit(format!("  fdiv {r}0, {r}0, {r}1")),
+                Kind::Equal => self.fcompare(r, "eq"),
+                Kind::NoEqual => self.fcompare(r, "ne"),
+                Kind::LowThan => self.fcompare(r, "mi"),
+                Kind::LowEqual => self.fcompare(r, "ls"),
+                _ => panic!("不正なノードがあります。プログラムを終了します。"),
+            }
+            if matches!(kind, Kind::Add | Kind::Sub | Kind::Mul | Kind::Div) {
+                self.emit(format!("  fmov {x}0, {r}0"));
+            }
+            self.push("x0");
+            return;
+        }
+        let unsigned = ty.is_unsigned();
+        match kind {
+            Kind::Add => self.emit("  add x0, x0, x1".to_string()),
+            Kind::Sub => self.emit("  sub x0, x0, x1".to_string()),
+            Kind::Mul => self.emit("  mul x0, x0, x1".to_string()),
+            Kind::Div if unsigned => self.emit("  udiv x0, x0, x1".to_string()),
+            Kind::Div => self.emit("  sdiv x0, x0, x1".to_string()),
+            Kind::Equal => self.compare("eq"),
+            Kind::NoEqual => self.compare("ne"),
+            Kind::LowThan if unsigned => self.compare("lo"),
+            Kind::LowThan => self.compare("lt"),
+            Kind::LowEqual if unsigned => self.compare("ls"),
+            Kind::LowEqual => self.compare("le"),
+            _ => panic!("不正なノードがあります。プログラムを終了します。"),
+        }
+        self.push("x0");
+    }
+
+    fn cast(&mut self, from: &Type, to: &Type) {
+        if !from.is_flonum() && !to.is_flonum() && to.size() == 8 {
+            return;
+        }
+        self.pop_to("x0");
+        // 整数と浮動小数点数の変換は、整数の符号に合わせて64ビット整数として行う
+        let sign = |ty: &Type| if ty.is_unsigned() { 'u' } else { 's' };
+        if from.is_flonum() {
+            let (r, x) = fp_regs(from);
+            self.emit(format!("  fmov {r}0, {x}0"));
+            if to.is_flonum() {
+                let (to_r, to_x) = fp_regs(to);
+                self.emit(format!("  fcvt {to_r}0, {r}0"));
+                self.emit(format!("  fmov {to_x}0, {to_r}0"));
+                self.push("x0");
+                return;
+            }
+            self.emit(format!("  fcvtz{} x0, {r}0", sign(to)));
+        } else if to.is_flonum() {
+            let (r, x) = fp_regs(to);
+            self.emit(format!("  {}cvtf {r}0, x0", sign(from)));
+            self.emit(format!("  fmov {x}0, {r}0"));
+            self.push("x0");
+            return;
+        }
+        // 8バイト未満の整数は切り詰めて拡張する
+        let inst = match (to.size(), to.is_unsigned()) {
+            (1, true) => Some("uxtb w0, w0"),
+            (1, false) => Some("sxtb x0, w0"),
+            (2, true) => Some("uxth w0, w0"),
+            (2, false) => Some("sxth x0, w0"),
+            (4, true) => Some("mov w0, w0"),
+            (4, false) => Some("sxtw x0, w0"),
+            _ => None,
+        };
+        if let Some(inst) = inst {
+            self.emit(format!("  {inst}"));
+        }
+        self.push("x0");
+    }
+
+    fn label(&mut self, label: &str) {
+        self.emit(format!("{label}:"));
+    }
+
+    fn jump(&mut self, label: &str) {
+        self.emit(format!("  b {label}"));
+    }
+
+    fn jump_if_zero(&mut self, label: &str) {
+        self.pop_to("x0");
+        self.emit(format!("  cbz x0, {label}"));
+    }
+
+    fn call(&mut self, func_name: &str, arg_types: &[Type], _: Option<usize>, ret: &Type) {
+        assert!(
+            !arg_types.iter().any(Type::is_struct),
+            "va_listや構造体を関数の引数に渡すことには対応していません。プログラムを終了します。"
+        );
+        // 整数と浮動小数点数はそれぞれのレジスタに順に入れる
+        let fp_num = arg_types.iter().filter(|ty| ty.is_flonum()).count();
+        let gp_num = arg_types.len() - fp_num;
+        assert!(
+            gp_num <= ARG_REGISTER_NUM && fp_num <= ARG_REGISTER_NUM,
+            "引数はレジスタの数である{}個以下にして下さい。プログラムを終了します。",
+            ARG_REGISTER_NUM
+        );
+        // 順番に注意
+        let (mut gp, mut fp) = (gp_num, fp_num);
+        for ty in arg_types.iter().rev() {
+            if ty.is_flonum() {
+                fp -= 1;
+                let (r, x) = fp_regs(ty);
+                self.pop_to("x9");
+                self.emit(format!("  fmov {r}{fp}, {x}9"));
+            } else {
+                gp -= 1;
+                self.pop_to(&format!("x{gp}"));
+            }
+        }
+        // スタックポインタは常に16の倍数なのでそのまま呼び出せる
+        self.emit(format!("  bl {func_name}"));
+        // 浮動小数点数の戻り値はd0(floatはs0)に入っている
+        if ret.is_flonum() {
+            let (r, x) = fp_regs(ret);
+            self.emit(format!("  fmov {x}0, {r}0"));
+        }
+        self.push("x0");
+    }
+
+    fn ret(&mut self, ty: &Type) {
+        self.pop_to("x0");
+        if ty.is_flonum() {
+            let (r, x) = fp_regs(ty);
+            self.emit(format!("  fmov {r}0, {x}0"));
+        }
+        self.emit(format!("  b .Lreturn.{}", self.func_name));
+    }
+
+    fn assembly(&self) -> String {
+        let mut asm = String::new();
+        for line in &self.lines {
+            asm.push_str(line);
+            asm.push('\n');
+        }
+        asm
+    }
+}
@@ -0,0 +1,396 @@
+use crate::codegen::reloc_expr;
+
+//アセンブリの記法
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Syntax {
+    Intel, // .intel_syntax noprefix
+    Att,   // AT&T記法
+}
+
+//x86-64の汎用レジスタ
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Reg {
+    Rax,
+    Rcx,
+    Rdx,
+    Rsp,
+    Rbp,
+    Rsi,
+    Rdi,
+    R8,
+    R9,
+}
+
+impl Reg {
+    //機械語でのレジスタ番号
+    pub fn number(self) -> u8 {
+        match self {
+            Reg::Rax => 0,
+            Reg::Rcx => 1,
+            Reg::Rdx => 2,
+            Reg::Rsp => 4,
+            Reg::Rbp => 5,
+            Reg::Rsi => 6,
+            Reg::Rdi => 7,
+            Reg::R8 => 8,
+            Reg::R9 => 9,
+        }
+    }
+
+    //64ビットレジスタの名前
+    fn name64(self) -> &'static str {
+        match self {
+            Reg::Rax => "rax",
+            Reg::Rcx => "rcx",
+            Reg::Rdx => "rdx",
+            Reg::Rsp => "rsp",
+            Reg::Rbp => "rbp",
+            Reg::Rsi => "rsi",
+            Reg::Rdi => "rdi",
+            Reg::R8 => "r8",
+            Reg::R9 => "r9",
+        }
+    }
+
+    //下位32ビットレジスタの名前
+    fn name32(self) -> &'static str {
+        match self {
+            Reg::Rax => "eax",
+            Reg::Rcx => "ecx",
+            Reg::Rdx => "edx",
+            Reg::Rsp => "esp",
+            Reg::Rbp => "ebp",
+            Reg::Rsi => "esi",
+            Reg::Rdi => "edi",
+            Reg::R8 => "r8d",
+            Reg::R9 => "r9d",
+        }
+    }
+
+    //下位16ビットレジスタの名前
+    fn name16(self) -> &'static str {
+        match self {
+            Reg::Rax => "ax",
+            Reg::Rcx => "cx",
+            Reg::Rdx => "dx",
+            Reg::Rsp => "sp",
+            Reg::Rbp => "bp",
+            Reg::Rsi => "si",
+            Reg::Rdi => "di",
+            Reg::R8 => "r8w",
+            Reg::R9 => "r9w",
+        }
+    }
+
+    //下位8ビットレジスタの名前
+    fn name8(self) -> &'static str {
+        match self {
+            Reg::Rax => "al",
+            Reg::Rcx => "cl",
+            Reg::Rdx => "dl",
+            Reg::Rsp => "spl",
+            Reg::Rbp => "bpl",
+            Reg::Rsi => "sil",
+            Reg::Rdi => "dil",
+            Reg::R8 => "r8b",
+            Reg::R9 => "r9b",
+        }
+    }
+}
+
+//命令のオペランド
+#[derive(Clone, PartialEq, Eq)]
+pub enum Operand {
+    Reg(Reg),        // 64ビットレジスタ
+    Reg32(Reg),      // レジスタの下位32ビット
+    Reg16(Reg),      // レジスタの下位16ビット
+    Reg8(Reg),       // レジスタの下位8ビット
+    Imm(i64),        // 即値
+    Mem(Reg, i32),   // [レジスタ + 変位] の64ビット
+    Mem32(Reg, i32), // [レジスタ + 変位] の32ビット
+    Mem16(Reg, i32), // [レジスタ + 変位] の16ビット
+    Mem8(Reg, i32),  // [レジスタ + 変位] の8ビット
+    Rip(String),     // [rip + シンボル] (シンボルのアドレスをripからの相対で表す)
+    Xmm(u8),         // xmmレジスタ(xmm0〜xmm7)
+}
+
+//SSE命令が扱う浮動小数点数の精度
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Prec {
+    Single, // float
+    Double, // double
+}
+
+impl Prec {
+    //命令名の末尾(ss, sd)の2文字目
+    fn suffix(self) -> &'static str {
+        match self {
+            Prec::Single => "s",
+            Prec::Double => "d",
+        }
+    }
+}
+
+//条件付き命令(setcc, jcc)の条件
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Cond {
+    E,  // ==
+    Ne, // !=
+    L,  // <
+    Le, // <=
+    B,  // < (符号なし)
+    Be, // <= (符号なし)
+    A,  // > (符号なし)
+    Ae, // >= (符号なし)
+    P,  // パリティフラグが立っている(浮動小数点数の比較で順序付けできない)
+    Np, // パリティフラグが立っていない
+}
+
+impl Cond {
+    fn suffix(self) -> &'static str {
+        match self {
+            Cond::E => "e",
+            Cond::Ne => "ne",
+            Cond::L => "l",
+            Cond::Le => "le",
+            Cond::B => "b",
+            Cond::Be => "be",
+            Cond::A => "a",
+            Cond::Ae => "ae",
+            Cond::P => "p",
+            Cond::Np => "np",
+        }
+    }
+}
+
+//アセンブリの1行分(命令、ラベル、ディレクティブ)
+//2オペランドの命令はIntel記法と同じ(出力先, 入力元)の順に持つ
+#[derive(Clone, PartialEq, Eq)]
+pub enum Inst {
+    Globl(String),                    // .globl シンボル
+    Text,                             // .text 以降を機械語のセクションに置く
+    Data,                             // .data 以降をデータのセクションに置く
+    Align(usize),                     // .balign アラインメント
+    Byte(Vec<u8>),                    // .byte バイト列
+    Zero(usize),                      // .zero バイト数(0で埋める)
+    Quad(String, i64),                // .quad シンボル+オフセット(シンボルのアドレス)
+    Label(String),                    // ラベル:
+    Push(Operand),                    // push
+    Pop(Operand),                     // pop
+    Mov(Operand, Operand),            // mov
+    Lea(Operand, Operand),            // lea
+    Movsx(Operand, Operand),          // movsx (8, 16, 32ビットからの符号拡張)
+    Movzx(Operand, Operand),          // movzx (8, 16ビットからのゼロ拡張)
+    Add(Operand, Operand),            // add
+    Sub(Operand, Operand),            // sub
+    Imul(Operand, Operand),           // imul
+    Cmp(Operand, Operand),            // cmp
+    Cqo,                              // cqo (raxを符号拡張してrdx:raxにする)
+    Idiv(Operand),                    // idiv
+    Div(Operand),                     // div (符号なしの除算)
+    And(Operand, Operand),            // and
+    Or(Operand, Operand),             // or
+    Shr(Operand, Operand),            // shr (論理右シフト)
+    Movq(Operand, Operand),           // movq (汎用レジスタとxmmレジスタの間の64ビットの転送)
+    Movd(Operand, Operand),           // movd (汎用レジスタとxmmレジスタの間の32ビットの転送)
+    Addf(Prec, Operand, Operand),     // addss, addsd
+    Subf(Prec, Operand, Operand),     // subss, subsd
+    Mulf(Prec, Operand, Operand),     // mulss, mulsd
+    Divf(Prec, Operand, Operand),     // divss, divsd
+    Ucomi(Prec, Operand, Operand),    // ucomiss, ucomisd
+    Cvtsi2f(Prec, Operand, Operand),  // cvtsi2ss, cvtsi2sd (64ビット整数から)
+    Cvttf2si(Prec, Operand, Operand), // cvttss2si, cvttsd2si (64ビット整数へ切り捨て)
+    Cvtf2f(Prec, Operand, Operand),   // cvtsd2ss, cvtss2sd (精度は変換先)
+    Set(Cond, Operand),               // setcc
+    Jmp(String),                      // jmp
+    Jcc(Cond, String),                // jcc
+    Call(String),                     // call
+    Ret,                              // ret
+}
+
+impl Operand {
+    //AT&T記法の命令の接尾辞になるオペランドの大きさ(即値とアドレスは大きさを持たない)
+    fn suffix(&self) -> Option<char> {
+        match self {
+            Operand::Reg(_) | Operand::Mem(..) => Some('q'),
+            Operand::Reg32(_) | Operand::Mem32(..) => Some('l'),
+            Operand::Reg16(_) | Operand::Mem16(..) => Some('w'),
+            Operand::Reg8(_) | Operand::Mem8(..) => Some('b'),
+            Operand::Imm(_) | Operand::Rip(_) | Operand::Xmm(_) => None,
+        }
+    }
+
+    fn intel(&self) -> String {
+        // [レジスタ + 変位]
+        let mem = |reg: &Reg, disp: &i32| match disp {
+            0 => format!("[{}]", reg.name64()),
+            disp if *disp < 0 => format!("[{}{}]", reg.name64(), disp),
+            disp => format!("[{}+{}]", reg.name64(), disp),
+        };
+        match self {
+            Operand::Reg(reg) => reg.name64().to_string(),
+            Operand::Reg32(reg) => reg.name32().to_string(),
+            Operand::Reg16(reg) => reg.name16().to_string(),
+            Operand::Reg8(reg) => reg.name8().to_string(),
+            Operand::Imm(val) => val.to_string(),
+            Operand::Mem(reg, disp) => mem(reg, disp),
+            Operand::Mem32(reg, disp) => format!("DWORD PTR {}", mem(reg, disp)),
+            Operand::Mem16(reg, disp) => format!("WORD PTR {}", mem(reg, disp)),
+            Operand::Mem8(reg, disp) => format!("BYTE PTR {}", mem(reg, disp)),
+            Operand::Rip(name) => format!("[rip+{name}]"),
+            Operand::Xmm(n) => format!("xmm{n}"),
+        }
+    }
+
+    fn att(&self) -> String {
+        match self {
+            Operand::Reg(reg) => format!("%{}", reg.name64()),
+            Operand::Reg32(reg) => format!("%{}", reg.name32()),
+            Operand::Reg16(reg) => format!("%{}", reg.name16()),
+            Operand::Reg8(reg) => format!("%{}", reg.name8()),
+            Operand::Imm(val) => format!("${val}"),
+            Operand::Mem(reg, 0)
+            | Operand::Mem32(reg, 0)
+            | Operand::Mem16(reg, 0)
+            | Operand::Mem8(reg, 0) => format!("(%{})", reg.name64()),
+            Operand::Mem(reg, disp)
+            | Operand::Mem32(reg, disp)
+            | Operand::Mem16(reg, disp)
+            | Operand::Mem8(reg, disp) => format!("{}(%{})", disp, reg.name64()),
+            Operand::Rip(name) => format!("{name}(%rip)"),
+            Operand::Xmm(n) => format!("%xmm{n}"),
+        }
+    }
+}
+
+impl Inst {
+    //指定された記法で1行分の文字列に変換する
+    pub fn render(&self, syntax: Syntax) -> String {
+        match syntax {
+            Syntax::Intel => self.intel(),
+            Syntax::Att => self.att(),
+        }
+    }
+
+    fn intel(&self) -> String {
+        // 2オペランド命令
+        let binary = |op: &str, dst: &Operand, src: &Operand| {
+            format!("  {} {}, {}", op, dst.intel(), src.intel())
+        };
+        match self {
+            Inst::Globl(name) => format!(".globl {name}"),
+            Inst::Text => ".text".to_string(),
+            Inst::Data => ".data".to_string(),
+            Inst::Align(align) => format!(".balign {align}"),
+            Inst::Byte(bytes) => format!(".byte {}", join_bytes(bytes)),
+            Inst::Zero(size) => format!(".zero {size}"),
+            Inst::Quad(label, addend) => format!(".quad {}", reloc_expr(label, *addend)),
+            Inst::Label(name) => format!("{name}:"),
+            Inst::Push(src) => format!("  push {}", src.intel()),
+            Inst::Pop(dst) => format!("  pop {}", dst.intel()),
+            Inst::Mov(dst, src) => binary("mov", dst, src),
+            Inst::Lea(dst, src) => binary("lea", dst, src),
+            // 32ビットからの符号拡張だけは命令名が異なる
+            Inst::Movsx(dst, src @ (Operand::Reg32(_) | Operand::Mem32(..))) => {
+                binary("movsxd", dst, src)
+            }
+            Inst::Movsx(dst, src) => binary("movsx", dst, src),
+            Inst::Movzx(dst, src) => binary("movzx", dst, src),
+            Inst::Add(dst, src) => binary("add", dst, src),
+            Inst::Sub(dst, src) => binary("sub", dst, src),
+            Inst::Imul(dst, src) => binary("imul", dst, src),
+            Inst::Cmp(dst, src) => binary("cmp", dst, src),
+            Inst::Cqo => "  cqo".to_string(),
+            Inst::Idiv(src) => format!("  idiv {}", src.intel()),
+            Inst::Div(src) => format!("  div {}", src.intel()),
+            Inst::And(dst, src) => binary("and", dst, src),
+            Inst::Or(dst, src) => binary("or", dst, src),
+            Inst::Shr(dst, src) => binary("shr", dst, src),
+            Inst::Movq(dst, src) => binary("movq", dst, src),
+            Inst::Movd(dst, src) => binary("movd", dst, src),
+            Inst::Addf(prec, dst, src) => binary(&format!("adds{}", prec.suffix()), dst, src),
+            Inst::Subf(prec, dst, src) => binary(&format!("subs{}", prec.suffix()), dst, src),
+            Inst::Mulf(prec, dst, src) => binary(&format!("muls{}", prec.suffix()), dst, src),
+            Inst::Divf(prec, dst, src) => binary(&format!("divs{}", prec.suffix()), dst, src),
+            Inst::Ucomi(prec, dst, src) => binary(&format!("ucomis{}", prec.suffix()), dst, src),
+            Inst::Cvtsi2f(prec, dst, src) => binary(&format!("cvtsi2s{}", prec.suffix()), dst, src),
+            Inst::Cvttf2si(prec, dst, src) => {
+                binary(&format!("cvtts{}2si", prec.suffix()), dst, src)
+            }
+            Inst::Cvtf2f(Prec::Single, dst, src) => binary("cvtsd2ss", dst, src),
+            Inst::Cvtf2f(Prec::Double, dst, src) => binary("cvtss2sd", dst, src),
+            Inst::Set(cond, dst) => format!("  set{} {}", cond.suffix(), dst.intel()),
+            Inst::Jmp(label) => format!("  jmp {label}"),
+            Inst::Jcc(cond, label) => format!("  j{} {}", cond.suffix(), label),
+            Inst::Call(name) => format!("  call {name}"),
+            Inst::Ret => "  ret".to_string(),
+        }
+    }
+
+    fn att(&self) -> String {
+        // 2オペランド命令(AT&T記法では入力元が先)
+        let binary = |op: &str, dst: &Operand, src: &Operand| {
+            format!("  {} {}, {}", op, src.att(), dst.att())
+        };
+        match self {
+            Inst::Globl(name) => format!(".globl {name}"),
+            Inst::Text => ".text".to_string(),
+            Inst::Data => ".data".to_string(),
+            Inst::Align(align) => format!(".balign {align}"),
+            Inst::Byte(bytes) => format!(".byte {}", join_bytes(bytes)),
+            Inst::Zero(size) => format!(".zero {size}"),
+            Inst::Quad(label, addend) => format!(".quad {}", reloc_expr(label, *addend)),
+            Inst::Label(name) => format!("{name}:"),
+            Inst::Push(src) => format!("  pushq {}", src.att()),
+            Inst::Pop(dst) => format!("  popq {}", dst.att()),
+            // 接尾辞はレジスタかメモリのオペランドの大きさに合わせる
+            Inst::Mov(dst, src) => {
+                let suffix = dst.suffix().or_else(|| src.suffix()).unwrap();
+                binary(&format!("mov{suffix}"), dst, src)
+            }
+            Inst::Lea(dst, src) => binary("leaq", dst, src),
+            Inst::Movsx(dst, src) => binary(&format!("movs{}q", src.suffix().unwrap()), dst, src),
+            Inst::Movzx(dst, src) => binary(&format!("movz{}q", src.suffix().unwrap()), dst, src),
+            Inst::Add(dst, src) => binary("addq", dst, src),
+            Inst::Sub(dst, src) => binary("subq", dst, src),
+            Inst::Imul(dst, src) => binary("imulq", dst, src),
+            Inst::Cmp(dst, src) => binary("cmpq", dst, src),
+            Inst::Cqo => "  cqto".to_string(),
+            Inst::Idiv(src) => format!("  idivq {}", src.att()),
+            Inst::Div(src) => format!("  divq {}", src.att()),
+            Inst::And(dst, src) => binary("andq", dst, src),
+            Inst::Or(dst, src) => binary("orq", dst, src),
+            Inst::Shr(dst, src) => binary("shrq", dst, src),
+            Inst::Movq(dst, src) => binary("movq", dst, src),
+            Inst::Movd(dst, src) => binary("movd", dst, src),
+            Inst::Addf(prec, dst, src) => binary(&format!("adds{}", prec.suffix()), dst, src),
+            Inst::Subf(prec, dst, src) => binary(&format!("subs{}", prec.suffix()), dst, src),
+            Inst::Mulf(prec, dst, src) => binary(&format!("muls{}", prec.suffix()), dst, src),
+            Inst::Divf(prec, dst, src) => binary(&format!("divs{}", prec.suffix()), dst, src),
+            Inst::Ucomi(prec, dst, src) => binary(&format!("ucomis{}", prec.suffix()), dst, src),
+            // 入力元が64ビット整数であることを接尾辞qで示す
+            Inst::Cvtsi2f(prec, dst, src) => {
+                binary(&format!("cvtsi2s{}q", prec.suffix()), dst, src)
+            }
+            Inst::Cvttf2si(prec, dst, src) => {
+                binary(&format!("cvtts{}2si", prec.suffix()), dst, src)
+            }
+            Inst::Cvtf2f(Prec::Single, dst, src) => binary("cvtsd2ss", dst, src),
+            Inst::Cvtf2f(Prec::Double, dst, src) => binary("cvtss2sd", dst, src),
+            Inst::Set(cond, dst) => format!("  set{} {}", cond.suffix(), dst.att()),
+            Inst::Jmp(label) => format!("  jmp {label}"),
+            Inst::Jcc(cond, label) => format!("  j{} {}", cond.suffix(), label),
+            Inst::Call(name) => format!("  call {name}"),
+            Inst::Ret => "  ret".to_string(),
+        }
+    }
+}
+
+//.byteに並べるバイト列
+fn join_bytes(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(u8::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
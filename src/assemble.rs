@@ -0,0 +1,514 @@
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+
+use crate::asm::{Cond, Inst, Operand, Prec, Reg};
+use crate::elf::{Object, RelocKind, Relocation, Section, Symbol};
+
+//組み込みアセンブラ
+//codegenが生成する命令を機械語に変換する
+struct Assembler {
+    //.textセクションの機械語
+    text: Vec<u8>,
+    //.dataセクションのデータ
+    data: Vec<u8>,
+    //現在出力しているセクション
+    section: Section,
+    //ラベル名と定義されたセクション、セクション先頭からのオフセット
+    labels: HashMap<String, (Section, usize)>,
+    //.globlで指定されたシンボル
+    globals: HashSet<String>,
+    //ジャンプ先のラベルが決まった後に書き込むrel32の位置とラベル名
+    fixups: Vec<(usize, String)>,
+    //関数呼び出しの呼び出し先のrel32の位置と関数名
+    calls: Vec<(usize, String)>,
+    //rip相対で参照するシンボルのrel32の位置とシンボル名
+    rip_refs: Vec<(usize, String)>,
+    //.quadで書き込むシンボルのアドレスの.dataでの位置、シンボル名、オフセット
+    data_refs: Vec<(usize, String, i64)>,
+}
+
+//命令列を機械語に変換し、オブジェクトファイルの内容を返す
+pub fn assemble(insts: &[Inst]) -> Object {
+    let mut asm = Assembler {
+        text: Vec::new(),
+        data: Vec::new(),
+        section: Section::Text,
+        labels: HashMap::new(),
+        globals: HashSet::new(),
+        fixups: Vec::new(),
+        calls: Vec::new(),
+        rip_refs: Vec::new(),
+        data_refs: Vec::new(),
+    };
+    for inst in insts {
+        asm.encode(inst);
+    }
+
+    // ジャンプ先の相対アドレスを書き込む
+    for (pos, label) in &asm.fixups {
+        let target = match asm.labels.get(label) {
+            Some((Section::Text, offset)) => *offset,
+            _ => panic!(
+                "ラベル{}が定義されていません。プログラムを終了します。",
+                label
+            ),
+        };
+        let rel = i32::try_from(target).unwrap() - i32::try_from(pos + 4).unwrap();
+        asm.text[*pos..pos + 4].copy_from_slice(&rel.to_le_bytes());
+    }
+
+    // シンボル
+    // .Lで始まるテキストのラベルはジャンプ先としてアセンブラ内部でのみ使うので出力しない
+    // .Lで始まるデータのラベル(文字列リテラル)はリンカが再配置できるようにローカルなシンボルとして出力する
+    let mut symbols = Vec::new();
+    let mut symbol_index = HashMap::new();
+    let mut labels: Vec<(&String, &(Section, usize))> = asm.labels.iter().collect();
+    labels.sort_by_key(|(_, (section, offset))| (*section == Section::Data, *offset));
+    for (name, (section, offset)) in labels {
+        if name.starts_with(".L") && *section == Section::Text {
+            continue;
+        }
+        symbol_index.insert(name.clone(), symbols.len());
+        symbols.push(Symbol {
+            name: name.clone(),
+            section: Some(*section),
+            offset: *offset,
+            global: asm.globals.contains(name),
+        });
+    }
+
+    // 関数呼び出しとrip相対の参照、データに書き込むアドレスはリンカにアドレスを解決させる
+    // rel32は次の命令の先頭からの相対アドレス
+    let mut relocations = Vec::new();
+    let refs = asm
+        .calls
+        .into_iter()
+        .map(|(pos, name)| (Section::Text, pos, name, RelocKind::Plt32, -4))
+        .chain(
+            asm.rip_refs
+                .into_iter()
+                .map(|(pos, name)| (Section::Text, pos, name, RelocKind::Pc32, -4)),
+        )
+        .chain(
+            asm.data_refs
+                .into_iter()
+                .map(|(pos, name, addend)| (Section::Data, pos, name, RelocKind::Abs64, addend)),
+        );
+    for (section, pos, name, kind, addend) in refs {
+        let symbol = *symbol_index.entry(name.clone()).or_insert_with(|| {
+            // 外部で定義されたシンボル
+            symbols.push(Symbol {
+                name,
+                section: None,
+                offset: 0,
+                global: true,
+            });
+            symbols.len() - 1
+        });
+        relocations.push(Relocation {
+            section,
+            offset: pos,
+            symbol,
+            kind,
+            addend,
+        });
+    }
+
+    Object {
+        text: asm.text,
+        data: asm.data,
+        rodata: Vec::new(),
+        symbols,
+        relocations,
+    }
+}
+
+//条件付き命令の条件を表す番号
+fn cond_code(cond: Cond) -> u8 {
+    match cond {
+        Cond::E => 0x4,
+        Cond::Ne => 0x5,
+        Cond::L => 0xc,
+        Cond::Le => 0xe,
+        Cond::B => 0x2,
+        Cond::Ae => 0x3,
+        Cond::Be => 0x6,
+        Cond::A => 0x7,
+        Cond::P => 0xa,
+        Cond::Np => 0xb,
+    }
+}
+
+//ModR/Mのr/mに入るレジスタ(メモリであればベースのレジスタ)
+fn rm_reg(rm: &Operand) -> Reg {
+    match rm {
+        Operand::Reg(reg)
+        | Operand::Reg32(reg)
+        | Operand::Reg16(reg)
+        | Operand::Reg8(reg)
+        | Operand::Mem(reg, _)
+        | Operand::Mem32(reg, _)
+        | Operand::Mem16(reg, _)
+        | Operand::Mem8(reg, _) => *reg,
+        Operand::Imm(_) | Operand::Rip(_) | Operand::Xmm(_) => unsupported(),
+    }
+}
+
+//スカラーのSSE命令の精度を表すプレフィックス
+fn prec_prefix(prec: Prec) -> u8 {
+    match prec {
+        Prec::Single => 0xf3,
+        Prec::Double => 0xf2,
+    }
+}
+
+//汎用レジスタかxmmレジスタの番号
+fn reg_number(operand: &Operand) -> u8 {
+    match operand {
+        Operand::Xmm(n) => *n,
+        _ => rm_reg(operand).number(),
+    }
+}
+
+//即値が8ビットの符号付き整数に収まるか
+fn is_imm8(value: i64) -> bool {
+    i8::try_from(value).is_ok()
+}
+
+impl Assembler {
+    fn emit(&mut self, bytes: &[u8]) {
+        match self.section {
+            Section::Text => self.text.extend_from_slice(bytes),
+            _ => self.data.extend_from_slice(bytes),
+        }
+    }
+
+    //現在のセクションの先頭からのオフセット
+    fn offset(&self) -> usize {
+        match self.section {
+            Section::Text => self.text.len(),
+            _ => self.data.len(),
+        }
+    }
+
+    fn emit_imm32(&mut self, value: i64) {
+        let value = i32::try_from(value).unwrap_or_else(|_| {
+            panic!(
+                "即値{}は32ビットに収まりません。プログラムを終了します。",
+                value
+            )
+        });
+        self.emit(&value.to_le_bytes());
+    }
+
+    //REXプレフィックス(wは64ビット演算、regとrmはModR/Mの各フィールドに入るレジスタ番号)
+    fn rex(&mut self, w: bool, reg: u8, rm: u8) {
+        self.emit(&[0x40 | (u8::from(w) << 3) | ((reg >> 3) << 2) | (rm >> 3)]);
+    }
+
+    //64ビット演算でない命令のREXプレフィックス(必要な場合だけ出力する)
+    //byteはregに8ビットレジスタを指定する命令(spl, bpl, sil, dilの指定にREXが必要)
+    fn rex_if_needed(&mut self, reg: u8, rm: u8, byte: bool) {
+        if reg >= 8 || rm >= 8 || (byte && reg >= 4) {
+            self.rex(false, reg, rm);
+        }
+    }
+
+    //レジスタかメモリのオペランドを表すModR/M
+    fn modrm(&mut self, reg: u8, rm: &Operand) {
+        match rm {
+            Operand::Mem(base, disp)
+            | Operand::Mem32(base, disp)
+            | Operand::Mem16(base, disp)
+            | Operand::Mem8(base, disp) => self.modrm_mem(reg, *base, *disp),
+            _ => self.modrm_reg(reg, rm_reg(rm).number()),
+        }
+    }
+
+    //レジスタ同士のModR/M
+    fn modrm_reg(&mut self, reg: u8, rm: u8) {
+        self.emit(&[0xc0 | ((reg & 7) << 3) | (rm & 7)]);
+    }
+
+    //[base + disp]を表すModR/M(と必要ならSIBと変位)
+    fn modrm_mem(&mut self, reg: u8, base: Reg, disp: i32) {
+        let base = base.number();
+        let reg = (reg & 7) << 3;
+        // rbp, r13は変位なしの形式を使えない
+        let mode = if disp == 0 && base & 7 != 5 {
+            0x00
+        } else if i8::try_from(disp).is_ok() {
+            0x40
+        } else {
+            0x80
+        };
+        self.emit(&[mode | reg | (base & 7)]);
+        // rsp, r12はSIBが必要
+        if base & 7 == 4 {
+            self.emit(&[0x24]);
+        }
+        match mode {
+            0x40 => self.emit(&disp.to_le_bytes()[..1]),
+            0x80 => self.emit(&disp.to_le_bytes()),
+            _ => (),
+        }
+    }
+
+    //add, sub, cmpのような2オペランドの算術命令
+    //op_rrは r/m64, r64 の形式のオペコード、extは即値の形式でModR/Mのregに入る番号
+    fn alu(&mut self, op_rr: u8, ext: u8, dst: &Operand, src: &Operand) {
+        match (dst, src) {
+            (Operand::Reg(dst), Operand::Reg(src)) => {
+                self.rex(true, src.number(), dst.number());
+                self.emit(&[op_rr]);
+                self.modrm_reg(src.number(), dst.number());
+            }
+            (Operand::Reg(dst), Operand::Imm(value)) => {
+                self.rex(true, 0, dst.number());
+                if is_imm8(*value) {
+                    self.emit(&[0x83]);
+                    self.modrm_reg(ext, dst.number());
+                    self.emit(&value.to_le_bytes()[..1]);
+                } else {
+                    self.emit(&[0x81]);
+                    self.modrm_reg(ext, dst.number());
+                    self.emit_imm32(*value);
+                }
+            }
+            _ => unsupported(),
+        }
+    }
+
+    //0fから始まるSSE命令(prefixは必要なら先頭に置くプレフィックス、wは64ビット整数を扱う命令)
+    fn sse(&mut self, prefix: Option<u8>, w: bool, opcode: u8, reg: &Operand, rm: &Operand) {
+        let (reg, rm) = (reg_number(reg), reg_number(rm));
+        if let Some(prefix) = prefix {
+            self.emit(&[prefix]);
+        }
+        if w {
+            self.rex(true, reg, rm);
+        } else {
+            self.rex_if_needed(reg, rm, false);
+        }
+        self.emit(&[0x0f, opcode]);
+        self.modrm_reg(reg, rm);
+    }
+
+    //rel32を後で書き込むために場所を空けておく
+    fn fixup(&mut self, label: &str) {
+        self.fixups.push((self.text.len(), label.to_string()));
+        self.emit(&[0; 4]);
+    }
+
+    //1命令分を機械語に変換する
+    #[allow(clippy::too_many_lines)]
+    fn encode(&mut self, inst: &Inst) {
+        match inst {
+            Inst::Globl(name) => {
+                self.globals.insert(name.clone());
+            }
+            Inst::Text => self.section = Section::Text,
+            Inst::Data => self.section = Section::Data,
+            Inst::Align(align) => {
+                while !self.offset().is_multiple_of(*align) {
+                    self.emit(&[0]);
+                }
+            }
+            Inst::Byte(bytes) => self.emit(bytes),
+            Inst::Zero(size) => self.emit(&vec![0; *size]),
+            Inst::Quad(label, addend) => {
+                self.data_refs.push((self.offset(), label.clone(), *addend));
+                self.emit(&[0; 8]);
+            }
+            Inst::Label(name) => {
+                let offset = self.offset();
+                assert!(
+                    self.labels
+                        .insert(name.clone(), (self.section, offset))
+                        .is_none(),
+                    "ラベル{}が重複しています。プログラムを終了します。",
+                    name
+                );
+            }
+            Inst::Push(Operand::Reg(reg)) => {
+                if reg.number() >= 8 {
+                    self.emit(&[0x41]);
+                }
+                self.emit(&[0x50 + (reg.number() & 7)]);
+            }
+            Inst::Push(Operand::Imm(value)) => {
+                if is_imm8(*value) {
+                    self.emit(&[0x6a]);
+                    self.emit(&value.to_le_bytes()[..1]);
+                } else {
+                    self.emit(&[0x68]);
+                    self.emit_imm32(*value);
+                }
+            }
+            Inst::Pop(Operand::Reg(reg)) => {
+                if reg.number() >= 8 {
+                    self.emit(&[0x41]);
+                }
+                self.emit(&[0x58 + (reg.number() & 7)]);
+            }
+            Inst::Mov(Operand::Reg(dst), Operand::Imm(value)) => {
+                if i32::try_from(*value).is_ok() {
+                    // 32ビットの即値を符号拡張する形式
+                    self.rex(true, 0, dst.number());
+                    self.emit(&[0xc7]);
+                    self.modrm_reg(0, dst.number());
+                    self.emit_imm32(*value);
+                } else {
+                    // 64ビットの即値(movabs)
+                    self.rex(true, 0, dst.number());
+                    self.emit(&[0xb8 + (dst.number() & 7)]);
+                    self.emit(&value.to_le_bytes());
+                }
+            }
+            Inst::Mov(Operand::Reg(dst), Operand::Mem(base, disp)) => {
+                self.rex(true, dst.number(), base.number());
+                self.emit(&[0x8b]);
+                self.modrm_mem(dst.number(), *base, *disp);
+            }
+            Inst::Mov(Operand::Mem(base, disp), Operand::Reg(src)) => {
+                self.rex(true, src.number(), base.number());
+                self.emit(&[0x89]);
+                self.modrm_mem(src.number(), *base, *disp);
+            }
+            // 64ビット未満の値の読み書き
+            Inst::Mov(Operand::Reg32(dst), src @ (Operand::Reg32(_) | Operand::Mem32(..))) => {
+                self.rex_if_needed(dst.number(), rm_reg(src).number(), false);
+                self.emit(&[0x8b]);
+                self.modrm(dst.number(), src);
+            }
+            Inst::Mov(dst @ Operand::Mem32(..), Operand::Reg32(src)) => {
+                self.rex_if_needed(src.number(), rm_reg(dst).number(), false);
+                self.emit(&[0x89]);
+                self.modrm(src.number(), dst);
+            }
+            Inst::Mov(dst @ Operand::Mem16(..), Operand::Reg16(src)) => {
+                // オペランドサイズを16ビットにするプレフィックス
+                self.emit(&[0x66]);
+                self.rex_if_needed(src.number(), rm_reg(dst).number(), false);
+                self.emit(&[0x89]);
+                self.modrm(src.number(), dst);
+            }
+            Inst::Mov(dst @ Operand::Mem8(..), Operand::Reg8(src)) => {
+                self.rex_if_needed(src.number(), rm_reg(dst).number(), true);
+                self.emit(&[0x88]);
+                self.modrm(src.number(), dst);
+            }
+            Inst::Mov(dst, src) => self.alu(0x89, 0, dst, src),
+            Inst::Lea(Operand::Reg(dst), Operand::Rip(name)) => {
+                // ModR/Mのmod=00, r/m=101はripからの32ビットの変位
+                self.rex(true, dst.number(), 0);
+                self.emit(&[0x8d, ((dst.number() & 7) << 3) | 0x05]);
+                self.rip_refs.push((self.text.len(), name.clone()));
+                self.emit(&[0; 4]);
+            }
+            Inst::Movsx(Operand::Reg(dst), src) => {
+                let opcode: &[u8] = match src {
+                    Operand::Reg8(_) | Operand::Mem8(..) => &[0x0f, 0xbe],
+                    Operand::Reg16(_) | Operand::Mem16(..) => &[0x0f, 0xbf],
+                    Operand::Reg32(_) | Operand::Mem32(..) => &[0x63],
+                    _ => unsupported(),
+                };
+                self.rex(true, dst.number(), rm_reg(src).number());
+                self.emit(opcode);
+                self.modrm(dst.number(), src);
+            }
+            Inst::Movzx(Operand::Reg(dst), src) => {
+                let opcode: &[u8] = match src {
+                    Operand::Reg8(_) | Operand::Mem8(..) => &[0x0f, 0xb6],
+                    Operand::Reg16(_) | Operand::Mem16(..) => &[0x0f, 0xb7],
+                    _ => unsupported(),
+                };
+                self.rex(true, dst.number(), rm_reg(src).number());
+                self.emit(opcode);
+                self.modrm(dst.number(), src);
+            }
+            Inst::Add(dst, src) => self.alu(0x01, 0, dst, src),
+            Inst::Sub(dst, src) => self.alu(0x29, 5, dst, src),
+            Inst::Cmp(dst, src) => self.alu(0x39, 7, dst, src),
+            Inst::Imul(Operand::Reg(dst), Operand::Reg(src)) => {
+                self.rex(true, dst.number(), src.number());
+                self.emit(&[0x0f, 0xaf]);
+                self.modrm_reg(dst.number(), src.number());
+            }
+            Inst::Cqo => self.emit(&[0x48, 0x99]),
+            Inst::Idiv(Operand::Reg(src)) => {
+                self.rex(true, 0, src.number());
+                self.emit(&[0xf7]);
+                self.modrm_reg(7, src.number());
+            }
+            Inst::And(dst, src) => self.alu(0x21, 4, dst, src),
+            Inst::Or(dst, src) => self.alu(0x09, 1, dst, src),
+            Inst::Shr(Operand::Reg(dst), Operand::Imm(count)) => {
+                self.rex(true, 0, dst.number());
+                self.emit(&[0xc1]);
+                self.modrm_reg(5, dst.number());
+                self.emit(&count.to_le_bytes()[..1]);
+            }
+            // movq, movdのregはxmmレジスタ
+            Inst::Movq(dst @ Operand::Xmm(_), src @ Operand::Reg(_)) => {
+                self.sse(Some(0x66), true, 0x6e, dst, src);
+            }
+            Inst::Movq(dst @ Operand::Reg(_), src @ Operand::Xmm(_)) => {
+                self.sse(Some(0x66), true, 0x7e, src, dst);
+            }
+            Inst::Movd(dst @ Operand::Xmm(_), src @ Operand::Reg32(_)) => {
+                self.sse(Some(0x66), false, 0x6e, dst, src);
+            }
+            Inst::Movd(dst @ Operand::Reg32(_), src @ Operand::Xmm(_)) => {
+                self.sse(Some(0x66), false, 0x7e, src, dst);
+            }
+            Inst::Addf(prec, dst, src) => self.sse(Some(prec_prefix(*prec)), false, 0x58, dst, src),
+            Inst::Subf(prec, dst, src) => self.sse(Some(prec_prefix(*prec)), false, 0x5c, dst, src),
+            Inst::Mulf(prec, dst, src) => self.sse(Some(prec_prefix(*prec)), false, 0x59, dst, src),
+            Inst::Divf(prec, dst, src) => self.sse(Some(prec_prefix(*prec)), false, 0x5e, dst, src),
+            Inst::Ucomi(Prec::Single, dst, src) => self.sse(None, false, 0x2e, dst, src),
+            Inst::Ucomi(Prec::Double, dst, src) => self.sse(Some(0x66), false, 0x2e, dst, src),
+            Inst::Cvtsi2f(prec, dst, src) => {
+                self.sse(Some(prec_prefix(*prec)), true, 0x2a, dst, src);
+            }
+            Inst::Cvttf2si(prec, dst, src) => {
+                self.sse(Some(prec_prefix(*prec)), true, 0x2c, dst, src);
+            }
+            // プレフィックスは変換元の精度を表す
+            Inst::Cvtf2f(Prec::Single, dst, src) => self.sse(Some(0xf2), false, 0x5a, dst, src),
+            Inst::Cvtf2f(Prec::Double, dst, src) => self.sse(Some(0xf3), false, 0x5a, dst, src),
+            Inst::Div(Operand::Reg(src)) => {
+                self.rex(true, 0, src.number());
+                self.emit(&[0xf7]);
+                self.modrm_reg(6, src.number());
+            }
+            Inst::Set(cond, Operand::Reg8(dst)) => {
+                // spl, bpl, sil, dilを指定するにはREXプレフィックスが必要
+                if dst.number() >= 4 {
+                    self.rex(false, 0, dst.number());
+                }
+                self.emit(&[0x0f, 0x90 + cond_code(*cond)]);
+                self.modrm_reg(0, dst.number());
+            }
+            Inst::Jmp(label) => {
+                self.emit(&[0xe9]);
+                self.fixup(label);
+            }
+            Inst::Jcc(cond, label) => {
+                self.emit(&[0x0f, 0x80 + cond_code(*cond)]);
+                self.fixup(label);
+            }
+            Inst::Call(name) => {
+                self.emit(&[0xe8]);
+                self.calls.push((self.text.len(), name.clone()));
+                self.emit(&[0; 4]);
+            }
+            Inst::Ret => self.emit(&[0xc3]),
+            _ => unsupported(),
+        }
+    }
+}
+
+fn unsupported() -> ! {
+    panic!("組み込みアセンブラが対応していない命令です。プログラムを終了します。");
+}
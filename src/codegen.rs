@@ -1,268 +1,559 @@
+use std::convert::TryFrom;
+
+use crate::kind::Case;
 use crate::kind::Kind;
 use crate::kind::Node;
+use crate::kind::Obj;
+use crate::kind::Program;
+use crate::kind::Reloc;
+use crate::types::Type;
 
-//構文木からアセンブリコードを生成
-pub fn codegen(nodes: Vec<Node>) {
-    // アセンブリの前半部分を出力
-    println!(".intel_syntax noprefix");
-    println!(".globl main");
-    println!("main:");
+//ターゲットアーキテクチャごとのコード生成
+//構文木はスタックマシンの操作に分解され、各ターゲットがそれをアセンブリに変換する
+pub trait Target {
+    //グローバル変数のシンボルと初期値(alignはアラインメント、is_staticはローカルなシンボルにするか)
+    //relocsの位置には、初期値の代わりに他のグローバル変数のアドレスを8バイトで書き込む
+    fn data(&mut self, name: &str, align: usize, init: &[u8], relocs: &[Reloc], is_static: bool);
+    //関数のシンボルとプロローグ(stack_sizeはローカル変数の領域のバイト数、is_staticはローカルなシンボルにするか)
+    fn prologue(&mut self, name: &str, stack_size: usize, is_static: bool);
+    //引数のレジスタの値を、フレームの先頭からoffsetバイト下にあるty型の仮引数に書き込む
+    //indexは整数と浮動小数点数のそれぞれのレジスタの中での番号
+    fn store_param(&mut self, index: usize, offset: usize, ty: &Type);
+    //可変長引数の関数で、引数のレジスタをすべてフレームの先頭からoffsetバイト下にあるレジスタ保存領域に書き込む
+    //整数のレジスタを8バイトずつ並べ、その後に浮動小数点数のレジスタを並べる
+    fn save_va_area(&mut self, offset: usize);
+    //レジスタ保存領域の配置(整数の引数のレジスタの数, 浮動小数点数のレジスタ1つ分のバイト数)
+    //可変長引数の浮動小数点数を整数のレジスタで渡すターゲットではバイト数をNoneとする
+    fn va_layout(&self) -> (usize, Option<usize>);
+    //エピローグ(returnのジャンプ先を含む)
+    fn epilogue(&mut self);
+    //即値をプッシュする(浮動小数点数はビット列を渡す)
+    fn push_num(&mut self, value: i64);
+    //フレームの先頭からoffsetバイト下にあるローカル変数のアドレスをプッシュする
+    fn push_local_address(&mut self, offset: usize);
+    //グローバル変数のアドレスをプッシュする
+    fn push_global_address(&mut self, name: &str);
+    //呼び出し元がスタックで渡した引数の先頭のアドレスをプッシュする
+    fn push_stack_args_address(&mut self);
+    //アドレスをポップし、そのアドレスにあるty型の値をプッシュする
+    //8バイト未満の整数は型の符号に合わせて8バイトに拡張する
+    //浮動小数点数はビット列のまま扱い、floatは上位をゼロで埋める
+    //構造体は値を読み込まず、アドレスをそのまま値として扱う
+    fn load(&mut self, ty: &Type);
+    //値とアドレスの順にポップし、アドレスにty型の値を書き込んで、値をプッシュし直す
+    //構造体は値として入力元のアドレスをポップし、メンバをすべてコピーする
+    fn store(&mut self, ty: &Type);
+    //値をポップして戻り値のレジスタに入れる
+    fn pop(&mut self);
+    //右辺、左辺の順に値をポップし、演算結果をプッシュする
+    //tyは両辺の型で、浮動小数点数の演算か、除算と比較を符号付きで行うか符号なしで行うかを決める
+    fn binary(&mut self, kind: &Kind, ty: &Type);
+    //値をポップし、from型からto型に変換してプッシュする
+    //8バイト未満の整数への変換では、型の大きさに切り詰めてから型の符号に合わせて8バイトに拡張する
+    fn cast(&mut self, from: &Type, to: &Type);
+    //ラベルを置く
+    fn label(&mut self, label: &str);
+    //無条件ジャンプ
+    fn jump(&mut self, label: &str);
+    //値をポップし、0であればジャンプする
+    fn jump_if_zero(&mut self, label: &str);
+    //引数をポップして関数を呼び出し、ret型の戻り値をプッシュする(arg_typesは各引数の型)
+    //可変長引数の関数ではnamed_numに名前付きの引数の数を渡す
+    fn call(&mut self, func_name: &str, arg_types: &[Type], named_num: Option<usize>, ret: &Type);
+    //ty型の値をポップして戻り値とし、エピローグへジャンプする
+    fn ret(&mut self, ty: &Type);
+    //生成したアセンブリを文字列で返す
+    fn assembly(&self) -> String;
+}
 
-    // プロローグ
-    // 変数26個分の領域を確保する
-    println!("  push rbp");
-    println!("  mov rbp, rsp");
-    println!("  sub rsp, 208"); // 8byte * 26
+//構文木からアセンブリコードを生成
+pub fn codegen(program: Program, target: &mut dyn Target) {
+    // グローバル変数
+    for global in &program.globals {
+        target.data(
+            &global.obj.name,
+            global.obj.ty.align(),
+            &global.init,
+            &global.relocs,
+            global.is_static,
+        );
+    }
 
     // ラベルに一意に付与する番号
     let mut labelseq: usize = 0;
 
-    for node in nodes {
-        //文単位で生成
-        labelseq = gen(Some(Box::new(node)), labelseq);
-        // 式の評価結果としてスタックに一つの値が残っている
-        // はずなので、スタックが溢れないようにポップしておく
-        println!("  pop rax");
-    }
+    for function in program.functions {
+        // プロローグ
+        // ローカル変数の領域を確保する
+        target.prologue(&function.name, function.stack_size, function.is_static);
+        // 可変長引数の関数では、va_argで読めるように引数のレジスタをすべて書き込んでおく
+        if let Some(offset) = function.va_area {
+            target.save_va_area(offset);
+        }
+        // レジスタで渡された引数を仮引数の領域に書き込む
+        // 整数と浮動小数点数は別々のレジスタで順に渡される
+        let (mut gp, mut fp) = (0, 0);
+        for param in &function.params {
+            let index = if param.ty.is_flonum() {
+                &mut fp
+            } else {
+                &mut gp
+            };
+            target.store_param(*index, param.offset.unwrap(), &param.ty);
+            *index += 1;
+        }
+
+        for node in function.body {
+            //文単位で生成
+            labelseq = gen_stmt(node, labelseq, None, target);
+        }
 
-    // エピローグ
-    // 最後の式の結果がRAXに残っているのでそれが返り値になる
-    println!(".Lreturn:");
-    println!("  mov rsp, rbp");
-    println!("  pop rbp");
-    println!("  ret");
+        // エピローグ
+        // 最後の式文の結果が戻り値のレジスタに残っているのでそれが返り値になる
+        target.epilogue();
+    }
 }
 
 // 文の処理
-#[allow(clippy::too_many_lines)]
-fn gen(node: Option<Box<Node>>, mut labelseq: usize) -> usize {
-    let node = *node.unwrap();
+// 文の実行前後でスタックの深さは変わらない
+// brkはbreakで抜ける最も内側のループかswitch文のラベル番号
+fn gen_stmt(node: Node, mut labelseq: usize, brk: Option<usize>, target: &mut dyn Target) -> usize {
     match node.kind {
-        Kind::Num(numbers) => {
-            print!("  push ");
-            //数値を出力
-            for number in numbers {
-                print!("{number}");
-            }
-            println!();
-            //構文木の末尾のノードなので関数終了
-            return labelseq;
-        }
         // {}の中
         Kind::CurlyBracOpen => {
             if node.lhs.is_none() {
                 return labelseq;
             }
-            labelseq = gen(node.lhs, labelseq);
-            return gen(node.rhs, labelseq);
+            labelseq = gen_stmt(*node.lhs.unwrap(), labelseq, brk, target);
+            gen_stmt(*node.rhs.unwrap(), labelseq, brk, target)
         }
         Kind::Return => {
-            labelseq = gen(node.lhs, labelseq);
-            println!("  pop rax");
-            println!("  jmp .Lreturn");
-            return labelseq;
-        }
-        Kind::FunCall(func_name, args) => {
-            // 引数の入るレジスタ
-            let arg_register = ["rdi", "rsi", "rdx", "rcx", "r8", "r9"];
-            // 引数がある場合
-            if let Some(args) = args {
-                let args_num: usize = if args.len() > arg_register.len() {
-                    panic!(
-                        "引数はレジスタの数である{}個以下にして下さい。プログラムを終了します。",
-                        arg_register.len()
-                    );
-                } else {
-                    args.len()
-                };
-                // 各引数を評価
-                for arg in args {
-                    labelseq = gen(Some(Box::new(arg)), labelseq);
-                }
-                if args_num >= 1 {
-                    // 順番に注意
-                    for i in (0..args_num).rev() {
-                        println!("  pop {}", arg_register[i]);
-                    }
-                }
-            }
-            let seq = labelseq;
-            labelseq += 1;
-            // We need to align RSP to a 16 byte boundary before
-            // calling a function because it is an ABI requirement.
-            // RAX is set to 0 for variadic function.
-            // スタックポインタが16の倍数か確認
-            println!("  mov rax, rsp");
-            println!("  and rax, 15");
-
-            // if (スタックポインタが16の倍数)
-            println!("  jnz .Lcall{seq}");
-            // {
-            println!("  mov rax, 0");
-            println!("  call {func_name}");
-            println!("  jmp .Lend{seq}",);
-            // } else {
-            println!(".Lcall{seq}:");
-            println!("  sub rsp, 8");
-            println!("  mov rax, 0");
-            println!("  call {func_name}");
-            println!("  push rax");
-            println!("  add rsp, 8");
-            // }
-            println!(".Lend{seq}:");
-            println!(" push rax");
-            return labelseq;
+            let ty = node.lhs.as_ref().unwrap().ty.clone().unwrap();
+            labelseq = gen(node.lhs, labelseq, target);
+            target.ret(&ty);
+            labelseq
         }
         Kind::If(node_cond) => {
             // この関数内でのみ使うラベル番号(ラベル番号を使うすべてのgen関数のラベル番号に対して一意)
             let seq = labelseq;
             // ラベル番号更新
             labelseq += 1;
-            if node.rhs.is_some() {
+            if let Some(node_else) = node.rhs {
                 // else文がある場合
                 // 条件式
-                labelseq = gen(node_cond, labelseq);
-                println!("  pop rax");
-                println!("  cmp rax, 0");
-                println!("  je  .Lelse{seq}");
+                labelseq = gen_cond(node_cond, labelseq, target);
+                target.jump_if_zero(&format!(".Lelse{seq}"));
                 // then式
-                labelseq = gen(node.lhs, labelseq);
-                println!("  jmp .Lend{seq}");
-                println!(".Lelse{seq}:");
+                labelseq = gen_stmt(*node.lhs.unwrap(), labelseq, brk, target);
+                target.jump(&format!(".Lend{seq}"));
+                target.label(&format!(".Lelse{seq}"));
                 // else式
-                labelseq = gen(node.rhs, labelseq);
-                println!(".Lend{seq}:");
+                labelseq = gen_stmt(*node_else, labelseq, brk, target);
+                target.label(&format!(".Lend{seq}"));
             } else {
                 // else文がない場合(rhsがNoneの場合)
                 // 条件式
-                labelseq = gen(node_cond, labelseq);
-                println!("  pop rax");
-                println!("  cmp rax, 0");
-                println!("  je  .Lend{seq}");
+                labelseq = gen_cond(node_cond, labelseq, target);
+                target.jump_if_zero(&format!(".Lend{seq}"));
                 // then式
-                labelseq = gen(node.lhs, labelseq);
-                println!(".Lend{seq}:");
+                labelseq = gen_stmt(*node.lhs.unwrap(), labelseq, brk, target);
+                target.label(&format!(".Lend{seq}"));
             }
-            return labelseq;
+            labelseq
         }
         Kind::While(node_cond) => {
             // この関数内でのみ使うラベル番号(ラベル番号を使うすべてのgen関数のラベル番号に対して一意)
             let seq = labelseq;
             // ラベル番号更新
             labelseq += 1;
-            println!(".Lbegin{seq}:");
+            target.label(&format!(".Lbegin{seq}"));
             // 条件式
-            labelseq = gen(node_cond, labelseq);
-            println!("  pop rax");
-            println!("  cmp rax, 0");
-            println!("  je  .Lend{seq}");
+            labelseq = gen_cond(node_cond, labelseq, target);
+            target.jump_if_zero(&format!(".Lend{seq}"));
             // then式
-            labelseq = gen(node.lhs, labelseq);
-            println!("  jmp .Lbegin{seq}");
-            println!(".Lend{seq}:");
-            return labelseq;
+            labelseq = gen_stmt(*node.lhs.unwrap(), labelseq, Some(seq), target);
+            target.jump(&format!(".Lbegin{seq}"));
+            target.label(&format!(".Lend{seq}"));
+            labelseq
         }
         Kind::For(node_init, node_cond, node_inc) => {
             // この関数内でのみ使うラベル番号(ラベル番号を使うすべてのgen関数のラベル番号に対して一意)
             let seq = labelseq;
             // ラベル番号更新
             labelseq += 1;
-            if node_init.is_some() {
-                // 存在すれば初期化処理
-                labelseq = gen(node_init, labelseq);
+            if let Some(node_init) = node_init {
+                // 存在すれば初期化処理(変数の宣言の場合もある)
+                labelseq = gen_stmt(*node_init, labelseq, brk, target);
             }
-            println!(".Lbegin{seq}:");
+            target.label(&format!(".Lbegin{seq}"));
             if node_cond.is_some() {
                 // 存在すれば条件式
-                labelseq = gen(node_cond, labelseq);
-                println!("  pop rax");
-                println!("  cmp rax, 0");
-                println!("  je  .Lend{seq}");
+                labelseq = gen_cond(node_cond, labelseq, target);
+                target.jump_if_zero(&format!(".Lend{seq}"));
             }
             // 条件式が真の場合のthen式
-            labelseq = gen(node.lhs, labelseq);
+            labelseq = gen_stmt(*node.lhs.unwrap(), labelseq, Some(seq), target);
             if node_inc.is_some() {
                 // 存在すれば変化式
-                labelseq = gen(node_inc, labelseq);
+                labelseq = gen(node_inc, labelseq, target);
+                target.pop();
             }
-            println!("  jmp .Lbegin{seq}");
-            println!(".Lend{seq}:");
-            return labelseq;
+            target.jump(&format!(".Lbegin{seq}"));
+            target.label(&format!(".Lend{seq}"));
+            labelseq
+        }
+        Kind::Switch(node_cond, tmp, cases) => gen_switch(node_cond, &tmp, cases, labelseq, target),
+        Kind::Break => {
+            target.jump(&format!(".Lend{}", brk.unwrap()));
+            labelseq
+        }
+        // 式文
+        _ => {
+            labelseq = gen(Some(Box::new(node)), labelseq, target);
+            // 式の評価結果としてスタックに一つの値が残っているのでポップしておく
+            // 最後に評価した式文の値が戻り値のレジスタに残る
+            target.pop();
+            labelseq
         }
+    }
+}
 
-        Kind::Var(ident) => {
-            //指定された変数のアドレスをスタックにプッシュする
-            push_var_address(ident);
+// 条件式の処理
+// 浮動小数点数は0と比較した結果にする(-0.0も偽になる)
+fn gen_cond(node: Option<Box<Node>>, mut labelseq: usize, target: &mut dyn Target) -> usize {
+    let ty = node.as_ref().unwrap().ty.clone().unwrap();
+    labelseq = gen(node, labelseq, target);
+    if ty.is_flonum() {
+        target.push_num(0);
+        target.binary(&Kind::NoEqual, &ty);
+    }
+    labelseq
+}
+
+// 式の処理
+// 評価結果の値を一つスタックにプッシュする
+fn gen(node: Option<Box<Node>>, mut labelseq: usize, target: &mut dyn Target) -> usize {
+    let node = *node.unwrap();
+    match node.kind {
+        Kind::Num(value, _) => {
+            //数値をプッシュ
+            target.push_num(value);
+            //構文木の末尾のノードなので関数終了
+            return labelseq;
+        }
+        Kind::FNum(value, ty) => {
+            //ビット列をプッシュ
+            #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+            let bits = match ty {
+                Type::Float => i64::from((value as f32).to_bits()),
+                _ => value.to_bits() as i64,
+            };
+            target.push_num(bits);
+            return labelseq;
+        }
+        Kind::FunCall(func_name, args, named_num) => {
+            let mut arg_types = Vec::new();
+            // 引数がある場合
+            if let Some(args) = args {
+                // 各引数を評価
+                for arg in args {
+                    arg_types.push(arg.ty.clone().unwrap());
+                    labelseq = gen(Some(Box::new(arg)), labelseq, target);
+                }
+            }
+            let ty = node.ty.unwrap();
+            target.call(&func_name, &arg_types, named_num, &ty);
+            // 戻り値のレジスタの上位ビットは不定なので戻り値の型に揃える
+            if ty.is_integer() && ty.size() < 8 {
+                target.cast(&Type::Long, &ty);
+            }
+            return labelseq;
+        }
+        Kind::Var(_) | Kind::Deref | Kind::Member(_) => {
+            //変数のアドレスをスタックにプッシュする
+            let ty = node.ty.clone().unwrap();
+            labelseq = gen_addr(node, labelseq, target);
             //変数の中身の値をスタックにプッシュする
-            println!("  pop rax");
-            println!("  mov rax, [rax]");
-            println!("  push rax");
+            target.load(&ty);
             //構文木の末尾のノードなので関数終了
             return labelseq;
         }
-        Kind::Assign => {
-            if let Kind::Var(ident) = (node.lhs).as_ref().unwrap().kind {
-                //指定された変数のアドレスをスタックにプッシュする
-                push_var_address(ident);
-                //右辺の値を計算
-                gen(node.rhs, labelseq);
-                //変数に右辺の値を代入
-                println!("  pop rdi");
-                println!("  pop rax");
-                println!("  mov [rax], rdi");
-                println!("  push rdi");
-                //代入式が終わったので関数終了
-                return labelseq;
+        Kind::Addr => return gen_addr(*node.lhs.unwrap(), labelseq, target),
+        Kind::Cast => {
+            let from = node.lhs.as_ref().unwrap().ty.clone().unwrap();
+            let to = node.ty.unwrap();
+            labelseq = gen(node.lhs, labelseq, target);
+            if !from.is_same(&to) {
+                target.cast(&from, &to);
             }
-            panic!("式の左辺に変数以外があります。プログラムを終了します。");
+            return labelseq;
+        }
+        Kind::VaStart(tmp, offset, gp, fp) => {
+            return gen_va_start(node.lhs, &tmp, offset, gp, fp, labelseq, target);
+        }
+        Kind::VaArg(tmp) => return gen_va_arg(node.lhs, &tmp, &node.ty.unwrap(), labelseq, target),
+        Kind::Assign => {
+            let ty = node.ty.unwrap();
+            //左辺のアドレスをスタックにプッシュする
+            labelseq = gen_addr(*node.lhs.unwrap(), labelseq, target);
+            //右辺の値を計算
+            labelseq = gen(node.rhs, labelseq, target);
+            //左辺に右辺の値を代入
+            target.store(&ty);
+            //代入式が終わったので関数終了
+            return labelseq;
+        }
+        Kind::CurlyBracOpen
+        | Kind::Return
+        | Kind::If(_)
+        | Kind::While(_)
+        | Kind::For(..)
+        | Kind::Switch(..)
+        | Kind::Break => {
+            panic!("式があるべき箇所に文があります。プログラムを終了します。")
         }
         //ノードが上記に当てはまらない場合のみ以降の処理に進む
         _ => (),
     }
     //ノードが演算子だった場合
-    labelseq = gen(node.lhs, labelseq);
-    labelseq = gen(node.rhs, labelseq);
+    let ty = node.lhs.as_ref().unwrap().ty.clone().unwrap();
+    labelseq = gen(node.lhs, labelseq, target);
+    labelseq = gen(node.rhs, labelseq, target);
+    target.binary(&node.kind, &ty);
+    // 算術演算は8バイトで行うので、8バイト未満の型では結果を型の大きさに収める
+    // (比較の結果は0か1なのでそのままでよい)
+    let is_arith = matches!(node.kind, Kind::Add | Kind::Sub | Kind::Mul | Kind::Div);
+    if is_arith && ty.is_integer() && ty.size() < 8 {
+        target.cast(&Type::Long, &ty);
+    }
+    labelseq
+}
 
-    println!("  pop rdi");
-    println!("  pop rax");
-    match node.kind {
-        Kind::Add => println!("  add rax, rdi"),
-        Kind::Sub => println!("  sub rax, rdi"),
-        Kind::Mul => println!("  imul rax, rdi"),
-        Kind::Div => {
-            println!("  cqo");
-            println!("  idiv rdi");
-        }
-        Kind::Equal => {
-            println!("  cmp rax, rdi");
-            println!("  sete al");
-            println!("  movzb rax, al");
-        }
-        Kind::NoEqual => {
-            println!("  cmp rax, rdi");
-            println!("  setne al");
-            println!("  movzb rax, al");
+// switch文の処理
+fn gen_switch(
+    node_cond: Option<Box<Node>>,
+    tmp: &Obj,
+    cases: Vec<Case>,
+    mut labelseq: usize,
+    target: &mut dyn Target,
+) -> usize {
+    // この関数内でのみ使うラベル番号(ラベル番号を使うすべてのgen関数のラベル番号に対して一意)
+    let seq = labelseq;
+    // ラベル番号更新
+    labelseq += 1;
+    // 条件式の値を一時的な変数に置く
+    push_var_address(tmp, target);
+    labelseq = gen(node_cond, labelseq, target);
+    target.store(&tmp.ty);
+    target.pop();
+    // 値が一致するcaseへジャンプし、どれとも一致しなければdefaultか末尾へジャンプする
+    let mut default = format!(".Lend{seq}");
+    for (index, case) in cases.iter().enumerate() {
+        for label in &case.labels {
+            match label {
+                Some(value) => {
+                    push_var_address(tmp, target);
+                    target.load(&tmp.ty);
+                    target.push_num(*value);
+                    target.binary(&Kind::NoEqual, &tmp.ty);
+                    target.jump_if_zero(&format!(".Lcase{seq}_{index}"));
+                }
+                None => default = format!(".Lcase{seq}_{index}"),
+            }
         }
-        Kind::LowThan => {
-            println!("  cmp rax, rdi");
-            println!("  setl al");
-            println!("  movzb rax, al");
+    }
+    target.jump(&default);
+    // 各caseの文は次のcaseに続けて実行し、breakで末尾へジャンプする
+    for (index, case) in cases.into_iter().enumerate() {
+        target.label(&format!(".Lcase{seq}_{index}"));
+        for node in case.body {
+            labelseq = gen_stmt(node, labelseq, Some(seq), target);
         }
-        Kind::LowEqual => {
-            println!("  cmp rax, rdi");
-            println!("  setle al");
-            println!("  movzb rax, al");
+    }
+    target.label(&format!(".Lend{seq}"));
+    labelseq
+}
+
+//値を書き込める式のアドレスをスタックにプッシュする
+fn gen_addr(node: Node, mut labelseq: usize, target: &mut dyn Target) -> usize {
+    match node.kind {
+        Kind::Var(obj) => push_var_address(&obj, target),
+        // 参照先のアドレスはポインタの値そのもの
+        Kind::Deref => labelseq = gen(node.lhs, labelseq, target),
+        // 構造体のアドレスにメンバのオフセットを足す
+        Kind::Member(member) => {
+            labelseq = gen_addr(*node.lhs.unwrap(), labelseq, target);
+            target.push_num(i64::try_from(member.offset).unwrap());
+            target.binary(&Kind::Add, &Type::Long);
         }
-        _ => panic!("不正なノードがあります。プログラムを終了します。"),
+        _ => panic!("式の左辺に変数以外があります。プログラムを終了します。"),
     }
-    println!("  push rax");
     labelseq
 }
 
 //指定された変数のアドレスをスタックにプッシュする
-fn push_var_address(ident: usize) {
-    println!("  mov rax, rbp");
-    //オフセット値には変数のサイズ(8byte)を考慮する
-    println!("  sub rax, {}", ident * 8);
-    println!("  push rax");
+fn push_var_address(obj: &Obj, target: &mut dyn Target) {
+    //ローカル変数のオフセットは構文解析で変数を宣言したときに決まっている
+    match obj.offset {
+        Some(offset) => target.push_local_address(offset),
+        None => target.push_global_address(&obj.name),
+    }
+}
+// va_startの処理
+// va_listの各メンバを名前付きの引数の次から読むように設定し、0をプッシュする
+fn gen_va_start(
+    lhs: Option<Box<Node>>,
+    tmp: &Obj,
+    offset: usize,
+    gp: usize,
+    fp: usize,
+    mut labelseq: usize,
+    target: &mut dyn Target,
+) -> usize {
+    // 一時変数にva_listのアドレスを入れる
+    labelseq = gen(lhs, labelseq, target);
+    target.pop();
+    // 名前付きの引数の次から読むようにする
+    let (gp_num, fp_size) = target.va_layout();
+    set_va_member(tmp, "gp_offset", target, |target| {
+        target.push_num(i64::try_from(gp * 8).unwrap());
+    });
+    set_va_member(tmp, "fp_offset", target, |target| {
+        let offset = gp_num * 8 + fp * fp_size.unwrap_or(0);
+        target.push_num(i64::try_from(offset).unwrap());
+    });
+    set_va_member(tmp, "overflow_arg_area", target, |target| {
+        target.push_stack_args_address();
+    });
+    set_va_member(tmp, "reg_save_area", target, |target| {
+        target.push_local_address(offset);
+    });
+    target.push_num(0);
+    labelseq
+}
+
+// va_argの処理
+// 次の可変長引数をty型として読み出してプッシュする
+fn gen_va_arg(
+    lhs: Option<Box<Node>>,
+    tmp: &Obj,
+    ty: &Type,
+    mut labelseq: usize,
+    target: &mut dyn Target,
+) -> usize {
+    // 一時変数にva_listのアドレスを入れる
+    labelseq = gen(lhs, labelseq, target);
+    target.pop();
+    let seq = labelseq;
+    labelseq += 1;
+    // 整数とポインタは整数のレジスタ、doubleは浮動小数点数のレジスタで渡される
+    // ただしターゲットによってはdoubleも整数のレジスタで渡される
+    let (gp_num, fp_size) = target.va_layout();
+    let (name, end, size) = match fp_size {
+        Some(fp_size) if ty.is_flonum() => ("fp_offset", gp_num * 8 + 8 * fp_size, fp_size),
+        _ => ("gp_offset", gp_num * 8, 8),
+    };
+    let size = i64::try_from(size).unwrap();
+    // レジスタ保存領域を読み終えていればスタックから読む
+    push_va_member_value(tmp, name, target);
+    target.push_num(i64::try_from(end).unwrap());
+    target.binary(&Kind::LowThan, &Type::UInt);
+    target.jump_if_zero(&format!(".Lva_stack{seq}"));
+    // 一時変数 = reg_save_area + (offset += size) - size
+    set_va_member(tmp, name, target, |target| {
+        push_va_member_value(tmp, name, target);
+        target.push_num(size);
+        target.binary(&Kind::Add, &Type::UInt);
+    });
+    target.push_local_address(tmp.offset.unwrap());
+    push_va_member_value(tmp, "reg_save_area", target);
+    push_va_member_value(tmp, name, target);
+    target.binary(&Kind::Add, &Type::Long);
+    target.push_num(size);
+    target.binary(&Kind::Sub, &Type::Long);
+    target.store(&tmp.ty);
+    target.pop();
+    target.jump(&format!(".Lva_end{seq}"));
+    // 一時変数 = (overflow_arg_area += 8) - 8
+    target.label(&format!(".Lva_stack{seq}"));
+    set_va_member(tmp, "overflow_arg_area", target, |target| {
+        push_va_member_value(tmp, "overflow_arg_area", target);
+        target.push_num(8);
+        target.binary(&Kind::Add, &Type::Long);
+    });
+    target.push_local_address(tmp.offset.unwrap());
+    push_va_member_value(tmp, "overflow_arg_area", target);
+    target.push_num(8);
+    target.binary(&Kind::Sub, &Type::Long);
+    target.store(&tmp.ty);
+    target.pop();
+    // 一時変数が指す引数を読み込む
+    target.label(&format!(".Lva_end{seq}"));
+    target.push_local_address(tmp.offset.unwrap());
+    target.load(&tmp.ty);
+    target.load(ty);
+    labelseq
+}
+
+//一時変数tmpが指すva_listのメンバのアドレスをプッシュし、メンバの型を返す
+fn push_va_member(tmp: &Obj, name: &str, target: &mut dyn Target) -> Type {
+    let member = match tmp.ty.base() {
+        Some(Type::Struct(st)) => st.member(name),
+        _ => unreachable!(),
+    };
+    target.push_local_address(tmp.offset.unwrap());
+    target.load(&tmp.ty);
+    target.push_num(i64::try_from(member.offset).unwrap());
+    target.binary(&Kind::Add, &Type::Long);
+    member.ty
+}
+
+//一時変数tmpが指すva_listのメンバの値をプッシュする
+fn push_va_member_value(tmp: &Obj, name: &str, target: &mut dyn Target) {
+    let ty = push_va_member(tmp, name, target);
+    target.load(&ty);
+}
+
+//一時変数tmpが指すva_listのメンバに、valueがプッシュする値を書き込む
+fn set_va_member(
+    tmp: &Obj,
+    name: &str,
+    target: &mut dyn Target,
+    value: impl FnOnce(&mut dyn Target),
+) {
+    let ty = push_va_member(tmp, name, target);
+    value(target);
+    target.store(&ty);
+    target.pop();
+}
+
+//可変長引数の関数のレジスタ保存領域のバイト数
+//x86-64の整数のレジスタ6個と浮動小数点数のレジスタ8個(1個16バイト)が最も大きい
+pub const VA_AREA_SIZE: usize = 176;
+
+//グローバル変数の初期値を、アドレスを書き込む位置で区切る
+//(バイト列, その直後に書き込むアドレス)の列を返し、最後のバイト列の後にはアドレスを書き込まない
+pub fn split_data<'a>(init: &'a [u8], relocs: &'a [Reloc]) -> Vec<(&'a [u8], Option<&'a Reloc>)> {
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    for reloc in relocs {
+        pieces.push((&init[start..reloc.offset], Some(reloc)));
+        start = reloc.offset + 8;
+    }
+    pieces.push((&init[start..], None));
+    pieces
+}
+
+//アドレスを書き込む位置に置く式(グローバル変数の名前+オフセット)
+pub fn reloc_expr(label: &str, addend: i64) -> String {
+    if addend == 0 {
+        label.to_string()
+    } else {
+        format!("{label}{addend:+}")
+    }
+}
+
+//nをalignの倍数に切り上げる
+pub fn align_to(n: usize, align: usize) -> usize {
+    n.div_ceil(align) * align
 }
@@ -0,0 +1,215 @@
+use std::convert::TryFrom;
+
+use crate::kind::Kind;
+use crate::kind::Node;
+use crate::types::Type;
+
+//コンパイル時に定数式を評価する
+//値はインタプリタと同じく8バイトのビット列で表し、浮動小数点数はそのビット列とする
+
+//整数または浮動小数点数の定数式の値を求める
+pub fn eval(node: &Node) -> i64 {
+    match eval_reloc(node) {
+        (None, value) => value,
+        (Some(label), _) => panic!(
+            "グローバル変数{}のアドレスは整数の定数として使えません。プログラムを終了します。",
+            label
+        ),
+    }
+}
+
+//グローバル変数のアドレスを含む定数式の値を、(グローバル変数の名前, アドレスに足す値)として求める
+//アドレスを含まない式の名前はNoneとする
+pub fn eval_reloc(node: &Node) -> (Option<String>, i64) {
+    let ty = node.ty.as_ref().unwrap();
+    match &node.kind {
+        Kind::Num(value, _) => (None, *value),
+        Kind::FNum(value, ty) => (None, from_float(*value, ty)),
+        Kind::Addr => eval_addr(node.lhs.as_ref().unwrap()),
+        Kind::Cast => {
+            let lhs = node.lhs.as_ref().unwrap();
+            match eval_reloc(lhs) {
+                (None, value) => (None, convert(value, lhs.ty.as_ref().unwrap(), ty)),
+                // アドレスは8バイトの整数かポインタにのみ変換できる
+                (Some(label), value) => {
+                    assert!(
+                        ty.size() == 8 && !ty.is_flonum(),
+                        "グローバル変数{}のアドレスを{}バイトの値に変換できません。プログラムを終了します。",
+                        label,
+                        ty.size()
+                    );
+                    (Some(label), value)
+                }
+            }
+        }
+        // ポインタと整数の加減算(整数には指す先の型の大きさを掛けてある)
+        Kind::Add | Kind::Sub if ty.base().is_some() => {
+            let (label, lhs) = eval_reloc(node.lhs.as_ref().unwrap());
+            let rhs = eval(node.rhs.as_ref().unwrap());
+            match node.kind {
+                Kind::Add => (label, lhs.wrapping_add(rhs)),
+                _ => (label, lhs.wrapping_sub(rhs)),
+            }
+        }
+        Kind::Add
+        | Kind::Sub
+        | Kind::Mul
+        | Kind::Div
+        | Kind::Equal
+        | Kind::NoEqual
+        | Kind::LowThan
+        | Kind::LowEqual => {
+            let lhs = node.lhs.as_ref().unwrap();
+            let value = binary(
+                &node.kind,
+                eval(lhs),
+                eval(node.rhs.as_ref().unwrap()),
+                lhs.ty.as_ref().unwrap(),
+                ty,
+            );
+            (None, value)
+        }
+        Kind::Var(obj) => panic!(
+            "変数{}の値は定数ではありません。プログラムを終了します。",
+            obj.name
+        ),
+        Kind::FunCall(func_name, _, _) => panic!(
+            "関数{}の呼び出しは定数ではありません。プログラムを終了します。",
+            func_name
+        ),
+        _ => panic!("定数式ではない式があります。プログラムを終了します。"),
+    }
+}
+
+//&の後の式が表すアドレスを、(グローバル変数の名前, アドレスに足す値)として求める
+fn eval_addr(node: &Node) -> (Option<String>, i64) {
+    match &node.kind {
+        Kind::Var(obj) => {
+            assert!(
+                obj.offset.is_none(),
+                "ローカル変数{}のアドレスは定数ではありません。プログラムを終了します。",
+                obj.name
+            );
+            (Some(obj.name.clone()), 0)
+        }
+        Kind::Member(member) => {
+            let (label, value) = eval_addr(node.lhs.as_ref().unwrap());
+            (label, value + i64::try_from(member.offset).unwrap())
+        }
+        // &*pはpの値とする(&a[i]は&*(a+i)になっている)
+        Kind::Deref => eval_reloc(node.lhs.as_ref().unwrap()),
+        _ => panic!("定数式ではない式があります。プログラムを終了します。"),
+    }
+}
+
+//二項演算の結果を求める
+//lhs_tyは両辺をそろえた型、tyは演算結果の型
+pub fn binary(kind: &Kind, lhs: i64, rhs: i64, lhs_ty: &Type, ty: &Type) -> i64 {
+    if lhs_ty.is_flonum() {
+        return float_binary(kind, lhs, rhs, lhs_ty);
+    }
+    // 符号なしの演算はビット列をそのままu64として扱う
+    #[allow(clippy::cast_sign_loss)]
+    let (ulhs, urhs) = (lhs as u64, rhs as u64);
+    let unsigned = lhs_ty.is_unsigned();
+    let value = match kind {
+        Kind::Add => lhs.wrapping_add(rhs),
+        Kind::Sub => lhs.wrapping_sub(rhs),
+        Kind::Mul => lhs.wrapping_mul(rhs),
+        Kind::Div => {
+            assert!(rhs != 0, "0で除算しました。プログラムを終了します。");
+            if unsigned {
+                #[allow(clippy::cast_possible_wrap)]
+                let value = (ulhs / urhs) as i64;
+                value
+            } else {
+                lhs.wrapping_div(rhs)
+            }
+        }
+        Kind::Equal => i64::from(lhs == rhs),
+        Kind::NoEqual => i64::from(lhs != rhs),
+        Kind::LowThan if unsigned => i64::from(ulhs < urhs),
+        Kind::LowThan => i64::from(lhs < rhs),
+        Kind::LowEqual if unsigned => i64::from(ulhs <= urhs),
+        Kind::LowEqual => i64::from(lhs <= rhs),
+        _ => panic!("不正なノードがあります。プログラムを終了します。"),
+    };
+    // 演算結果を型の大きさに収める
+    if ty.is_integer() {
+        cast(value, ty)
+    } else {
+        value
+    }
+}
+
+//浮動小数点数同士の演算
+#[allow(clippy::float_cmp)]
+fn float_binary(kind: &Kind, lhs: i64, rhs: i64, ty: &Type) -> i64 {
+    let (lhs, rhs) = (to_float(lhs, ty), to_float(rhs, ty));
+    let value = match kind {
+        Kind::Add => lhs + rhs,
+        Kind::Sub => lhs - rhs,
+        Kind::Mul => lhs * rhs,
+        Kind::Div => lhs / rhs,
+        // 比較の結果は整数
+        Kind::Equal => return i64::from(lhs == rhs),
+        Kind::NoEqual => return i64::from(lhs != rhs),
+        Kind::LowThan => return i64::from(lhs < rhs),
+        Kind::LowEqual => return i64::from(lhs <= rhs),
+        _ => panic!("不正なノードがあります。プログラムを終了します。"),
+    };
+    from_float(value, ty)
+}
+
+//from型の値をto型に変換する
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_precision_loss,
+    clippy::cast_sign_loss,
+    clippy::cast_possible_wrap
+)]
+pub fn convert(value: i64, from: &Type, to: &Type) -> i64 {
+    match (from.is_flonum(), to.is_flonum()) {
+        (true, true) => from_float(to_float(value, from), to),
+        // 小数部を切り捨てる(範囲外の値は飽和させる)
+        (true, false) if to.is_unsigned() => cast(to_float(value, from) as u64 as i64, to),
+        (true, false) => cast(to_float(value, from) as i64, to),
+        (false, true) if from.is_unsigned() => from_float(value as u64 as f64, to),
+        (false, true) => from_float(value as f64, to),
+        (false, false) => cast(value, to),
+    }
+}
+
+//ビット列で持っているty型の浮動小数点数を取り出す
+pub fn to_float(value: i64, ty: &Type) -> f64 {
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    match ty {
+        Type::Float => f64::from(f32::from_bits(value as u32)),
+        _ => f64::from_bits(value as u64),
+    }
+}
+
+//浮動小数点数をty型のビット列にする(floatは上位を0で埋める)
+pub fn from_float(value: f64, ty: &Type) -> i64 {
+    #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+    match ty {
+        Type::Float => i64::from((value as f32).to_bits()),
+        _ => value.to_bits() as i64,
+    }
+}
+
+//値をty型の大きさに切り詰め、型の符号に合わせて8バイトに拡張する
+//浮動小数点数は符号なしと同じく0で埋める
+pub fn cast(value: i64, ty: &Type) -> i64 {
+    if ty.size() == 8 {
+        return value;
+    }
+    let shift = 64 - 8 * ty.size();
+    if ty.is_unsigned() || ty.is_flonum() {
+        #[allow(clippy::cast_sign_loss, clippy::cast_possible_wrap)]
+        let value = ((value as u64) << shift >> shift) as i64;
+        value
+    } else {
+        (value << shift) >> shift
+    }
+}
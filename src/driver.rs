@@ -0,0 +1,275 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+
+use crate::aarch64::AArch64;
+use crate::asm::Syntax;
+use crate::assemble::assemble;
+use crate::codegen::{codegen, Target};
+use crate::elf::write_elf;
+use crate::kind::Program;
+use crate::parse::program;
+use crate::preprocess::preprocess;
+use crate::riscv64::RiscV64;
+use crate::tokenize::tokenize;
+use crate::x86_64::X86_64;
+
+//どの段階で処理を止めるか
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Stop {
+    //-E 前処理した結果を出力する
+    Preprocess,
+    //-S アセンブリを出力する
+    Assembly,
+    //-c オブジェクトファイルを出力する
+    Object,
+    //実行ファイルまでリンクする
+    Link,
+}
+
+//複数のファイルを受け取り、アセンブルとリンクまで行うドライバ
+pub struct Driver {
+    pub target_name: String,
+    pub syntax: Syntax,
+    pub stop: Stop,
+    //-o で指定された出力ファイル名
+    pub output: Option<String>,
+    //リンカにそのまま渡す引数(-l, -L, -static)
+    pub link_args: Vec<String>,
+    //-I で指定されたインクルードファイルの検索パス
+    pub include_paths: Vec<String>,
+    //-D で指定されたマクロ
+    pub defines: Vec<String>,
+    //入力ファイル(.c, .s, .o)
+    pub inputs: Vec<String>,
+    //終了時に削除する一時ファイル
+    temps: Vec<PathBuf>,
+}
+
+//入力文字列をコンパイルし、アセンブリコードを返す
+pub fn compile_to_assembly(input: &str, target_name: &str, syntax: Syntax) -> String {
+    let mut target: Box<dyn Target> = match target_name {
+        "x86_64" | "x86-64" => Box::new(X86_64::new(syntax)),
+        "aarch64" | "arm64" => Box::new(AArch64::new()),
+        "riscv64" => Box::new(RiscV64::new()),
+        _ => panic!(
+            "不明なターゲット\"{}\"が指定されました。プログラムを終了します。",
+            target_name
+        ),
+    };
+    //構文木からアセンブリコードを出力
+    codegen(parse_input(input), target.as_mut());
+    target.assembly()
+}
+
+//入力文字列をコンパイルし、組み込みアセンブラでELFのオブジェクトファイルを生成する
+//組み込みアセンブラはx86-64の命令列をそのまま機械語に変換する
+pub fn compile_to_object(input: &str, syntax: Syntax) -> Vec<u8> {
+    let mut target = X86_64::new(syntax);
+    codegen(parse_input(input), &mut target);
+    write_elf(&assemble(target.insts()))
+}
+
+//入力文字列をトークナイズして構文木を生成する
+pub fn parse_input(input: &str) -> Program {
+    //引数の文字列をトークナイズする
+    let tokens = tokenize(&mut input.chars());
+    //トークン列が空(入力が空)ならばエラー
+    assert!(
+        !tokens.is_empty(),
+        "入力がありません。プログラムを終了します。"
+    );
+    // トークン列から構文木を生成
+    program(&tokens)
+}
+
+//ドライバが扱う入力ファイルかどうかを拡張子で判定する
+pub fn is_input_file(arg: &str) -> bool {
+    matches!(
+        Path::new(arg).extension().and_then(|ext| ext.to_str()),
+        Some("c" | "s" | "o")
+    )
+}
+
+impl Driver {
+    pub fn new(target_name: String, syntax: Syntax) -> Self {
+        Driver {
+            target_name,
+            syntax,
+            stop: Stop::Link,
+            output: None,
+            link_args: Vec::new(),
+            include_paths: Vec::new(),
+            defines: Vec::new(),
+            inputs: Vec::new(),
+            temps: Vec::new(),
+        }
+    }
+
+    //入力ファイルを順に処理し、必要ならリンクする
+    pub fn run(&mut self) {
+        assert!(
+            self.target_name != "wasm32",
+            "wasm32ではドライバを使えません。プログラムを終了します。"
+        );
+        assert!(
+            self.output.is_none() || self.stop == Stop::Link || self.inputs.len() == 1,
+            "-E, -S, -cで複数の入力ファイルがある場合は-oを指定できません。プログラムを終了します。"
+        );
+
+        let inputs = self.inputs.clone();
+        let mut objects = Vec::new();
+        for input in &inputs {
+            let path = Path::new(input);
+            match path.extension().and_then(|ext| ext.to_str()) {
+                Some("c") => {
+                    let source = fs::read_to_string(path).unwrap_or_else(|_| {
+                        panic!(
+                            "ファイル{}を読み込めません。プログラムを終了します。",
+                            input
+                        )
+                    });
+                    let source =
+                        preprocess(&source, Some(path), &self.include_paths, &self.defines);
+                    match self.stop {
+                        Stop::Preprocess => match &self.output {
+                            Some(output) => write_file(Path::new(output), source.as_bytes()),
+                            None => print!("{source}"),
+                        },
+                        Stop::Assembly => {
+                            let asm = compile_to_assembly(&source, &self.target_name, self.syntax);
+                            let output = self.output_path(path, "s");
+                            write_file(&output, asm.as_bytes());
+                        }
+                        Stop::Object => {
+                            let output = self.output_path(path, "o");
+                            self.compile_object(&source, &output);
+                        }
+                        Stop::Link => {
+                            let output = self.temp_path(path, "o");
+                            self.compile_object(&source, &output);
+                            objects.push(output);
+                        }
+                    }
+                }
+                Some("s") => match self.stop {
+                    // アセンブリは前処理しない
+                    Stop::Preprocess | Stop::Assembly => (),
+                    Stop::Object => {
+                        let output = self.output_path(path, "o");
+                        self.assemble_file(path, &output);
+                    }
+                    Stop::Link => {
+                        let output = self.temp_path(path, "o");
+                        self.assemble_file(path, &output);
+                        objects.push(output);
+                    }
+                },
+                // オブジェクトファイルはリンク時にそのまま渡す
+                _ => objects.push(path.to_path_buf()),
+            }
+        }
+
+        if self.stop == Stop::Link {
+            let output = self.output.clone().unwrap_or_else(|| "a.out".to_string());
+            let mut command = Command::new(self.cc());
+            command
+                .arg("-o")
+                .arg(output)
+                .args(&objects)
+                .args(&self.link_args);
+            run_command(&mut command);
+        }
+    }
+
+    //.cファイルの中身をコンパイルしてオブジェクトファイルを出力する
+    fn compile_object(&mut self, source: &str, output: &Path) {
+        if matches!(self.target_name.as_str(), "x86_64" | "x86-64") {
+            write_file(output, &compile_to_object(source, self.syntax));
+        } else {
+            // x86-64以外はアセンブリを経由してシステムのアセンブラに渡す
+            let asm = compile_to_assembly(source, &self.target_name, self.syntax);
+            let asm_path = self.temp_path(output, "s");
+            write_file(&asm_path, asm.as_bytes());
+            self.assemble_file(&asm_path, output);
+        }
+    }
+
+    //アセンブリファイルをシステムのアセンブラでオブジェクトファイルにする
+    fn assemble_file(&self, input: &Path, output: &Path) {
+        let mut command = Command::new(self.cc());
+        command.arg("-c").arg("-o").arg(output).arg(input);
+        run_command(&mut command);
+    }
+
+    //ターゲットに対応するgccドライバ
+    fn cc(&self) -> &'static str {
+        match self.target_name.as_str() {
+            "x86_64" | "x86-64" => "cc",
+            "aarch64" | "arm64" => "aarch64-linux-gnu-gcc",
+            "riscv64" => "riscv64-linux-gnu-gcc",
+            _ => panic!(
+                "不明なターゲット\"{}\"が指定されました。プログラムを終了します。",
+                self.target_name
+            ),
+        }
+    }
+
+    //-oが無ければ入力ファイル名の拡張子を変えてカレントディレクトリに出力する
+    fn output_path(&self, input: &Path, ext: &str) -> PathBuf {
+        match &self.output {
+            Some(output) => PathBuf::from(output),
+            None => PathBuf::from(input.file_name().unwrap()).with_extension(ext),
+        }
+    }
+
+    //一時ファイルの名前を決める
+    //終了時に削除するために記録しておく
+    fn temp_path(&mut self, input: &Path, ext: &str) -> PathBuf {
+        let stem = input.file_stem().unwrap().to_string_lossy();
+        let name = format!(
+            "nineccr-{}-{}-{}.{}",
+            process::id(),
+            self.temps.len(),
+            stem,
+            ext
+        );
+        let path = env::temp_dir().join(name);
+        self.temps.push(path.clone());
+        path
+    }
+}
+
+impl Drop for Driver {
+    //エラーで終了する場合も一時ファイルを削除する
+    fn drop(&mut self) {
+        for temp in &self.temps {
+            let _ = fs::remove_file(temp);
+        }
+    }
+}
+
+fn write_file(path: &Path, bytes: &[u8]) {
+    fs::write(path, bytes).unwrap_or_else(|_| {
+        panic!(
+            "ファイル{}に書き込めません。プログラムを終了します。",
+            path.display()
+        )
+    });
+}
+
+//外部コマンドを実行し、失敗したらエラーにする
+fn run_command(command: &mut Command) {
+    let status = command.status().unwrap_or_else(|_| {
+        panic!(
+            "{}を実行できません。プログラムを終了します。",
+            command.get_program().to_string_lossy()
+        )
+    });
+    assert!(
+        status.success(),
+        "{}が失敗しました。プログラムを終了します。",
+        command.get_program().to_string_lossy()
+    );
+}
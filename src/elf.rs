@@ -0,0 +1,329 @@
+use std::convert::TryFrom;
+
+//オブジェクトファイルのセクション
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Section {
+    Text,   // .text 機械語
+    Data,   // .data 書き込み可能なデータ
+    Rodata, // .rodata 読み込み専用のデータ
+}
+
+//シンボル
+pub struct Symbol {
+    pub name: String,
+    //定義されているセクション(Noneは外部で定義されたシンボル)
+    pub section: Option<Section>,
+    //セクション先頭からのオフセット
+    pub offset: usize,
+    //他のオブジェクトファイルから参照できるか
+    pub global: bool,
+}
+
+//再配置の種類
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum RelocKind {
+    Plt32, // R_X86_64_PLT32 (関数呼び出し)
+    Pc32,  // R_X86_64_PC32 (rip相対のデータの参照)
+    Abs64, // R_X86_64_64 (データに書き込むアドレス)
+}
+
+//再配置
+pub struct Relocation {
+    //再配置する値のあるセクションとオフセット
+    pub section: Section,
+    pub offset: usize,
+    //参照するシンボルのSymbolsでの添字
+    pub symbol: usize,
+    pub kind: RelocKind,
+    pub addend: i64,
+}
+
+//ELFファイルに書き出す内容
+pub struct Object {
+    pub text: Vec<u8>,
+    pub data: Vec<u8>,
+    pub rodata: Vec<u8>,
+    pub symbols: Vec<Symbol>,
+    pub relocations: Vec<Relocation>,
+}
+
+// セクションヘッダの種類
+const SHT_PROGBITS: u32 = 1;
+const SHT_SYMTAB: u32 = 2;
+const SHT_STRTAB: u32 = 3;
+const SHT_RELA: u32 = 4;
+// セクションヘッダのフラグ
+const SHF_WRITE: u64 = 0x1;
+const SHF_ALLOC: u64 = 0x2;
+const SHF_EXECINSTR: u64 = 0x4;
+const SHF_INFO_LINK: u64 = 0x40;
+// シンボルの種類と結合
+const STB_LOCAL: u8 = 0;
+const STB_GLOBAL: u8 = 1;
+const STT_NOTYPE: u8 = 0;
+const STT_SECTION: u8 = 3;
+
+//セクションヘッダ1つ分
+struct SectionHeader {
+    name: u32,
+    kind: u32,
+    flags: u64,
+    offset: usize,
+    size: usize,
+    link: u32,
+    info: u32,
+    align: u64,
+    entsize: u64,
+}
+
+//文字列テーブル
+struct StrTab {
+    bytes: Vec<u8>,
+}
+
+impl StrTab {
+    fn new() -> Self {
+        // 先頭は空文字列
+        StrTab { bytes: vec![0] }
+    }
+
+    //文字列を追加し、その位置を返す
+    fn add(&mut self, s: &str) -> u32 {
+        let index = u32::try_from(self.bytes.len()).unwrap();
+        self.bytes.extend_from_slice(s.as_bytes());
+        self.bytes.push(0);
+        index
+    }
+}
+
+impl Section {
+    //.text, .data, .rodataの順にセクションヘッダの番号を1から振る
+    fn index(self) -> u16 {
+        match self {
+            Section::Text => 1,
+            Section::Data => 2,
+            Section::Rodata => 3,
+        }
+    }
+}
+
+impl RelocKind {
+    fn number(self) -> u64 {
+        match self {
+            RelocKind::Plt32 => 4,
+            RelocKind::Pc32 => 2,
+            RelocKind::Abs64 => 1,
+        }
+    }
+}
+
+//x86-64用のELF64再配置可能オブジェクトファイルを生成する
+#[allow(clippy::too_many_lines)]
+pub fn write_elf(object: &Object) -> Vec<u8> {
+    let mut shstrtab = StrTab::new();
+    let mut strtab = StrTab::new();
+
+    // シンボルテーブル
+    // ローカルシンボルを先に並べなければならない
+    // 0番は空、1〜3番は各セクションのシンボル
+    let mut symtab = vec![0; 24];
+    for section in [Section::Text, Section::Data, Section::Rodata] {
+        push_symbol(&mut symtab, 0, STB_LOCAL, STT_SECTION, section.index(), 0);
+    }
+    // シンボルの添字からシンボルテーブルでの番号への対応
+    let mut symbol_index = vec![0; object.symbols.len()];
+    let mut next_index = 4;
+    for global in [false, true] {
+        for (i, symbol) in object.symbols.iter().enumerate() {
+            if symbol.global != global {
+                continue;
+            }
+            let name = strtab.add(&symbol.name);
+            let bind = if global { STB_GLOBAL } else { STB_LOCAL };
+            // 未定義のシンボルのセクション番号は0
+            let shndx = symbol.section.map_or(0, Section::index);
+            push_symbol(&mut symtab, name, bind, STT_NOTYPE, shndx, symbol.offset);
+            symbol_index[i] = next_index;
+            next_index += 1;
+        }
+    }
+    // 最初のグローバルシンボルの番号
+    let first_global = 4 + object.symbols.iter().filter(|s| !s.global).count();
+
+    // 各セクションの再配置テーブル
+    let mut rela = Vec::new();
+    for section in [Section::Text, Section::Data, Section::Rodata] {
+        let mut table = Vec::new();
+        for reloc in object.relocations.iter().filter(|r| r.section == section) {
+            let info = (symbol_index[reloc.symbol] << 32) | reloc.kind.number();
+            table.extend_from_slice(&(reloc.offset as u64).to_le_bytes());
+            table.extend_from_slice(&info.to_le_bytes());
+            table.extend_from_slice(&reloc.addend.to_le_bytes());
+        }
+        rela.push(table);
+    }
+
+    // セクションの中身を並べる
+    // ELFヘッダの直後から配置する
+    let mut body: Vec<u8> = Vec::new();
+    let mut headers = vec![SectionHeader {
+        name: 0,
+        kind: 0,
+        flags: 0,
+        offset: 0,
+        size: 0,
+        link: 0,
+        info: 0,
+        align: 0,
+        entsize: 0,
+    }];
+    let mut add_section = |body: &mut Vec<u8>, mut header: SectionHeader, bytes: &[u8]| {
+        // アラインメントを揃える
+        while !(64 + body.len()).is_multiple_of(usize::try_from(header.align.max(1)).unwrap()) {
+            body.push(0);
+        }
+        header.offset = 64 + body.len();
+        header.size = bytes.len();
+        body.extend_from_slice(bytes);
+        headers.push(header);
+        u32::try_from(headers.len() - 1).unwrap()
+    };
+    let sections = [
+        (".text", SHF_ALLOC | SHF_EXECINSTR, 16, &object.text),
+        (".data", SHF_ALLOC | SHF_WRITE, 8, &object.data),
+        (".rodata", SHF_ALLOC, 8, &object.rodata),
+    ];
+    for (name, flags, align, bytes) in sections {
+        let header = SectionHeader {
+            name: shstrtab.add(name),
+            kind: SHT_PROGBITS,
+            flags,
+            offset: 0,
+            size: 0,
+            link: 0,
+            info: 0,
+            align,
+            entsize: 0,
+        };
+        add_section(&mut body, header, bytes);
+    }
+    // スタックを実行可能にする必要がないことをリンカに伝える
+    let header = SectionHeader {
+        name: shstrtab.add(".note.GNU-stack"),
+        kind: SHT_PROGBITS,
+        flags: 0,
+        offset: 0,
+        size: 0,
+        link: 0,
+        info: 0,
+        align: 1,
+        entsize: 0,
+    };
+    add_section(&mut body, header, &[]);
+    // シンボルテーブルと文字列テーブルの番号は再配置テーブルから参照されるので先に決めておく
+    let rela_names = [".rela.text", ".rela.data", ".rela.rodata"];
+    let rela_num = rela.iter().filter(|table| !table.is_empty()).count();
+    let symtab_index = u32::try_from(5 + rela_num).unwrap();
+    for (i, table) in rela.iter().enumerate() {
+        if table.is_empty() {
+            continue;
+        }
+        let header = SectionHeader {
+            name: shstrtab.add(rela_names[i]),
+            kind: SHT_RELA,
+            flags: SHF_INFO_LINK,
+            offset: 0,
+            size: 0,
+            link: symtab_index,
+            info: u32::try_from(i + 1).unwrap(),
+            align: 8,
+            entsize: 24,
+        };
+        add_section(&mut body, header, table);
+    }
+    let header = SectionHeader {
+        name: shstrtab.add(".symtab"),
+        kind: SHT_SYMTAB,
+        flags: 0,
+        offset: 0,
+        size: 0,
+        link: symtab_index + 1,
+        info: u32::try_from(first_global).unwrap(),
+        align: 8,
+        entsize: 24,
+    };
+    add_section(&mut body, header, &symtab);
+    let header = SectionHeader {
+        name: shstrtab.add(".strtab"),
+        kind: SHT_STRTAB,
+        flags: 0,
+        offset: 0,
+        size: 0,
+        link: 0,
+        info: 0,
+        align: 1,
+        entsize: 0,
+    };
+    add_section(&mut body, header, &strtab.bytes);
+    let header = SectionHeader {
+        name: shstrtab.add(".shstrtab"),
+        kind: SHT_STRTAB,
+        flags: 0,
+        offset: 0,
+        size: 0,
+        link: 0,
+        info: 0,
+        align: 1,
+        entsize: 0,
+    };
+    let shstrndx = add_section(&mut body, header, &shstrtab.bytes);
+
+    // セクションヘッダテーブルはファイルの末尾に置く
+    while !body.len().is_multiple_of(8) {
+        body.push(0);
+    }
+    let shoff = 64 + body.len();
+
+    let mut elf = Vec::new();
+    // ELFヘッダ
+    elf.extend_from_slice(&[0x7f, b'E', b'L', b'F', 2, 1, 1, 0]);
+    elf.extend_from_slice(&[0; 8]);
+    elf.extend_from_slice(&1u16.to_le_bytes()); // e_type: ET_REL
+    elf.extend_from_slice(&62u16.to_le_bytes()); // e_machine: EM_X86_64
+    elf.extend_from_slice(&1u32.to_le_bytes()); // e_version
+    elf.extend_from_slice(&0u64.to_le_bytes()); // e_entry
+    elf.extend_from_slice(&0u64.to_le_bytes()); // e_phoff
+    elf.extend_from_slice(&(shoff as u64).to_le_bytes()); // e_shoff
+    elf.extend_from_slice(&0u32.to_le_bytes()); // e_flags
+    elf.extend_from_slice(&64u16.to_le_bytes()); // e_ehsize
+    elf.extend_from_slice(&0u16.to_le_bytes()); // e_phentsize
+    elf.extend_from_slice(&0u16.to_le_bytes()); // e_phnum
+    elf.extend_from_slice(&64u16.to_le_bytes()); // e_shentsize
+    elf.extend_from_slice(&u16::try_from(headers.len()).unwrap().to_le_bytes()); // e_shnum
+    elf.extend_from_slice(&u16::try_from(shstrndx).unwrap().to_le_bytes()); // e_shstrndx
+    elf.extend_from_slice(&body);
+    // セクションヘッダテーブル
+    for header in headers {
+        elf.extend_from_slice(&header.name.to_le_bytes());
+        elf.extend_from_slice(&header.kind.to_le_bytes());
+        elf.extend_from_slice(&header.flags.to_le_bytes());
+        elf.extend_from_slice(&0u64.to_le_bytes()); // sh_addr
+        elf.extend_from_slice(&(header.offset as u64).to_le_bytes());
+        elf.extend_from_slice(&(header.size as u64).to_le_bytes());
+        elf.extend_from_slice(&header.link.to_le_bytes());
+        elf.extend_from_slice(&header.info.to_le_bytes());
+        elf.extend_from_slice(&header.align.to_le_bytes());
+        elf.extend_from_slice(&header.entsize.to_le_bytes());
+    }
+    elf
+}
+
+//シンボルテーブルに1つ分のエントリを追加する
+fn push_symbol(symtab: &mut Vec<u8>, name: u32, bind: u8, kind: u8, shndx: u16, value: usize) {
+    symtab.extend_from_slice(&name.to_le_bytes());
+    symtab.push((bind << 4) | kind);
+    symtab.push(0); // st_other
+    symtab.extend_from_slice(&shndx.to_le_bytes());
+    symtab.extend_from_slice(&(value as u64).to_le_bytes());
+    symtab.extend_from_slice(&0u64.to_le_bytes()); // st_size
+}
@@ -0,0 +1,411 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+use crate::codegen::align_to;
+use crate::consteval::{binary, cast, convert, from_float, to_float};
+use crate::kind::Function;
+use crate::kind::Kind;
+use crate::kind::Node;
+use crate::kind::Obj;
+use crate::kind::Program;
+use crate::types::{Member, Type};
+
+//インタプリタで呼び出せる組み込み関数
+//test.shでリンクしているtmp2.oの関数と同じ動作をする
+type Builtin = fn(&[i64]) -> i64;
+const BUILTINS: [(&str, usize, Builtin); 7] = [
+    ("ret31", 0, |_| 31),
+    ("ret5", 0, |_| 5),
+    ("add", 2, |args| args[0].wrapping_add(args[1])),
+    ("sub", 2, |args| args[0].wrapping_sub(args[1])),
+    ("add6", 6, |args| {
+        args.iter().fold(0, |sum, arg| sum.wrapping_add(*arg))
+    }),
+    ("add_mixed", 4, |args| {
+        #[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation)]
+        let sum = (args[0] as f64
+            + to_float(args[1], &Type::Double)
+            + args[2] as f64
+            + to_float(args[3], &Type::Float)) as i64;
+        sum
+    }),
+    ("div2", 1, |args| {
+        from_float(to_float(args[0], &Type::Double) / 2.0, &Type::Double)
+    }),
+];
+
+//文の実行結果
+enum Flow {
+    //次の文へ進む
+    Next,
+    //returnで関数を抜ける
+    Return(i64),
+    //breakでループかswitch文を抜ける
+    Break,
+}
+
+//構文木を直接実行するインタプリタ
+//グローバル変数とローカル変数はバイト列のメモリに置き、ポインタはその中の位置で表す
+struct Interpreter<'a> {
+    //定義された関数
+    functions: HashMap<&'a str, &'a Function>,
+    //グローバル変数のアドレス
+    globals: HashMap<&'a str, usize>,
+    //グローバル変数の領域の後に、呼び出し中の関数のフレームを順に積む
+    mem: Vec<u8>,
+    //実行中の関数のフレームの先頭(ローカル変数はここからオフセット分下にある)
+    fp: usize,
+    //最後に評価した式文の値(returnが無い場合の戻り値)
+    result: i64,
+}
+
+//構文木を実行し、mainの戻り値を返す
+pub fn interpret(program: &Program) -> i64 {
+    let mut interpreter = Interpreter {
+        functions: program
+            .functions
+            .iter()
+            .map(|function| (function.name.as_str(), function))
+            .collect(),
+        globals: HashMap::new(),
+        // アドレス0はヌルポインタとして使わない
+        mem: vec![0; 8],
+        fp: 0,
+        result: 0,
+    };
+    for global in &program.globals {
+        let addr = align_to(interpreter.mem.len(), global.obj.ty.align());
+        interpreter.mem.resize(addr, 0);
+        interpreter.mem.extend_from_slice(&global.init);
+        interpreter.globals.insert(global.obj.name.as_str(), addr);
+    }
+    // 初期値に他のグローバル変数のアドレスを書き込む
+    for global in &program.globals {
+        let addr = interpreter.globals[global.obj.name.as_str()];
+        for reloc in &global.relocs {
+            let value =
+                i64::try_from(interpreter.global_addr(&reloc.label)).unwrap() + reloc.addend;
+            let offset = addr + reloc.offset;
+            interpreter.mem[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+        }
+    }
+    interpreter.call("main", &[])
+}
+
+impl Interpreter<'_> {
+    //グローバル変数のアドレス
+    //externで宣言しただけの変数はほかの翻訳単位と結合しないので使えない
+    fn global_addr(&self, name: &str) -> usize {
+        *self.globals.get(name).unwrap_or_else(|| {
+            panic!(
+                "グローバル変数{}が定義されていません。プログラムを終了します。",
+                name
+            )
+        })
+    }
+
+    //関数を呼び出して戻り値を返す
+    //定義された関数が無ければ組み込み関数を探す
+    fn call(&mut self, func_name: &str, args: &[i64]) -> i64 {
+        if let Some(function) = self.functions.get(func_name).copied() {
+            let is_variadic = function.va_area.is_some();
+            assert!(
+                args.len() == function.params.len()
+                    || is_variadic && args.len() > function.params.len(),
+                "関数{}の引数は{}個です。プログラムを終了します。",
+                func_name,
+                function.params.len()
+            );
+            // 0で埋めたフレームを確保し、仮引数に引数を入れる
+            // 可変長引数はフレームの先頭から8バイトずつ並べる
+            let base = self.mem.len();
+            let va_size = 8 * (args.len() - function.params.len());
+            self.mem.resize(base + function.stack_size + va_size, 0);
+            let caller_fp = std::mem::replace(&mut self.fp, base + function.stack_size);
+            let caller_result = std::mem::replace(&mut self.result, 0);
+            let (args, va_args) = args.split_at(function.params.len());
+            for (param, arg) in function.params.iter().zip(args) {
+                self.store(self.fp - param.offset.unwrap(), *arg, &param.ty);
+            }
+            for (i, arg) in va_args.iter().enumerate() {
+                self.store(self.fp + 8 * i, *arg, &Type::Long);
+            }
+            let mut value = None;
+            for node in &function.body {
+                //文単位で実行
+                if let Flow::Return(ret) = self.exec(node) {
+                    value = Some(ret);
+                    break;
+                }
+            }
+            // returnが無ければ最後の式文の結果が返り値になる
+            let value = value.unwrap_or(self.result);
+            self.mem.truncate(base);
+            self.fp = caller_fp;
+            self.result = caller_result;
+            return value;
+        }
+        let (_, args_num, func) = BUILTINS
+            .iter()
+            .find(|(name, _, _)| *name == func_name)
+            .unwrap_or_else(|| {
+                panic!(
+                    "関数{}はインタプリタでは呼び出せません。プログラムを終了します。",
+                    func_name
+                )
+            });
+        assert!(
+            args.len() == *args_num,
+            "関数{}の引数は{}個です。プログラムを終了します。",
+            func_name,
+            args_num
+        );
+        func(args)
+    }
+
+    // 文の実行
+    fn exec(&mut self, node: &Node) -> Flow {
+        match &node.kind {
+            // {}の中
+            Kind::CurlyBracOpen => {
+                if let (Some(lhs), Some(rhs)) = (&node.lhs, &node.rhs) {
+                    match self.exec(lhs) {
+                        Flow::Next => return self.exec(rhs),
+                        flow => return flow,
+                    }
+                }
+                Flow::Next
+            }
+            Kind::Return => Flow::Return(self.eval(node.lhs.as_ref().unwrap())),
+            Kind::Break => Flow::Break,
+            Kind::If(node_cond) => {
+                if self.eval_cond(node_cond.as_ref().unwrap()) {
+                    // then式
+                    self.exec(node.lhs.as_ref().unwrap())
+                } else if let Some(node_else) = &node.rhs {
+                    // else式
+                    self.exec(node_else)
+                } else {
+                    Flow::Next
+                }
+            }
+            Kind::While(node_cond) => {
+                while self.eval_cond(node_cond.as_ref().unwrap()) {
+                    match self.exec(node.lhs.as_ref().unwrap()) {
+                        Flow::Next => (),
+                        Flow::Return(value) => return Flow::Return(value),
+                        Flow::Break => break,
+                    }
+                }
+                Flow::Next
+            }
+            Kind::For(node_init, node_cond, node_inc) => {
+                if let Some(node_init) = node_init {
+                    // 存在すれば初期化処理(変数の宣言の場合もある)
+                    self.exec(node_init);
+                }
+                // 条件式が無ければ無条件ループ
+                while node_cond.as_ref().is_none_or(|cond| self.eval_cond(cond)) {
+                    match self.exec(node.lhs.as_ref().unwrap()) {
+                        Flow::Next => (),
+                        Flow::Return(value) => return Flow::Return(value),
+                        Flow::Break => break,
+                    }
+                    if let Some(node_inc) = node_inc {
+                        // 存在すれば変化式
+                        self.eval(node_inc);
+                    }
+                }
+                Flow::Next
+            }
+            Kind::Switch(node_cond, _, cases) => {
+                // 値が一致するcase、無ければdefaultから最後まで続けて実行する
+                let value = self.eval(node_cond.as_ref().unwrap());
+                let start = cases
+                    .iter()
+                    .position(|case| case.labels.contains(&Some(value)))
+                    .or_else(|| cases.iter().position(|case| case.labels.contains(&None)));
+                if let Some(start) = start {
+                    // breakがあればそこで抜ける
+                    for node in cases[start..].iter().flat_map(|case| &case.body) {
+                        match self.exec(node) {
+                            Flow::Next => (),
+                            Flow::Return(value) => return Flow::Return(value),
+                            Flow::Break => break,
+                        }
+                    }
+                }
+                Flow::Next
+            }
+            // 式文
+            _ => {
+                self.result = self.eval(node);
+                Flow::Next
+            }
+        }
+    }
+
+    // 条件式の評価
+    fn eval_cond(&mut self, node: &Node) -> bool {
+        let value = self.eval(node);
+        let ty = node.ty.as_ref().unwrap();
+        if ty.is_flonum() {
+            to_float(value, ty) != 0.0
+        } else {
+            value != 0
+        }
+    }
+
+    // 式の評価
+    fn eval(&mut self, node: &Node) -> i64 {
+        match &node.kind {
+            Kind::Num(value, _) => *value,
+            Kind::FNum(value, ty) => from_float(*value, ty),
+            Kind::FunCall(func_name, args, _) => {
+                let args: Vec<i64> = args.iter().flatten().map(|arg| self.eval(arg)).collect();
+                let value = self.call(func_name, &args);
+                cast(value, node.ty.as_ref().unwrap())
+            }
+            Kind::Var(_) | Kind::Deref | Kind::Member(_) => {
+                let addr = self.addr(node);
+                // 構造体は値を読み込まず、アドレスをそのまま値として扱う
+                if node.ty.as_ref().unwrap().is_struct() {
+                    return to_value(addr);
+                }
+                self.load(addr, node.ty.as_ref().unwrap())
+            }
+            Kind::Addr => to_value(self.addr(node.lhs.as_ref().unwrap())),
+            Kind::Cast => {
+                let lhs = node.lhs.as_ref().unwrap();
+                convert(
+                    self.eval(lhs),
+                    lhs.ty.as_ref().unwrap(),
+                    node.ty.as_ref().unwrap(),
+                )
+            }
+            Kind::Assign => {
+                let addr = self.addr(node.lhs.as_ref().unwrap());
+                let value = self.eval(node.rhs.as_ref().unwrap());
+                let ty = node.ty.as_ref().unwrap();
+                if ty.is_struct() {
+                    // 入力元のアドレスからメンバをすべてコピーする
+                    let src = self.check_addr(value, ty.size());
+                    self.mem.copy_within(src..src + ty.size(), addr);
+                    return to_value(addr);
+                }
+                self.store(addr, value, ty);
+                value
+            }
+            Kind::VaStart(tmp, ..) => self.va_start(node, tmp),
+            Kind::VaArg(tmp) => self.va_arg(node, tmp),
+            Kind::CurlyBracOpen
+            | Kind::Return
+            | Kind::If(_)
+            | Kind::While(_)
+            | Kind::For(..)
+            | Kind::Switch(..)
+            | Kind::Break => {
+                panic!("式があるべき箇所に文があります。プログラムを終了します。")
+            }
+            kind => {
+                //ノードが演算子だった場合
+                let lhs_node = node.lhs.as_ref().unwrap();
+                let lhs = self.eval(lhs_node);
+                let rhs = self.eval(node.rhs.as_ref().unwrap());
+                binary(
+                    kind,
+                    lhs,
+                    rhs,
+                    lhs_node.ty.as_ref().unwrap(),
+                    node.ty.as_ref().unwrap(),
+                )
+            }
+        }
+    }
+}
+
+impl Interpreter<'_> {
+    //値を書き込める式のアドレスを求める
+    fn addr(&mut self, node: &Node) -> usize {
+        match &node.kind {
+            Kind::Var(obj) => match obj.offset {
+                Some(offset) => self.fp - offset,
+                None => self.global_addr(&obj.name),
+            },
+            // 参照先のアドレスはポインタの値そのもの
+            Kind::Deref => {
+                let value = self.eval(node.lhs.as_ref().unwrap());
+                self.check_addr(value, node.ty.as_ref().unwrap().size())
+            }
+            // 構造体のアドレスにメンバのオフセットを足す
+            Kind::Member(member) => self.addr(node.lhs.as_ref().unwrap()) + member.offset,
+            _ => panic!("式の左辺に変数以外があります。プログラムを終了します。"),
+        }
+    }
+
+    //va_listを可変長引数の先頭から読むように設定する
+    //レジスタ保存領域は無いので、すべてoverflow_arg_areaから読み出す
+    fn va_start(&mut self, node: &Node, tmp: &Obj) -> i64 {
+        let ap = self.eval(node.lhs.as_ref().unwrap());
+        let ap = self.check_addr(ap, tmp.ty.base().unwrap().size());
+        let fp = to_value(self.fp);
+        for (name, value) in [
+            ("gp_offset", 0),
+            ("fp_offset", 0),
+            ("overflow_arg_area", fp),
+            ("reg_save_area", 0),
+        ] {
+            let member = va_member(tmp, name);
+            self.store(ap + member.offset, value, &member.ty);
+        }
+        0
+    }
+
+    //次の可変長引数を読み出し、overflow_arg_areaを8バイト進める
+    fn va_arg(&mut self, node: &Node, tmp: &Obj) -> i64 {
+        let ap = self.eval(node.lhs.as_ref().unwrap());
+        let ap = self.check_addr(ap, tmp.ty.base().unwrap().size());
+        let member = va_member(tmp, "overflow_arg_area");
+        let addr = self.load(ap + member.offset, &member.ty);
+        self.store(ap + member.offset, addr + 8, &member.ty);
+        let ty = node.ty.as_ref().unwrap();
+        let addr = self.check_addr(addr, ty.size());
+        self.load(addr, ty)
+    }
+
+    //ポインタの値がsizeバイト読み書きできるアドレスであることを確かめる
+    fn check_addr(&self, value: i64, size: usize) -> usize {
+        match usize::try_from(value) {
+            Ok(addr) if addr != 0 && addr + size <= self.mem.len() => addr,
+            _ => panic!(
+                "不正なアドレス{}を参照しました。プログラムを終了します。",
+                value
+            ),
+        }
+    }
+
+    //ty型の値を読み込み、型の符号に合わせて8バイトに拡張する
+    fn load(&self, addr: usize, ty: &Type) -> i64 {
+        let mut bytes = [0; 8];
+        bytes[..ty.size()].copy_from_slice(&self.mem[addr..addr + ty.size()]);
+        cast(i64::from_le_bytes(bytes), ty)
+    }
+
+    //値の下位からty型の大きさ分を書き込む
+    fn store(&mut self, addr: usize, value: i64, ty: &Type) {
+        self.mem[addr..addr + ty.size()].copy_from_slice(&value.to_le_bytes()[..ty.size()]);
+    }
+}
+
+//一時変数tmpが指すva_listのメンバ
+fn va_member(tmp: &Obj, name: &str) -> Member {
+    match tmp.ty.base() {
+        Some(Type::Struct(st)) => st.member(name),
+        _ => unreachable!(),
+    }
+}
+
+//アドレスをポインタの値にする
+fn to_value(addr: usize) -> i64 {
+    i64::try_from(addr).unwrap()
+}
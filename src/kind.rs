@@ -1,3 +1,7 @@
+use std::rc::Rc;
+
+use crate::types::{Member, Type};
+
 //トークンとノードの種類
 pub enum Kind {
     Add,                                                          // +
@@ -6,28 +10,52 @@ pub enum Kind {
     Div,                                                          // /
     RoundBracOpen,                                                // (
     RoundBracClose,                                               // )
-    CurlyBracOpen,                      // トークンでは{, ノードでは{}内の文を表す
-    CurlyBracClose,                     // }
-    Equal,                              // ==
-    NoEqual,                            // !=
-    LowThan,                            // <
-    LowEqual,                           // <=
-    HighThan,                           // >
-    HighEqual,                          // >=
-    Semicolon,                          // ;
-    Assign,                             // = 代入演算子
-    Return,                             // return
-    Comma,                              // ,
-    FunCall(String, Option<Vec<Node>>), // 関数呼び出し (関数名, 引数(トークンでは常にNoneとする))
-    If(Option<Box<Node>>),              // if(条件式のノード)
-    While(Option<Box<Node>>),           // while(条件式のノード)
+    CurlyBracOpen,   // トークンでは{, ノードでは{}内の文を表す
+    CurlyBracClose,  // }
+    SquareBracOpen,  // [
+    SquareBracClose, // ]
+    Equal,           // ==
+    NoEqual,         // !=
+    LowThan,         // <
+    LowEqual,        // <=
+    HighThan,        // >
+    HighEqual,       // >=
+    Semicolon,       // ;
+    Assign,          // = 代入演算子
+    Return,          // return
+    Comma,           // ,
+    FunCall(String, Option<Vec<Node>>, Option<usize>), // 関数呼び出し (関数名, 引数, 可変長引数の関数であれば名前付きの引数の数)
+    If(Option<Box<Node>>),                             // if(条件式のノード)
+    While(Option<Box<Node>>),                          // while(条件式のノード)
     For(Option<Box<Node>>, Option<Box<Node>>, Option<Box<Node>>), // for(初期化式;条件式;変化式)
-    Else,                               //else
-    //変数の1文字目にはアルファベットまたはアンダーバーのみ可
+    Switch(Option<Box<Node>>, Rc<Obj>, Vec<Case>), // switch(条件式のノード, 条件式の値を置く変数, 各ラベルと文)
+    Break,            // break (ノードのみで使う。最も内側のループかswitch文を抜ける)
+    Else,             //else
+    Keyword(Keyword), // 上記以外のキーワード
+    Addr,             // & (ノードではアドレス演算子)
+    Deref,            // 単項の* (ノードのみで使う)
+    Dot,              // .
+    Arrow,            // ->
+    Colon,            // :
+    Ellipsis,         // ...
+    Member(Member),   // 構造体のメンバ(ノードのみで使う。lhsが構造体)
+    Cast,             // 型変換(ノードのみで使う。lhsをノードの型に変換する)
+    //va_listの初期化(ノードのみで使う。lhsはva_listのアドレスを一時変数に入れる代入式)
+    //(va_listを指す一時変数, レジスタ保存領域のオフセット, 名前付きの整数の仮引数の数, 浮動小数点数の仮引数の数)
+    VaStart(Rc<Obj>, usize, usize, usize),
+    //次の可変長引数の読み出し(ノードのみで使う。lhsはVaStartと同じで、ノードの型の値を読み出す)
+    //一時変数には読み出す引数のアドレスを入れ直す
+    VaArg(Rc<Obj>),
+    //識別子の1文字目にはアルファベットまたはアンダーバーのみ可
     //2文字目以降はそれに加えて数字も可
-    Var(usize), // 変数(変数を一意に指す識別番号。1からの連番)
-    //数値はそのまま出力するだけなのでchar型とする
-    Num(Vec<char>),
+    Ident(String), // 識別子(トークンのみで使う。変数か関数かは構文解析で決める)
+    Var(Rc<Obj>),  // 変数(構文解析で名前を解決した変数)
+    //整数リテラルの値と型(unsignedの値もビット列をそのまま格納する)
+    Num(i64, Type),
+    //浮動小数点数リテラルの値と型(floatの値もf64で格納する)
+    FNum(f64, Type),
+    //文字列リテラルのバイト列(トークンのみで使う。終端の0は含まない)
+    Str(Vec<u8>),
 }
 
 //構文木を構成するノード
@@ -35,4 +63,112 @@ pub struct Node {
     pub kind: Kind,
     pub lhs: Option<Box<Node>>,
     pub rhs: Option<Box<Node>>,
+    //式の型(文ではNone)
+    pub ty: Option<Type>,
+}
+
+//switch文の中のcase, defaultのラベルと、次のラベルまでの文
+pub struct Case {
+    //caseの値(defaultはNone)
+    pub labels: Vec<Option<i64>>,
+    pub body: Vec<Node>,
+}
+
+//変数
+pub struct Obj {
+    pub name: String,
+    pub ty: Type,
+    //ローカル変数のフレームの先頭からのオフセット(グローバル変数ではNone)
+    pub offset: Option<usize>,
+}
+
+//グローバル変数の定義
+pub struct Global {
+    pub obj: Rc<Obj>,
+    //初期値のバイト列(変数の型の大きさと同じ長さ)
+    pub init: Vec<u8>,
+    //初期値に含まれる他のグローバル変数のアドレス(オフセットの順に並べる)
+    pub relocs: Vec<Reloc>,
+    //翻訳単位の中でのみ参照できるか(文字列リテラルとstaticの変数はローカルなシンボルにする)
+    pub is_static: bool,
+}
+
+//グローバル変数の初期値に埋め込む、他のグローバル変数のアドレス
+pub struct Reloc {
+    //初期値の先頭からのオフセット(ここから8バイトにアドレスを書き込む)
+    pub offset: usize,
+    //参照するグローバル変数の名前
+    pub label: String,
+    //アドレスに足す値
+    pub addend: i64,
+}
+
+//翻訳単位全体
+pub struct Program {
+    pub globals: Vec<Global>,
+    pub functions: Vec<Function>,
+}
+
+//関数の定義
+pub struct Function {
+    pub name: String,
+    //仮引数(順番に並べる)
+    pub params: Vec<Rc<Obj>>,
+    //関数本体の文
+    pub body: Vec<Node>,
+    //ローカル変数の領域のバイト数
+    pub stack_size: usize,
+    //可変長引数の関数では、引数のレジスタを書き込むレジスタ保存領域のフレームの先頭からのオフセット
+    pub va_area: Option<usize>,
+    //翻訳単位の中でのみ呼び出せるか(staticの関数はローカルなシンボルにする)
+    pub is_static: bool,
+}
+
+//Cのキーワード
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Keyword {
+    Auto,
+    Break,
+    Case,
+    Char,
+    Const,
+    Continue,
+    Default,
+    Do,
+    Double,
+    Else,
+    Enum,
+    Extern,
+    Float,
+    For,
+    Goto,
+    If,
+    Inline,
+    Int,
+    Long,
+    Register,
+    Restrict,
+    Return,
+    Short,
+    Signed,
+    Sizeof,
+    Static,
+    Struct,
+    Switch,
+    Typedef,
+    Union,
+    Unsigned,
+    Void,
+    Volatile,
+    While,
+    Alignas,
+    Alignof,
+    Atomic,
+    Bool,
+    Complex,
+    Generic,
+    Imaginary,
+    Noreturn,
+    StaticAssert,
+    ThreadLocal,
 }
@@ -1,29 +1,162 @@
 #![warn(clippy::all, clippy::pedantic)]
 use std::env;
+use std::io::{self, Write};
+use std::process;
 
+mod aarch64;
+mod asm;
+mod assemble;
 mod codegen;
+mod consteval;
+mod driver;
+mod elf;
+mod interpret;
 mod kind;
 mod parse;
+mod preprocess;
+mod riscv64;
 mod tokenize;
+mod types;
+mod wasm;
+mod x86_64;
 
-use crate::codegen::codegen;
-use crate::parse::program;
-use crate::tokenize::tokenize;
+use crate::asm::Syntax;
+use crate::driver::{
+    compile_to_assembly, compile_to_object, is_input_file, parse_input, Driver, Stop,
+};
+use crate::interpret::interpret;
+use crate::preprocess::preprocess;
+use crate::wasm::wasm;
 
 fn main() {
-    //引数を入力文字列として格納
-    let arg_vec: Vec<String> = env::args().collect();
-    let mut arg = arg_vec[1].chars();
-
-    //引数の文字列をトークナイズする
-    let tokens = tokenize(&mut arg);
-    //トークン列が空(入力が空)ならばエラー
-    if tokens.is_empty() {
-        panic!("入力がありません。プログラムを終了します。");
+    //出力するアセンブリの記法(デフォルトはIntel記法)
+    let mut syntax = Syntax::Intel;
+    //出力先のアーキテクチャ名(デフォルトはx86-64)
+    let mut target_name = "x86_64".to_string();
+    //アセンブリを出力せずに構文木を直接実行するか
+    let mut run = false;
+    //どの段階まで処理するか
+    let mut stop = Stop::Link;
+    //出力ファイル名
+    let mut output = None;
+    //リンカに渡す引数
+    let mut link_args = Vec::new();
+    //インクルードファイルの検索パス
+    let mut include_paths = Vec::new();
+    //コマンドラインで定義するマクロ
+    let mut defines = Vec::new();
+    //入力ファイル
+    let mut files = Vec::new();
+    //入力文字列
+    let mut input = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if let Some(name) = arg.strip_prefix("-masm=") {
+            //-masm=intel または -masm=att で記法を指定する
+            syntax = match name {
+                "intel" => Syntax::Intel,
+                "att" => Syntax::Att,
+                _ => panic!(
+                    "不明なアセンブリ記法\"{}\"が指定されました。プログラムを終了します。",
+                    name
+                ),
+            };
+        } else if let Some(name) = arg.strip_prefix("--target=") {
+            //--target=x86_64, aarch64, riscv64, wasm32 でアーキテクチャを指定する
+            target_name = name.to_string();
+        } else if arg == "--run" {
+            //--run でインタプリタとして実行する
+            run = true;
+        } else if arg == "-E" {
+            //-E で前処理した結果を出力する
+            stop = Stop::Preprocess;
+        } else if arg == "-S" {
+            //-S でアセンブリを出力する
+            stop = Stop::Assembly;
+        } else if arg == "-c" {
+            //-c でオブジェクトファイルを出力する
+            stop = Stop::Object;
+        } else if arg == "-o" {
+            //-o で出力ファイル名を指定する
+            output = Some(
+                args.next()
+                    .expect("-oの後に出力ファイル名がありません。プログラムを終了します。"),
+            );
+        } else if let Some(path) = arg.strip_prefix("-I") {
+            //-I でインクルードファイルの検索パスを追加する
+            include_paths.push(option_value(path, &mut args, "-I"));
+        } else if let Some(define) = arg.strip_prefix("-D") {
+            //-D NAME または -D NAME=VALUE でマクロを定義する
+            defines.push(option_value(define, &mut args, "-D"));
+        } else if arg.starts_with("-l") || arg.starts_with("-L") || arg == "-static" {
+            //-l, -L, -staticはリンカにそのまま渡す
+            link_args.push(arg);
+        } else if is_input_file(&arg) {
+            //拡張子が.c, .s, .oの引数は入力ファイルとして扱う
+            files.push(arg);
+        } else {
+            //オプション以外の引数を入力文字列として格納
+            input = Some(arg);
+        }
     }
-    // トークン列から構文木を生成
-    let nodes = program(&tokens);
 
-    //構文木からアセンブリコードを出力
-    codegen(nodes);
+    //入力ファイルがあればコンパイラドライバとして動作する
+    if !files.is_empty() {
+        let mut driver = Driver::new(target_name, syntax);
+        driver.stop = stop;
+        driver.output = output;
+        driver.link_args = link_args;
+        driver.include_paths = include_paths;
+        driver.defines = defines;
+        driver.inputs = files;
+        driver.run();
+        return;
+    }
+
+    let input = input.expect("入力がありません。プログラムを終了します。");
+    let input = preprocess(&input, None, &include_paths, &defines);
+
+    //構文木を実行し、その戻り値を終了ステータスとする
+    if run {
+        #[allow(clippy::cast_possible_truncation)]
+        process::exit(interpret(&parse_input(&input)) as i32);
+    }
+
+    //WebAssemblyは制御構造の表し方が異なるため、構文木から直接生成する
+    let bytes = if stop == Stop::Preprocess {
+        input.into_bytes()
+    } else if target_name == "wasm32" {
+        wasm(parse_input(&input)).into_bytes()
+    } else if stop == Stop::Object {
+        assert!(
+            matches!(target_name.as_str(), "x86_64" | "x86-64"),
+            "組み込みアセンブラはx86-64にのみ対応しています。プログラムを終了します。"
+        );
+        compile_to_object(&input, syntax)
+    } else {
+        compile_to_assembly(&input, &target_name, syntax).into_bytes()
+    };
+
+    //-oが無ければ標準出力に出力する
+    match output {
+        Some(output) => std::fs::write(&output, bytes).unwrap_or_else(|_| {
+            panic!(
+                "ファイル{}に書き込めません。プログラムを終了します。",
+                output
+            )
+        }),
+        None => io::stdout()
+            .write_all(&bytes)
+            .expect("出力できません。プログラムを終了します。"),
+    }
+}
+
+//-Iや-Dのように値が続く引数の値を返す
+//値はオプションに続けて書くか、次の引数として書く
+fn option_value(value: &str, args: &mut impl Iterator<Item = String>, option: &str) -> String {
+    if !value.is_empty() {
+        return value.to_string();
+    }
+    args.next()
+        .unwrap_or_else(|| panic!("{}の後に値がありません。プログラムを終了します。", option))
 }
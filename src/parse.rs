@@ -1,51 +1,1239 @@
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::mem;
 use std::panic;
+use std::rc::Rc;
 
+use crate::codegen::{align_to, VA_AREA_SIZE};
+use crate::consteval;
+use crate::kind::Case;
+use crate::kind::Function;
+use crate::kind::Global;
+use crate::kind::Keyword;
 use crate::kind::Kind;
 use crate::kind::Node;
+use crate::kind::Obj;
+use crate::kind::Program;
+use crate::kind::Reloc;
+use crate::tokenize::keyword_name;
+use crate::types::{add_type, new_cast, new_va_list, FuncType, Struct, Type};
+
+//変数と同じ名前空間に置かれる識別子
+#[derive(Clone)]
+enum VarScope {
+    Var(Rc<Obj>),
+    //列挙定数とその値
+    EnumConst(i64),
+    //typedefで宣言された型名とその型
+    Typedef(Type),
+    //宣言された関数とその型
+    Func(Rc<FuncType>),
+}
+
+//構造体、共用体、列挙型のタグ
+#[derive(Clone)]
+enum Tag {
+    Struct(Rc<Struct>),
+    Enum,
+}
+
+//ブロックのスコープ
+#[derive(Default)]
+struct Scope {
+    //変数、列挙定数、typedefの名前
+    vars: HashMap<String, VarScope>,
+    //構造体、共用体、列挙型のタグ
+    tags: HashMap<String, Tag>,
+}
+
+//構文解析の状態
+struct Env {
+    //ブロックごとのスコープ(先頭がファイル全体、末尾が最も内側のブロック)
+    scopes: Vec<Scope>,
+    //解析中の関数のローカル変数の領域のバイト数
+    stack_size: usize,
+    //解析中の関数の戻り値の型
+    ret: Type,
+    //解析中の関数が可変長引数をとる場合は、レジスタ保存領域のオフセットと
+    //名前付きの整数と浮動小数点数の仮引数の数
+    va_area: Option<(usize, usize, usize)>,
+    //組み込みのva_listの型
+    va_list: Type,
+    //宣言されていない変数を暗黙に宣言するか(関数の外に書かれた文のみ)
+    implicit: bool,
+    //グローバル変数の定義
+    globals: Vec<Global>,
+    //グローバルな変数と関数の名前ごとに、最初の宣言でstaticとしたか
+    linkages: HashMap<String, bool>,
+    //初期化子のない仮定義しかされていないグローバル変数のラベル
+    tentatives: HashSet<String>,
+    //解析中の関数の名前(静的ローカル変数のラベルに使う)
+    func_name: String,
+    //breakで抜けられる文(ループかswitch文)の中にいるか
+    breakable: bool,
+}
+
+impl Env {
+    //ブロックに入る
+    fn enter_scope(&mut self) {
+        self.scopes.push(Scope::default());
+    }
+
+    //ブロックを出る
+    fn leave_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    //現在のブロックに変数、列挙定数、typedefの名前を登録する
+    fn push_scope(&mut self, name: &str, entry: VarScope) {
+        let scope = self.scopes.last_mut().unwrap();
+        assert!(
+            !scope.vars.contains_key(name),
+            "変数{}が同じブロックで二重に宣言されています。プログラムを終了します。",
+            name
+        );
+        scope.vars.insert(name.to_string(), entry);
+    }
+
+    //現在のブロックに変数の名前を登録する
+    fn push_var(&mut self, obj: &Rc<Obj>) {
+        self.push_scope(&obj.name, VarScope::Var(Rc::clone(obj)));
+    }
+
+    //ファイル全体のスコープに関数を宣言する
+    //既に宣言されていれば、型が一致していなければならない
+    //以前の宣言がプロトタイプでなければ、新しい宣言のプロトタイプを採用する
+    fn declare_func(&mut self, name: &str, func: FuncType) -> Rc<FuncType> {
+        match self.scopes[0].vars.get(name) {
+            Some(VarScope::Func(declared)) => {
+                assert!(
+                    declared.is_same(&func),
+                    "関数{}の型が以前の宣言と一致しません。プログラムを終了します。",
+                    name
+                );
+                if declared.is_prototyped || !func.is_prototyped {
+                    return Rc::clone(declared);
+                }
+            }
+            Some(_) => panic!(
+                "{}は関数以外として宣言されています。プログラムを終了します。",
+                name
+            ),
+            None => (),
+        }
+        let func = Rc::new(func);
+        self.scopes[0]
+            .vars
+            .insert(name.to_string(), VarScope::Func(Rc::clone(&func)));
+        func
+    }
+
+    //フレームにローカル変数の領域を割り当てる
+    //変数は型のアラインメントに揃えて、フレームの先頭から順に下に置く
+    fn new_local(&mut self, name: &str, ty: Type) -> Rc<Obj> {
+        self.stack_size = align_to(self.stack_size + ty.size(), ty.align());
+        Rc::new(Obj {
+            name: name.to_string(),
+            ty,
+            offset: Some(self.stack_size),
+        })
+    }
+
+    //現在のブロックにローカル変数を宣言する
+    fn declare(&mut self, name: &str, ty: Type) -> Rc<Obj> {
+        let obj = self.new_local(name, ty);
+        self.push_var(&obj);
+        obj
+    }
+
+    //現在のブロックにグローバル変数を宣言する
+    //静的ローカル変数はブロック内の名前nameと異なるラベルlabelで定義する
+    //externで宣言済みの変数は、同じ型で定義し直せる(要素数を省略した配列は要素数を決められる)
+    fn declare_global(&mut self, name: &str, label: &str, ty: Type) -> Rc<Obj> {
+        let obj = Rc::new(Obj {
+            name: label.to_string(),
+            ty,
+            offset: None,
+        });
+        let scope = self.scopes.last_mut().unwrap();
+        match scope.vars.get(name) {
+            Some(VarScope::Var(declared))
+                if declared.offset.is_none() && declared.name == label =>
+            {
+                let is_same = match (&declared.ty, &obj.ty) {
+                    (Type::Array(base, None), Type::Array(other, _)) => base.is_same(other),
+                    (declared, ty) => declared.is_same(ty),
+                };
+                assert!(
+                    is_same,
+                    "変数{}の型が以前の宣言と一致しません。プログラムを終了します。",
+                    name
+                );
+                scope
+                    .vars
+                    .insert(name.to_string(), VarScope::Var(Rc::clone(&obj)));
+            }
+            _ => self.push_scope(name, VarScope::Var(Rc::clone(&obj))),
+        }
+        obj
+    }
+
+    //グローバルな名前のリンケージを決め、staticであれば真を返す
+    //最初の宣言でstaticとした名前は、後の宣言でstaticを省略してもstaticのままにする
+    fn linkage(&mut self, name: &str, is_static: bool) -> bool {
+        let first = *self.linkages.entry(name.to_string()).or_insert(is_static);
+        assert!(
+            first || !is_static,
+            "{}はstaticではないものとして宣言済みです。プログラムを終了します。",
+            name
+        );
+        first
+    }
+
+    //内側のブロックから順に名前を探す
+    fn lookup(&self, name: &str) -> Option<&VarScope> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.vars.get(name))
+    }
+
+    //名前がtypedefで宣言された型名であればその型を返す
+    //内側のブロックで同じ名前の変数が宣言されていれば型名ではない
+    fn find_typedef(&self, name: &str) -> Option<Type> {
+        match self.lookup(name) {
+            Some(VarScope::Typedef(ty)) => Some(ty.clone()),
+            _ => None,
+        }
+    }
+
+    //内側のブロックから順に変数と列挙定数を探す
+    //関数の外に書かれた文では、見つからなければmain関数の一番外側のブロックで変数を宣言する
+    fn find_var(&mut self, name: &str) -> VarScope {
+        if let Some(entry) = self.lookup(name) {
+            assert!(
+                !matches!(entry, VarScope::Typedef(_)),
+                "型名{}を式の中で使っています。プログラムを終了します。",
+                name
+            );
+            return entry.clone();
+        }
+        assert!(
+            self.implicit,
+            "変数{}は宣言されていません。プログラムを終了します。",
+            name
+        );
+        let obj = self.new_local(name, Type::Int);
+        self.scopes[1]
+            .vars
+            .insert(name.to_string(), VarScope::Var(Rc::clone(&obj)));
+        VarScope::Var(obj)
+    }
+
+    //内側のブロックから順にタグを探す
+    fn find_tag(&self, name: &str) -> Option<Tag> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.tags.get(name))
+            .cloned()
+    }
+}
+
+//変数の初期化子
+//配列と構造体は要素ごとの初期化子を持ち、省略された要素は0で初期化する
+struct Initializer {
+    ty: Type,
+    //式で初期化する場合の式(スカラー、または同じ型の構造体)
+    expr: Option<Node>,
+    //配列の各要素、または構造体の各メンバの初期化子
+    children: Vec<Initializer>,
+}
+
+impl Initializer {
+    //すべての要素を省略した初期化子
+    //要素数を省略した配列は、初期化子を解析しながら要素を追加する
+    fn new(ty: Type) -> Self {
+        let children = match &ty {
+            Type::Array(base, Some(len)) => (0..*len)
+                .map(|_| Initializer::new((**base).clone()))
+                .collect(),
+            Type::Struct(st) => st
+                .members()
+                .into_iter()
+                .map(|member| Initializer::new(member.ty))
+                .collect(),
+            _ => Vec::new(),
+        };
+        Initializer {
+            ty,
+            expr: None,
+            children,
+        }
+    }
+}
 
 //外部から呼び出される関数
-pub fn program(tokens: &Vec<Kind>) -> Vec<Node> {
+// program = (function | global-decl | typedef | stmt)*
+// 関数の外に書かれた文はまとめてmain関数の本体とする
+pub fn program(tokens: &Vec<Kind>) -> Program {
     //トークン列から構文木を生成
     let mut progress = 0;
+    let mut functions: Vec<Function> = Vec::new();
+    let mut env = Env {
+        scopes: vec![Scope::default()],
+        stack_size: 0,
+        ret: Type::Int,
+        va_area: None,
+        va_list: new_va_list(),
+        implicit: false,
+        globals: Vec::new(),
+        linkages: HashMap::new(),
+        tentatives: HashSet::new(),
+        func_name: String::new(),
+        breakable: false,
+    };
+    // va_listは組み込みの型名とする
+    let va_list = VarScope::Typedef(env.va_list.clone());
+    env.push_scope("va_list", va_list);
+    //関数の外に書かれた文とその変数
     let mut nodes: Vec<Node> = Vec::new();
+    let mut main_scope = Scope::default();
+    let mut main_stack_size = 0;
     let mut ret_node;
-    //文単位で保存
     while progress < tokens.len() {
-        (ret_node, progress) = stmt(tokens, progress);
+        if let Some(Kind::Keyword(Keyword::Typedef)) = tokens.get(progress) {
+            progress = typedef(tokens, progress + 1, &mut env);
+            continue;
+        }
+        if is_declaration(tokens, progress, &env) {
+            let function;
+            (function, progress) = global_decl(tokens, progress, &mut env);
+            if let Some(function) = function {
+                assert!(
+                    functions.iter().all(|f| f.name != function.name),
+                    "関数{}が二重に定義されています。プログラムを終了します。",
+                    function.name
+                );
+                functions.push(function);
+            }
+            continue;
+        }
+        //文単位で保存
+        //関数の外に書かれた文はmain関数のスコープで解析する
+        env.scopes.push(mem::take(&mut main_scope));
+        env.stack_size = main_stack_size;
+        env.ret = Type::Int;
+        env.va_area = None;
+        env.implicit = true;
+        env.func_name = "main".to_string();
+        (ret_node, progress) = stmt(tokens, progress, &mut env);
+        main_scope = env.scopes.pop().unwrap();
+        main_stack_size = env.stack_size;
+        env.implicit = false;
         nodes.push(ret_node);
     }
-    nodes
+    if !nodes.is_empty() {
+        assert!(
+            functions.iter().all(|f| f.name != "main"),
+            "main関数があるときは関数の外に文を書けません。プログラムを終了します。"
+        );
+        functions.push(Function {
+            name: "main".to_string(),
+            params: Vec::new(),
+            body: nodes,
+            stack_size: main_stack_size,
+            va_area: None,
+            is_static: false,
+        });
+    }
+    Program {
+        globals: env.globals,
+        functions,
+    }
+}
+
+//宣言(記憶域クラス指定子か型名)で始まっていれば真を返す
+fn is_declaration(tokens: &[Kind], progress: usize, env: &Env) -> bool {
+    storage_class(tokens, progress).0.is_some() || is_typename(tokens, progress, env)
+}
+
+// storage-class = "static" | "extern"
+// 記憶域クラス指定子があればそのキーワードを返す
+fn storage_class(tokens: &[Kind], progress: usize) -> (Option<Keyword>, usize) {
+    match tokens.get(progress) {
+        Some(Kind::Keyword(keyword @ (Keyword::Static | Keyword::Extern))) => {
+            (Some(*keyword), progress + 1)
+        }
+        _ => (None, progress),
+    }
+}
+
+//型名で始まっていれば真を返す
+//識別子はtypedefで宣言された型名であれば型名とする
+fn is_typename(tokens: &[Kind], progress: usize, env: &Env) -> bool {
+    match tokens.get(progress) {
+        Some(Kind::Keyword(keyword)) => matches!(
+            keyword,
+            Keyword::Char
+                | Keyword::Short
+                | Keyword::Int
+                | Keyword::Long
+                | Keyword::Float
+                | Keyword::Double
+                | Keyword::Signed
+                | Keyword::Unsigned
+                | Keyword::Struct
+                | Keyword::Union
+                | Keyword::Enum
+        ),
+        Some(Kind::Ident(name)) => env.find_typedef(name).is_some(),
+        _ => false,
+    }
+}
+
+// global-decl = storage-class? declspec (init-declarator ("," init-declarator)*)? ";"
+// init-declarator = declarator ("=" initializer)? | declarator func-params
+// function = storage-class? declspec declarator func-params "{" stmt* "}"
+// 関数定義であれば関数を返す
+// staticの関数と変数はローカルなシンボルにし、externの変数はほかの翻訳単位で定義されたものを参照する
+fn global_decl(tokens: &Vec<Kind>, progress: usize, env: &mut Env) -> (Option<Function>, usize) {
+    let (storage, progress) = storage_class(tokens, progress);
+    let (base, mut progress) = declspec(tokens, progress, env);
+    if let Some(Kind::Semicolon) = tokens.get(progress) {
+        // 構造体、共用体、列挙型の宣言のみ
+        return (None, progress + 1);
+    }
+    loop {
+        let (ty, name);
+        (ty, name, progress) = declarator(tokens, progress, base.clone(), env);
+        let is_static = env.linkage(&name, storage == Some(Keyword::Static));
+        if let Some(Kind::RoundBracOpen) = tokens.get(progress) {
+            // int f();のように仮引数を書かない宣言はプロトタイプとしない(定義はプロトタイプとする)
+            let empty = matches!(tokens.get(progress + 1), Some(Kind::RoundBracClose));
+            let (params, is_variadic);
+            (params, is_variadic, progress) = func_params(tokens, progress + 1, &name, env);
+            let is_definition = matches!(tokens.get(progress), Some(Kind::CurlyBracOpen));
+            assert!(
+                !ty.is_struct(),
+                "構造体を返す関数には対応していません。プログラムを終了します。"
+            );
+            env.declare_func(
+                &name,
+                FuncType {
+                    ret: ty.clone(),
+                    params: params.iter().map(|(_, ty)| ty.clone()).collect(),
+                    is_variadic,
+                    is_prototyped: !empty || is_definition,
+                },
+            );
+            if is_definition {
+                let mut function;
+                (function, progress) =
+                    function_def(tokens, progress, name, ty, params, is_variadic, env);
+                function.is_static = is_static;
+                return (Some(function), progress);
+            }
+            // プロトタイプ宣言のみ
+            match tokens.get(progress) {
+                Some(Kind::Comma) => progress += 1,
+                Some(Kind::Semicolon) => return (None, progress + 1),
+                _ => panic!(
+                    "関数{}の宣言の終わりに;が付いていません。プログラムを終了します。",
+                    name
+                ),
+            }
+            continue;
+        }
+        progress = match storage {
+            Some(Keyword::Extern) => declare_extern(tokens, progress, &name, ty, env),
+            _ => define_global(tokens, progress, &name, &name, ty, is_static, env),
+        };
+        match tokens.get(progress) {
+            Some(Kind::Comma) => progress += 1,
+            Some(Kind::Semicolon) => return (None, progress + 1),
+            _ => panic!("変数の宣言の終わりに;が付いていません。プログラムを終了します。"),
+        }
+    }
+}
+
+//グローバル変数を定義する(静的ローカル変数はブロック内の名前nameと異なるラベルlabelで定義する)
+//初期化子は定数式でなければならない
+//要素数を省略した配列は、初期化子から要素数を決めてから宣言する
+//初期化子のない定義は仮定義とし、0で初期化しておいて後の定義の初期化子で置き換える
+fn define_global(
+    tokens: &Vec<Kind>,
+    mut progress: usize,
+    name: &str,
+    label: &str,
+    ty: Type,
+    is_static: bool,
+    env: &mut Env,
+) -> usize {
+    let has_init = matches!(tokens.get(progress), Some(Kind::Assign));
+    let defined = env
+        .globals
+        .iter()
+        .position(|global| global.obj.name == label);
+    if let Some(index) = defined {
+        if !has_init {
+            // 定義済みの変数の仮定義は宣言として扱う
+            let ty = match ty {
+                Type::Array(_, None) => env.globals[index].obj.ty.clone(),
+                ty => ty,
+            };
+            env.declare_global(name, label, ty);
+            return progress;
+        }
+        assert!(
+            env.tentatives.remove(label),
+            "変数{}が二重に定義されています。プログラムを終了します。",
+            name
+        );
+        env.globals.remove(index);
+    } else if !has_init {
+        env.tentatives.insert(label.to_string());
+    }
+    let obj = match ty {
+        Type::Array(_, None) => None,
+        _ => Some(env.declare_global(name, label, ty.clone())),
+    };
+    let mut init = Initializer::new(ty);
+    if has_init {
+        progress = initializer(tokens, progress + 1, &mut init, env);
+    }
+    let obj = obj.unwrap_or_else(|| env.declare_global(name, label, init.ty.clone()));
+    let mut data = vec![0; obj.ty.size()];
+    let mut relocs = Vec::new();
+    gvar_data(init, 0, &mut data, &mut relocs);
+    env.globals.push(Global {
+        obj,
+        init: data,
+        relocs,
+        is_static,
+    });
+    progress
+}
+
+//externで、ほかで定義されたグローバル変数を宣言する
+fn declare_extern(tokens: &[Kind], progress: usize, name: &str, ty: Type, env: &mut Env) -> usize {
+    assert!(
+        !matches!(tokens.get(progress), Some(Kind::Assign)),
+        "externで宣言した変数{}は初期化できません。プログラムを終了します。",
+        name
+    );
+    env.declare_global(name, name, ty);
+    progress
+}
+
+// 配列の要素数、列挙定数の値、caseのラベルに使う整数定数
+// const-int = equality
+// コンパイル時に値を求められる整数の式でなければならない
+fn const_int(tokens: &Vec<Kind>, progress: usize, env: &mut Env) -> (i64, usize) {
+    let (mut node, progress) = equality(tokens, progress, env);
+    add_type(&mut node);
+    assert!(
+        node.ty.as_ref().unwrap().is_integer(),
+        "整数定数があるべき箇所に整数以外の式があります。プログラムを終了します。"
+    );
+    (consteval::eval(&node), progress)
+}
+
+//グローバル変数の初期化子を、offsetバイト目からのバイト列とアドレスの埋め込み位置にする
+//省略された要素は0のままにする
+fn gvar_data(init: Initializer, offset: usize, data: &mut [u8], relocs: &mut Vec<Reloc>) {
+    if let Some(expr) = init.expr {
+        assert!(
+            !init.ty.is_struct(),
+            "グローバル変数の構造体は式で初期化できません。プログラムを終了します。"
+        );
+        let size = init.ty.size();
+        let (label, value) = consteval::eval_reloc(&new_conv(expr, &init.ty));
+        match label {
+            Some(label) => relocs.push(Reloc {
+                offset,
+                label,
+                addend: value,
+            }),
+            None => data[offset..offset + size].copy_from_slice(&value.to_le_bytes()[..size]),
+        }
+        return;
+    }
+    match &init.ty {
+        Type::Array(base, _) => {
+            let size = base.size();
+            for (index, child) in init.children.into_iter().enumerate() {
+                gvar_data(child, offset + size * index, data, relocs);
+            }
+        }
+        // 共用体は最初のメンバのみ初期化する
+        Type::Struct(st) => {
+            let count = if st.is_union { 1 } else { init.children.len() };
+            for (child, member) in init.children.into_iter().zip(st.members()).take(count) {
+                gvar_data(child, offset + member.offset, data, relocs);
+            }
+        }
+        _ => {}
+    }
+}
+
+// func-params = "(" ("void" | param ("," param)* ("," "...")?)? ")"
+// param = declspec "*"* ident?
+// 仮引数名は関数定義でのみ必要で、プロトタイプ宣言では省略できる
+// 仮引数と、可変長引数をとるかを返す
+fn func_params(
+    tokens: &Vec<Kind>,
+    mut progress: usize,
+    name: &str,
+    env: &mut Env,
+) -> (Vec<(Option<String>, Type)>, bool, usize) {
+    let mut params = Vec::new();
+    if let (Some(Kind::Keyword(Keyword::Void)), Some(Kind::RoundBracClose)) =
+        (tokens.get(progress), tokens.get(progress + 1))
+    {
+        return (params, false, progress + 2);
+    }
+    if let Some(Kind::RoundBracClose) = tokens.get(progress) {
+        return (params, false, progress + 1);
+    }
+    loop {
+        if let (Some(Kind::Ellipsis), false) = (tokens.get(progress), params.is_empty()) {
+            assert!(
+                matches!(tokens.get(progress + 1), Some(Kind::RoundBracClose)),
+                "関数{}の...は仮引数の最後に置いてください。プログラムを終了します。",
+                name
+            );
+            return (params, true, progress + 2);
+        }
+        let (base, ty);
+        (base, progress) = declspec(tokens, progress, env);
+        (ty, progress) = pointers(tokens, progress, base);
+        assert!(
+            !ty.is_struct(),
+            "構造体を値で受け取る仮引数には対応していません。プログラムを終了します。"
+        );
+        let param = match tokens.get(progress) {
+            Some(Kind::Ident(param)) => {
+                progress += 1;
+                Some(param.clone())
+            }
+            _ => None,
+        };
+        params.push((param, ty));
+        match tokens.get(progress) {
+            Some(Kind::Comma) => progress += 1,
+            Some(Kind::RoundBracClose) => return (params, false, progress + 1),
+            _ => panic!(
+                "関数{}の仮引数の記述が不正です。プログラムを終了します。",
+                name
+            ),
+        }
+    }
+}
+
+// 仮引数は関数本体と同じスコープに置く
+// 可変長引数の関数では、仮引数の後にレジスタ保存領域を確保する
+fn function_def(
+    tokens: &Vec<Kind>,
+    mut progress: usize,
+    name: String,
+    ret: Type,
+    params: Vec<(Option<String>, Type)>,
+    is_variadic: bool,
+    env: &mut Env,
+) -> (Function, usize) {
+    env.enter_scope();
+    env.stack_size = 0;
+    env.ret = ret;
+    env.func_name.clone_from(&name);
+    let params: Vec<Rc<Obj>> = params
+        .into_iter()
+        .map(|(param, ty)| {
+            let param = param.unwrap_or_else(|| {
+                panic!(
+                    "関数{}の定義で仮引数名が省略されています。プログラムを終了します。",
+                    name
+                )
+            });
+            env.declare(&param, ty)
+        })
+        .collect();
+    env.va_area = if is_variadic {
+        env.stack_size = align_to(env.stack_size + VA_AREA_SIZE, 16);
+        let fp = params.iter().filter(|param| param.ty.is_flonum()).count();
+        Some((env.stack_size, params.len() - fp, fp))
+    } else {
+        None
+    };
+    let body;
+    (body, progress) = stmt(tokens, progress, env);
+    env.leave_scope();
+    (
+        Function {
+            name,
+            params,
+            body: vec![body],
+            stack_size: env.stack_size,
+            va_area: env.va_area.map(|(offset, _, _)| offset),
+            is_static: false,
+        },
+        progress,
+    )
+}
+
+// typedef = "typedef" declspec declarator ("," declarator)* ";"
+// 宣言した名前を型名として現在のブロックに登録する
+fn typedef(tokens: &Vec<Kind>, progress: usize, env: &mut Env) -> usize {
+    let (base, mut progress) = declspec(tokens, progress, env);
+    loop {
+        let (ty, name);
+        (ty, name, progress) = declarator(tokens, progress, base.clone(), env);
+        env.push_scope(&name, VarScope::Typedef(ty));
+        match tokens.get(progress) {
+            Some(Kind::Comma) => progress += 1,
+            Some(Kind::Semicolon) => return progress + 1,
+            _ => panic!("typedefの終わりに;が付いていません。プログラムを終了します。"),
+        }
+    }
+}
+
+// declspec = basic-type | struct-decl | union-decl | enum-decl | typedef-name
+fn declspec(tokens: &Vec<Kind>, progress: usize, env: &mut Env) -> (Type, usize) {
+    match tokens.get(progress) {
+        Some(Kind::Keyword(Keyword::Struct)) => struct_decl(tokens, progress + 1, env, false),
+        Some(Kind::Keyword(Keyword::Union)) => struct_decl(tokens, progress + 1, env, true),
+        Some(Kind::Keyword(Keyword::Enum)) => enum_decl(tokens, progress + 1, env),
+        Some(Kind::Ident(name)) if env.find_typedef(name).is_some() => {
+            (env.find_typedef(name).unwrap(), progress + 1)
+        }
+        _ => basic_type(tokens, progress),
+    }
+}
+
+// basic-type = ("char" | "short" | "int" | "long" | "float" | "double" | "signed" | "unsigned")+
+// キーワードは任意の順で並べられ、longは2つまで重ねられる
+// long doubleはdoubleとして扱う
+fn basic_type(tokens: &[Kind], mut progress: usize) -> (Type, usize) {
+    // 各キーワードの出現回数
+    let (mut char, mut short, mut int, mut long) = (0, 0, 0, 0);
+    let (mut float, mut double, mut signed, mut unsigned) = (0, 0, 0, 0);
+    while let Some(Kind::Keyword(keyword)) = tokens.get(progress) {
+        match keyword {
+            Keyword::Char => char += 1,
+            Keyword::Short => short += 1,
+            Keyword::Int => int += 1,
+            Keyword::Long => long += 1,
+            Keyword::Float => float += 1,
+            Keyword::Double => double += 1,
+            Keyword::Signed => signed += 1,
+            Keyword::Unsigned => unsigned += 1,
+            _ => break,
+        }
+        progress += 1;
+    }
+    assert!(
+        char + short + int + long + float + double + signed + unsigned > 0,
+        "型名があるべき箇所にありません。プログラムを終了します。"
+    );
+    if float + double > 0 {
+        assert!(
+            char + short + int + signed + unsigned == 0
+                && float + double == 1
+                && (long == 0 || (double == 1 && long == 1)),
+            "型名の組み合わせが不正です。プログラムを終了します。"
+        );
+        let ty = if float == 1 {
+            Type::Float
+        } else {
+            Type::Double
+        };
+        return (ty, progress);
+    }
+    assert!(
+        char <= 1
+            && short <= 1
+            && int <= 1
+            && long <= 2
+            && signed + unsigned <= 1
+            && (char == 0 || short + int + long == 0)
+            && (short == 0 || long == 0),
+        "型名の組み合わせが不正です。プログラムを終了します。"
+    );
+    let ty = match (char, short, long, unsigned) {
+        (1, _, _, 0) => Type::Char,
+        (1, _, _, _) => Type::UChar,
+        (_, 1, _, 0) => Type::Short,
+        (_, 1, _, _) => Type::UShort,
+        (_, _, 0, 0) => Type::Int,
+        (_, _, 0, _) => Type::UInt,
+        (_, _, _, 0) => Type::Long,
+        _ => Type::ULong,
+    };
+    (ty, progress)
+}
+
+// declarator = "*"* ident type-suffix
+fn declarator(
+    tokens: &Vec<Kind>,
+    progress: usize,
+    ty: Type,
+    env: &mut Env,
+) -> (Type, String, usize) {
+    let (ty, progress) = pointers(tokens, progress, ty);
+    let (name, progress) = expect_ident(tokens, progress);
+    let (ty, progress) = type_suffix(tokens, progress, ty, env);
+    (ty, name, progress)
+}
+
+// type-suffix = ("[" const-int? "]")*
+// int a[2][3]は「intの3要素の配列」の2要素の配列とする
+// 要素数を省略できるのは最初の[]のみ
+fn type_suffix(tokens: &Vec<Kind>, progress: usize, ty: Type, env: &mut Env) -> (Type, usize) {
+    if !matches!(tokens.get(progress), Some(Kind::SquareBracOpen)) {
+        return (ty, progress);
+    }
+    let mut progress = progress + 1;
+    let mut len = None;
+    if !matches!(tokens.get(progress), Some(Kind::SquareBracClose)) {
+        let value;
+        (value, progress) = const_int(tokens, progress, env);
+        len = Some(
+            usize::try_from(value)
+                .unwrap_or_else(|_| panic!("配列の要素数が負の値です。プログラムを終了します。")),
+        );
+    }
+    assert!(
+        matches!(tokens.get(progress), Some(Kind::SquareBracClose)),
+        "配列の要素数の後に]がありません。プログラムを終了します。"
+    );
+    let (base, progress) = type_suffix(tokens, progress + 1, ty, env);
+    assert!(
+        !matches!(base, Type::Array(_, None)),
+        "配列の要素数を省略できるのは最初の[]のみです。プログラムを終了します。"
+    );
+    (Type::Array(Rc::new(base), len), progress)
+}
+
+// type-name = declspec "*"* type-suffix
+// 変数名を省略した宣言の型
+fn type_name(tokens: &Vec<Kind>, progress: usize, env: &mut Env) -> (Type, usize) {
+    let (ty, progress) = declspec(tokens, progress, env);
+    let (ty, progress) = pointers(tokens, progress, ty);
+    type_suffix(tokens, progress, ty, env)
+}
+
+// "*"*
+// 型の前に付いた*の数だけポインタにした型を返す
+fn pointers(tokens: &[Kind], mut progress: usize, mut ty: Type) -> (Type, usize) {
+    while let Some(Kind::Mul) = tokens.get(progress) {
+        ty = Type::Ptr(Rc::new(ty));
+        progress += 1;
+    }
+    (ty, progress)
+}
+
+// struct-decl = "struct" ident? ("{" (declspec declarator ("," declarator)* ";")* "}")?
+// union-decl = "union" ident? ("{" (declspec declarator ("," declarator)* ";")* "}")?
+// タグだけの場合は宣言済みの構造体を指し、無ければ不完全型として宣言する
+fn struct_decl(
+    tokens: &Vec<Kind>,
+    mut progress: usize,
+    env: &mut Env,
+    is_union: bool,
+) -> (Type, usize) {
+    let mut tag = None;
+    if let Some(Kind::Ident(name)) = tokens.get(progress) {
+        tag = Some(name.clone());
+        progress += 1;
+    }
+    if !matches!(tokens.get(progress), Some(Kind::CurlyBracOpen)) {
+        let name = tag.unwrap_or_else(|| {
+            panic!("構造体のタグもメンバもありません。プログラムを終了します。")
+        });
+        let st = match env.find_tag(&name) {
+            Some(Tag::Struct(st)) if st.is_union == is_union => st,
+            Some(_) => panic!(
+                "タグ{}は別の種類の型として宣言されています。プログラムを終了します。",
+                name
+            ),
+            None => {
+                let st = Rc::new(Struct::new(is_union));
+                let scope = env.scopes.last_mut().unwrap();
+                scope.tags.insert(name, Tag::Struct(Rc::clone(&st)));
+                st
+            }
+        };
+        return (Type::Struct(st), progress);
+    }
+    progress += 1;
+
+    // 同じブロックで宣言済みの不完全型があればそれを定義する
+    // メンバから自分自身を指せるように、メンバより先にタグを登録する
+    let scope = env.scopes.last_mut().unwrap();
+    let st = match tag.as_ref().and_then(|name| scope.tags.get(name)) {
+        Some(Tag::Struct(st)) if st.is_union == is_union && st.layout.borrow().is_none() => {
+            Rc::clone(st)
+        }
+        Some(_) => panic!(
+            "タグ{}が二重に定義されています。プログラムを終了します。",
+            tag.unwrap()
+        ),
+        None => {
+            let st = Rc::new(Struct::new(is_union));
+            if let Some(name) = tag {
+                scope.tags.insert(name, Tag::Struct(Rc::clone(&st)));
+            }
+            st
+        }
+    };
+
+    let mut members = Vec::new();
+    while !matches!(tokens.get(progress), Some(Kind::CurlyBracClose)) {
+        let base;
+        (base, progress) = declspec(tokens, progress, env);
+        loop {
+            let (ty, name);
+            (ty, name, progress) = declarator(tokens, progress, base.clone(), env);
+            members.push((name, ty));
+            match tokens.get(progress) {
+                Some(Kind::Comma) => progress += 1,
+                Some(Kind::Semicolon) => {
+                    progress += 1;
+                    break;
+                }
+                _ => panic!("メンバの宣言の終わりに;が付いていません。プログラムを終了します。"),
+            }
+        }
+    }
+    st.define(members);
+    (Type::Struct(st), progress + 1)
+}
+
+// enum-decl = "enum" ident? ("{" enumerator ("," enumerator)* ","? "}")?
+// enumerator = ident ("=" const-int)?
+// 列挙型はintとして扱い、列挙定数は変数と同じスコープに登録する
+fn enum_decl(tokens: &Vec<Kind>, mut progress: usize, env: &mut Env) -> (Type, usize) {
+    let mut tag = None;
+    if let Some(Kind::Ident(name)) = tokens.get(progress) {
+        tag = Some(name.clone());
+        progress += 1;
+    }
+    if !matches!(tokens.get(progress), Some(Kind::CurlyBracOpen)) {
+        // 列挙型は前方宣言できない
+        let name = tag.unwrap_or_else(|| {
+            panic!("列挙型のタグも列挙定数もありません。プログラムを終了します。")
+        });
+        match env.find_tag(&name) {
+            Some(Tag::Enum) => return (Type::Int, progress),
+            Some(_) => panic!(
+                "タグ{}は別の種類の型として宣言されています。プログラムを終了します。",
+                name
+            ),
+            None => panic!(
+                "列挙型{}は定義されていません。プログラムを終了します。",
+                name
+            ),
+        }
+    }
+    progress += 1;
+    if let Some(name) = tag {
+        let scope = env.scopes.last_mut().unwrap();
+        assert!(
+            !scope.tags.contains_key(&name),
+            "タグ{}が二重に定義されています。プログラムを終了します。",
+            name
+        );
+        scope.tags.insert(name, Tag::Enum);
+    }
+
+    // 値を省略した列挙定数は直前の値に1を足した値になる
+    let mut value = 0;
+    loop {
+        let name;
+        (name, progress) = expect_ident(tokens, progress);
+        if let Some(Kind::Assign) = tokens.get(progress) {
+            (value, progress) = const_int(tokens, progress + 1, env);
+        }
+        env.push_scope(&name, VarScope::EnumConst(value));
+        value = value.wrapping_add(1);
+        match (tokens.get(progress), tokens.get(progress + 1)) {
+            (Some(Kind::Comma), Some(Kind::CurlyBracClose)) => return (Type::Int, progress + 2),
+            (Some(Kind::Comma), _) => progress += 1,
+            (Some(Kind::CurlyBracClose), _) => return (Type::Int, progress + 1),
+            _ => panic!("列挙定数の記述が不正です。プログラムを終了します。"),
+        }
+    }
+}
+
+// declaration = storage-class? declspec (init-declarator ("," init-declarator)*)? ";"
+// init-declarator = declarator ("=" initializer)?
+// 初期化子は要素ごとの代入式の式文として、宣言全体を{}の中の文と同じ形のノードにする
+// 静的ローカル変数は関数名と変数名から作ったラベルのグローバル変数として定義し、
+// externの変数はほかで定義されたグローバル変数を参照する
+fn declaration(tokens: &Vec<Kind>, progress: usize, env: &mut Env) -> (Node, usize) {
+    let (storage, progress) = storage_class(tokens, progress);
+    let (base, mut progress) = declspec(tokens, progress, env);
+    let mut node = Node {
+        kind: Kind::CurlyBracOpen,
+        lhs: None,
+        rhs: None,
+        ty: None,
+    };
+    if let Some(Kind::Semicolon) = tokens.get(progress) {
+        // 構造体、共用体、列挙型の宣言のみ
+        return (node, progress + 1);
+    }
+    loop {
+        let (ty, name);
+        (ty, name, progress) = declarator(tokens, progress, base.clone(), env);
+        if let Some(Keyword::Static) = storage {
+            let label = format!("{}.{}.{}", env.func_name, name, env.globals.len());
+            progress = define_global(tokens, progress, &name, &label, ty, true, env);
+        } else if let Some(Keyword::Extern) = storage {
+            progress = declare_extern(tokens, progress, &name, ty, env);
+        } else if let Some(Kind::Assign) = tokens.get(progress) {
+            // 要素数を省略した配列は、初期化子から要素数を決めてから宣言する
+            let obj = match ty {
+                Type::Array(_, None) => None,
+                _ => Some(env.declare(&name, ty.clone())),
+            };
+            let mut init = Initializer::new(ty);
+            progress = initializer(tokens, progress + 1, &mut init, env);
+            let obj = obj.unwrap_or_else(|| env.declare(&name, init.ty.clone()));
+            let var = || {
+                decay(Node {
+                    kind: Kind::Var(Rc::clone(&obj)),
+                    lhs: None,
+                    rhs: None,
+                    ty: None,
+                })
+            };
+            node = init_stmts(init, &var, node);
+        } else {
+            env.declare(&name, ty);
+        }
+        match tokens.get(progress) {
+            Some(Kind::Comma) => progress += 1,
+            Some(Kind::Semicolon) => return (node, progress + 1),
+            _ => panic!("変数の宣言の終わりに;が付いていません。プログラムを終了します。"),
+        }
+    }
+}
+
+// initializer = str | "{" (initializer ("," initializer)* ","?)? "}" | assign
+// charの配列は文字列リテラルで初期化でき、スカラーの初期化子は{}で囲んでもよい
+fn initializer(
+    tokens: &Vec<Kind>,
+    progress: usize,
+    init: &mut Initializer,
+    env: &mut Env,
+) -> usize {
+    match (init.ty.clone(), tokens.get(progress)) {
+        (Type::Array(base, len), Some(Kind::Str(bytes)))
+            if base.is_integer() && base.size() == 1 =>
+        {
+            // 終端の0も含めて、配列に収まる分の文字で初期化する
+            let mut bytes = bytes.clone();
+            bytes.push(0);
+            if len.is_none() {
+                *init = Initializer::new(Type::Array(base, Some(bytes.len())));
+            }
+            for (child, byte) in init.children.iter_mut().zip(bytes) {
+                child.expr = Some(new_num(i64::from(byte)));
+            }
+            progress + 1
+        }
+        (Type::Array(base, len), Some(Kind::CurlyBracOpen)) => {
+            let mut progress = progress + 1;
+            let mut count = 0;
+            while !matches!(tokens.get(progress), Some(Kind::CurlyBracClose)) {
+                if len.is_none() {
+                    init.children.push(Initializer::new((*base).clone()));
+                }
+                let child = init.children.get_mut(count).unwrap_or_else(|| {
+                    panic!("配列の要素数より初期化子が多くあります。プログラムを終了します。")
+                });
+                progress = initializer(tokens, progress, child, env);
+                progress = initializer_separator(tokens, progress);
+                count += 1;
+            }
+            if len.is_none() {
+                init.ty = Type::Array(base, Some(count));
+            }
+            progress + 1
+        }
+        (Type::Array(..), _) => {
+            panic!(
+                "配列の初期化子を{}で囲んでいません。プログラムを終了します。",
+                "{}"
+            )
+        }
+        // 共用体は最初のメンバのみ初期化できる
+        (Type::Struct(st), Some(Kind::CurlyBracOpen)) => {
+            let max = if st.is_union { 1 } else { init.children.len() };
+            let mut progress = progress + 1;
+            let mut count = 0;
+            while !matches!(tokens.get(progress), Some(Kind::CurlyBracClose)) {
+                assert!(
+                    count < max,
+                    "構造体のメンバより初期化子が多くあります。プログラムを終了します。"
+                );
+                progress = initializer(tokens, progress, &mut init.children[count], env);
+                progress = initializer_separator(tokens, progress);
+                count += 1;
+            }
+            progress + 1
+        }
+        (_, Some(Kind::CurlyBracOpen)) => {
+            let progress = initializer(tokens, progress + 1, init, env);
+            let progress = initializer_separator(tokens, progress);
+            assert!(
+                matches!(tokens.get(progress), Some(Kind::CurlyBracClose)),
+                "スカラーの初期化子は1つのみです。プログラムを終了します。"
+            );
+            progress + 1
+        }
+        _ => {
+            let (expr, progress) = assign(tokens, progress, env);
+            init.expr = Some(expr);
+            progress
+        }
+    }
+}
+
+// {}の中の初期化子の区切りの,を読み飛ばす
+fn initializer_separator(tokens: &[Kind], progress: usize) -> usize {
+    match tokens.get(progress) {
+        Some(Kind::Comma) => progress + 1,
+        Some(Kind::CurlyBracClose) => progress,
+        _ => panic!("初期化子の記述が不正です。プログラムを終了します。"),
+    }
+}
+
+//初期化子を要素ごとの代入式の文にして、{}の中の文の列に追加する
+//lvalueは初期化する要素のノードを作る(配列は先頭の要素へのポインタになる)
+fn init_stmts(init: Initializer, lvalue: &dyn Fn() -> Node, node: Node) -> Node {
+    if let Some(expr) = init.expr {
+        return push_stmt(node, new_assign(lvalue(), expr));
+    }
+    match &init.ty {
+        Type::Array(..) => {
+            init.children
+                .into_iter()
+                .enumerate()
+                .fold(node, |node, (index, child)| {
+                    let index = i64::try_from(index).unwrap();
+                    let elem = || new_deref(new_add(lvalue(), new_num(index)));
+                    init_stmts(child, &elem, node)
+                })
+        }
+        // 共用体は最初のメンバのみ初期化する
+        Type::Struct(st) => {
+            let count = if st.is_union { 1 } else { init.children.len() };
+            init.children
+                .into_iter()
+                .zip(st.members())
+                .take(count)
+                .fold(node, |node, (child, member)| {
+                    let elem = || new_member(lvalue(), &member.name);
+                    init_stmts(child, &elem, node)
+                })
+        }
+        // 省略された要素は0で初期化する
+        _ => push_stmt(node, new_assign(lvalue(), new_num(0))),
+    }
+}
+
+//{}の中の文の列の末尾に文を追加する
+fn push_stmt(node: Node, stmt: Node) -> Node {
+    Node {
+        kind: Kind::CurlyBracOpen,
+        lhs: Some(Box::new(node)),
+        rhs: Some(Box::new(stmt)),
+        ty: None,
+    }
 }
 
 // statement
 // stmt = expr ";"
+// | declaration
+// | typedef
 // | "{" stmt* "}"
 // | "return" expr ";"
 // | "if" "(" expr ")" stmt ("else" stmt)?
 // | "while" "(" expr ")" stmt
-// | "for" "(" expr? ";" expr? ";" expr? ")" stmt
+// | "for" "(" (expr? ";" | declaration) expr? ";" expr? ")" stmt
+// | "switch" "(" expr ")" switch-body
+// | "break" ";"
 #[allow(clippy::too_many_lines)]
-fn stmt(tokens: &Vec<Kind>, mut progress: usize) -> (Node, usize) {
+fn stmt(tokens: &Vec<Kind>, mut progress: usize, env: &mut Env) -> (Node, usize) {
     let mut node;
     match tokens.get(progress) {
         // "return" expr ";"
+        // 戻り値は関数の戻り値の型に変換する
         Some(Kind::Return) => {
-            (node, progress) = expr(tokens, progress + 1);
+            (node, progress) = expr(tokens, progress + 1, env);
             node = Node {
                 kind: Kind::Return,
-                lhs: Some(Box::new(node)),
+                lhs: Some(Box::new(new_conv(node, &env.ret.clone()))),
                 rhs: None,
+                ty: None,
             };
 
-            if tokens.len() <= progress {
-                panic!("文の終わりに;が付いていません。プログラムを終了します。");
-            }
+            assert!(
+                progress < tokens.len(),
+                "文の終わりに;が付いていません。プログラムを終了します。"
+            );
             if let Kind::Semicolon = tokens[progress] {
                 return (node, progress + 1);
             }
             panic!("文の終わりに;が付いていません。プログラムを終了します。");
         }
+        // "break" ";"
+        // 最も内側のループかswitch文を抜ける
+        Some(Kind::Keyword(Keyword::Break)) => {
+            assert!(
+                env.breakable,
+                "breakはループかswitch文の中でしか使えません。プログラムを終了します。"
+            );
+            assert!(
+                matches!(tokens.get(progress + 1), Some(Kind::Semicolon)),
+                "文の終わりに;が付いていません。プログラムを終了します。"
+            );
+            (
+                Node {
+                    kind: Kind::Break,
+                    lhs: None,
+                    rhs: None,
+                    ty: None,
+                },
+                progress + 2,
+            )
+        }
+        // typedef
+        Some(Kind::Keyword(Keyword::Typedef)) => {
+            let progress = typedef(tokens, progress + 1, env);
+            // 何もしない文とする
+            (
+                Node {
+                    kind: Kind::CurlyBracOpen,
+                    lhs: None,
+                    rhs: None,
+                    ty: None,
+                },
+                progress,
+            )
+        }
+        // declaration
+        // 記憶域クラス指定子か型名で始まる文は宣言、それ以外は式文とする
+        _ if is_declaration(tokens, progress, env) => declaration(tokens, progress, env),
+        // "switch" "(" expr ")" switch-body
+        Some(Kind::Keyword(Keyword::Switch)) => switch_stmt(tokens, progress + 1, env),
+        Some(Kind::Keyword(Keyword::Case | Keyword::Default)) => {
+            panic!("caseとdefaultはswitch文の直下にしか置けません。プログラムを終了します。")
+        }
         // "{" stmt* "}"
+        // ブロックの中で宣言した変数はブロックの中でのみ有効
         Some(Kind::CurlyBracOpen) => {
             let mut node = Node {
                 // ここは他のノードのkindとかぶらなければ何でも良い
@@ -54,19 +1242,27 @@ fn stmt(tokens: &Vec<Kind>, mut progress: usize) -> (Node, usize) {
                 lhs: None,
                 // 前の文
                 rhs: None,
+                ty: None,
             };
             progress += 1;
+            env.enter_scope();
             // 波括弧閉じが出てくるまで繰り返す
             loop {
                 let node_stmt;
-                if let Kind::CurlyBracClose = tokens[progress] {
-                    return (node, progress + 1);
+                match tokens.get(progress) {
+                    Some(Kind::CurlyBracClose) => {
+                        env.leave_scope();
+                        return (node, progress + 1);
+                    }
+                    None => panic!("波括弧が閉じていません。プログラムを終了します。"),
+                    _ => (),
                 }
-                (node_stmt, progress) = stmt(tokens, progress);
+                (node_stmt, progress) = stmt(tokens, progress, env);
                 node = Node {
                     kind: Kind::CurlyBracOpen,
                     lhs: Some(Box::new(node)),
                     rhs: Some(Box::new(node_stmt)),
+                    ty: None,
                 };
             }
         }
@@ -81,23 +1277,24 @@ fn stmt(tokens: &Vec<Kind>, mut progress: usize) -> (Node, usize) {
             let node_else;
             if let Kind::RoundBracOpen = tokens[progress + 1] {
                 // 条件式
-                (node_cond, progress) = expr(tokens, progress + 2);
+                (node_cond, progress) = expr(tokens, progress + 2, env);
             } else {
                 panic!("if文の条件式は括弧で囲ってください。プログラムを終了します。");
             }
             if let Kind::RoundBracClose = tokens[progress] {
                 // 条件式が真のときに実行する部分
-                (node_then, progress) = stmt(tokens, progress + 1);
+                (node_then, progress) = stmt(tokens, progress + 1, env);
             } else {
                 panic!("if文の条件式は括弧で囲ってください。プログラムを終了します。");
             }
             if let Kind::Else = tokens[progress] {
                 // 条件式がの偽のときに実行する部分
-                (node_else, progress) = stmt(tokens, progress + 1);
+                (node_else, progress) = stmt(tokens, progress + 1, env);
                 node = Node {
                     kind: Kind::If(Some(Box::new(node_cond))),
                     lhs: Some(Box::new(node_then)),
                     rhs: Some(Box::new(node_else)),
+                    ty: None,
                 };
             } else {
                 // elseが無い場合
@@ -105,6 +1302,7 @@ fn stmt(tokens: &Vec<Kind>, mut progress: usize) -> (Node, usize) {
                     kind: Kind::If(Some(Box::new(node_cond))),
                     lhs: Some(Box::new(node_then)),
                     rhs: None,
+                    ty: None,
                 };
             }
             // 条件式が真ならlhsを、偽ならrhsを実行すべし
@@ -118,13 +1316,13 @@ fn stmt(tokens: &Vec<Kind>, mut progress: usize) -> (Node, usize) {
             let node_then;
             if let Kind::RoundBracOpen = tokens[progress + 1] {
                 // 条件式
-                (node_cond, progress) = expr(tokens, progress + 2);
+                (node_cond, progress) = expr(tokens, progress + 2, env);
             } else {
                 panic!("while文の条件式は括弧で囲ってください。プログラムを終了します。");
             }
             if let Kind::RoundBracClose = tokens[progress] {
                 // 条件式が真のときに実行する部分
-                (node_then, progress) = stmt(tokens, progress + 1);
+                (node_then, progress) = breakable_stmt(tokens, progress + 1, env);
             } else {
                 panic!("while文の条件式は括弧で囲ってください。プログラムを終了します。");
             }
@@ -132,12 +1330,14 @@ fn stmt(tokens: &Vec<Kind>, mut progress: usize) -> (Node, usize) {
                 kind: Kind::While(Some(Box::new(node_cond))),
                 lhs: Some(Box::new(node_then)),
                 rhs: None,
+                ty: None,
             };
 
             // 条件式が真ならlhsの処理をループ
             (node, progress)
         }
-        // "for" "(" expr? ";" expr? ";" expr? ")" stmt
+        // "for" "(" (expr? ";" | declaration) expr? ";" expr? ")" stmt
+        // 初期化式で宣言した変数はfor文の中でのみ有効
         Some(Kind::For(..)) => {
             // 初期化式
             let node_init;
@@ -148,14 +1348,19 @@ fn stmt(tokens: &Vec<Kind>, mut progress: usize) -> (Node, usize) {
             // then式
             let node_then;
 
+            env.enter_scope();
             if let Kind::RoundBracOpen = tokens[progress + 1] {
-                if let Kind::Semicolon = tokens[progress + 2] {
+                if is_typename(tokens, progress + 2, env) {
+                    // 宣言した変数の初期化
+                    (node, progress) = declaration(tokens, progress + 2, env);
+                    node_init = Some(Box::new(node));
+                } else if let Kind::Semicolon = tokens[progress + 2] {
                     // 初期化式無し
                     node_init = None;
                     progress += 3;
                 } else {
                     // 初期化式
-                    (node, progress) = expr(tokens, progress + 2);
+                    (node, progress) = expr(tokens, progress + 2, env);
                     node_init = Some(Box::new(node));
                     // 初期化式と条件式の間のセミコロン
                     if let Kind::Semicolon = tokens[progress] {
@@ -173,7 +1378,7 @@ fn stmt(tokens: &Vec<Kind>, mut progress: usize) -> (Node, usize) {
                 progress += 1;
             } else {
                 // 条件式
-                (node, progress) = expr(tokens, progress);
+                (node, progress) = expr(tokens, progress, env);
                 node_cond = Some(Box::new(node));
                 // 条件式と変化式の間のセミコロン
                 if let Kind::Semicolon = tokens[progress] {
@@ -188,7 +1393,7 @@ fn stmt(tokens: &Vec<Kind>, mut progress: usize) -> (Node, usize) {
                 progress += 1;
             } else {
                 // 変化式
-                (node, progress) = expr(tokens, progress);
+                (node, progress) = expr(tokens, progress, env);
                 node_inc = Some(Box::new(node));
                 if let Kind::RoundBracClose = tokens[progress] {
                     progress += 1;
@@ -198,21 +1403,24 @@ fn stmt(tokens: &Vec<Kind>, mut progress: usize) -> (Node, usize) {
             }
 
             // ループ本体
-            (node_then, progress) = stmt(tokens, progress);
+            (node_then, progress) = breakable_stmt(tokens, progress, env);
+            env.leave_scope();
             node = Node {
                 kind: Kind::For(node_init, node_cond, node_inc),
                 lhs: Some(Box::new(node_then)),
                 rhs: None,
+                ty: None,
             };
             (node, progress)
         }
         // expr ";"
         _ => {
-            (node, progress) = expr(tokens, progress);
+            (node, progress) = expr(tokens, progress, env);
 
-            if tokens.len() <= progress {
-                panic!("文の終わりに;が付いていません。プログラムを終了します。");
-            }
+            assert!(
+                progress < tokens.len(),
+                "文の終わりに;が付いていません。プログラムを終了します。"
+            );
             if let Kind::Semicolon = tokens[progress] {
                 return (node, progress + 1);
             }
@@ -221,57 +1429,153 @@ fn stmt(tokens: &Vec<Kind>, mut progress: usize) -> (Node, usize) {
     }
 }
 
+// ループかswitch文の本体の文
+// 本体の中のbreakは、この文を抜ける
+fn breakable_stmt(tokens: &Vec<Kind>, progress: usize, env: &mut Env) -> (Node, usize) {
+    let breakable = mem::replace(&mut env.breakable, true);
+    let (node, progress) = stmt(tokens, progress, env);
+    env.breakable = breakable;
+    (node, progress)
+}
+
+// switch文
+// switch-body = "{" (("case" const-int | "default") ":" | stmt)* "}"
+// caseとdefaultのラベルはswitch文の本体の直下にのみ置ける
+fn switch_stmt(tokens: &Vec<Kind>, mut progress: usize, env: &mut Env) -> (Node, usize) {
+    let node_cond;
+    if let Some(Kind::RoundBracOpen) = tokens.get(progress) {
+        (node_cond, progress) = expr(tokens, progress + 1, env);
+    } else {
+        panic!("switch文の条件式は括弧で囲ってください。プログラムを終了します。");
+    }
+    assert!(
+        node_cond.ty.as_ref().unwrap().is_integer(),
+        "switch文の条件式は整数でなければなりません。プログラムを終了します。"
+    );
+    if let (Some(Kind::RoundBracClose), Some(Kind::CurlyBracOpen)) =
+        (tokens.get(progress), tokens.get(progress + 1))
+    {
+        progress += 2;
+    } else {
+        panic!("switch文の本体は波括弧で囲ってください。プログラムを終了します。");
+    }
+    // 条件式の値は各caseの値と比べるために一時的な変数に置く
+    let tmp = env.new_local("", node_cond.ty.clone().unwrap());
+
+    env.enter_scope();
+    let mut cases: Vec<Case> = Vec::new();
+    loop {
+        let label = match tokens.get(progress) {
+            Some(Kind::CurlyBracClose) => break,
+            None => panic!("波括弧が閉じていません。プログラムを終了します。"),
+            Some(Kind::Keyword(Keyword::Case)) => {
+                let value;
+                (value, progress) = const_int(tokens, progress + 1, env);
+                assert!(
+                    cases.iter().all(|case| !case.labels.contains(&Some(value))),
+                    "caseの値{}が重複しています。プログラムを終了します。",
+                    value
+                );
+                Some(Some(value))
+            }
+            Some(Kind::Keyword(Keyword::Default)) => {
+                progress += 1;
+                assert!(
+                    cases.iter().all(|case| !case.labels.contains(&None)),
+                    "defaultが重複しています。プログラムを終了します。"
+                );
+                Some(None)
+            }
+            _ => None,
+        };
+        if let Some(label) = label {
+            if let Some(Kind::Colon) = tokens.get(progress) {
+                progress += 1;
+            } else {
+                panic!("ラベルの後に:が付いていません。プログラムを終了します。");
+            }
+            // 文を挟まずに続くラベルは同じ位置を指す
+            match cases.last_mut() {
+                Some(case) if case.body.is_empty() => case.labels.push(label),
+                _ => cases.push(Case {
+                    labels: vec![label],
+                    body: Vec::new(),
+                }),
+            }
+            continue;
+        }
+        let node_stmt;
+        (node_stmt, progress) = breakable_stmt(tokens, progress, env);
+        match cases.last_mut() {
+            Some(case) => case.body.push(node_stmt),
+            // 最初のラベルより前の文は実行されない
+            None => cases.push(Case {
+                labels: Vec::new(),
+                body: vec![node_stmt],
+            }),
+        }
+    }
+    env.leave_scope();
+    (
+        Node {
+            kind: Kind::Switch(Some(Box::new(node_cond)), tmp, cases),
+            lhs: None,
+            rhs: None,
+            ty: None,
+        },
+        progress + 1,
+    )
+}
+
 // expr = assign
-fn expr(tokens: &Vec<Kind>, progress: usize) -> (Node, usize) {
-    assign(tokens, progress)
+// 式全体に型を付ける
+fn expr(tokens: &Vec<Kind>, progress: usize, env: &mut Env) -> (Node, usize) {
+    let (mut node, progress) = assign(tokens, progress, env);
+    add_type(&mut node);
+    (node, progress)
 }
 
 // assign = equality ("=" assign)?
-fn assign(tokens: &Vec<Kind>, progress: usize) -> (Node, usize) {
-    let (node, mut progress) = equality(tokens, progress);
+fn assign(tokens: &Vec<Kind>, progress: usize, env: &mut Env) -> (Node, usize) {
+    let (node, mut progress) = equality(tokens, progress, env);
     //代入演算子が無い場合
     if tokens.len() <= progress {
         return (node, progress);
     }
     if let Kind::Assign = tokens[progress] {
         let rhs_node;
-        (rhs_node, progress) = assign(tokens, progress + 1);
-        (
-            Node {
-                kind: Kind::Assign,
-                lhs: Some(Box::new(node)),
-                rhs: Some(Box::new(rhs_node)),
-            },
-            progress,
-        )
+        (rhs_node, progress) = assign(tokens, progress + 1, env);
+        (new_assign(node, rhs_node), progress)
     } else {
         (node, progress)
     }
 }
 
 // equality = relational ("==" relational | "!=" relational)*
-fn equality(tokens: &Vec<Kind>, progress: usize) -> (Node, usize) {
+fn equality(tokens: &Vec<Kind>, progress: usize, env: &mut Env) -> (Node, usize) {
     //relational
-    let (mut node, mut progress) = relational(tokens, progress);
+    let (mut node, mut progress) = relational(tokens, progress, env);
     //("==" relational | "!=" relational)*
     while progress < tokens.len() {
         match tokens.get(progress) {
             Some(Kind::Equal) => {
                 let rhs_node;
-                (rhs_node, progress) = relational(tokens, progress + 1);
+                (rhs_node, progress) = relational(tokens, progress + 1, env);
                 node = Node {
                     kind: Kind::Equal,
                     lhs: Some(Box::new(node)),
                     rhs: Some(Box::new(rhs_node)),
+                    ty: None,
                 }
             }
             Some(Kind::NoEqual) => {
                 let rhs_node;
-                (rhs_node, progress) = relational(tokens, progress + 1);
+                (rhs_node, progress) = relational(tokens, progress + 1, env);
                 node = Node {
                     kind: Kind::NoEqual,
                     lhs: Some(Box::new(node)),
                     rhs: Some(Box::new(rhs_node)),
+                    ty: None,
                 }
             }
             _ => return (node, progress),
@@ -281,48 +1585,52 @@ fn equality(tokens: &Vec<Kind>, progress: usize) -> (Node, usize) {
 }
 
 // relational = add ("<" add | "<=" add | ">" add | ">=" add)*
-fn relational(tokens: &Vec<Kind>, progress: usize) -> (Node, usize) {
+fn relational(tokens: &Vec<Kind>, progress: usize, env: &mut Env) -> (Node, usize) {
     //add
-    let (mut node, mut progress) = add(tokens, progress);
+    let (mut node, mut progress) = add(tokens, progress, env);
     //("==" relational | "!=" relational)*
     while progress < tokens.len() {
         match tokens.get(progress) {
             Some(Kind::LowThan) => {
                 let rhs_node;
-                (rhs_node, progress) = add(tokens, progress + 1);
+                (rhs_node, progress) = add(tokens, progress + 1, env);
                 node = Node {
                     kind: Kind::LowThan,
                     lhs: Some(Box::new(node)),
                     rhs: Some(Box::new(rhs_node)),
+                    ty: None,
                 };
             }
             Some(Kind::LowEqual) => {
                 let rhs_node;
-                (rhs_node, progress) = add(tokens, progress + 1);
+                (rhs_node, progress) = add(tokens, progress + 1, env);
                 node = Node {
                     kind: Kind::LowEqual,
                     lhs: Some(Box::new(node)),
                     rhs: Some(Box::new(rhs_node)),
+                    ty: None,
                 };
             }
             Some(Kind::HighThan) => {
                 let rhs_node;
-                (rhs_node, progress) = add(tokens, progress + 1);
+                (rhs_node, progress) = add(tokens, progress + 1, env);
                 node = Node {
                     //ノードの左右を入れ替えて小なりに統一する
                     kind: Kind::LowThan,
                     lhs: Some(Box::new(rhs_node)),
                     rhs: Some(Box::new(node)),
+                    ty: None,
                 };
             }
             Some(Kind::HighEqual) => {
                 let rhs_node;
-                (rhs_node, progress) = add(tokens, progress + 1);
+                (rhs_node, progress) = add(tokens, progress + 1, env);
                 node = Node {
                     //ノードの左右を入れ替えて小なりに統一する
                     kind: Kind::LowEqual,
                     lhs: Some(Box::new(rhs_node)),
                     rhs: Some(Box::new(node)),
+                    ty: None,
                 };
             }
             _ => return (node, progress),
@@ -332,29 +1640,21 @@ fn relational(tokens: &Vec<Kind>, progress: usize) -> (Node, usize) {
 }
 
 // add = mul ("+" mul | "-" mul)*
-fn add(tokens: &Vec<Kind>, progress: usize) -> (Node, usize) {
+fn add(tokens: &Vec<Kind>, progress: usize, env: &mut Env) -> (Node, usize) {
     //mul
-    let (mut node, mut progress) = mul(tokens, progress);
+    let (mut node, mut progress) = mul(tokens, progress, env);
     //("+" mul | "-" mul)*
     while progress < tokens.len() {
         match tokens.get(progress) {
             Some(Kind::Add) => {
                 let rhs_node;
-                (rhs_node, progress) = mul(tokens, progress + 1);
-                node = Node {
-                    kind: Kind::Add,
-                    lhs: Some(Box::new(node)),
-                    rhs: Some(Box::new(rhs_node)),
-                };
+                (rhs_node, progress) = mul(tokens, progress + 1, env);
+                node = new_add(node, rhs_node);
             }
             Some(Kind::Sub) => {
                 let rhs_node;
-                (rhs_node, progress) = mul(tokens, progress + 1);
-                node = Node {
-                    kind: Kind::Sub,
-                    lhs: Some(Box::new(node)),
-                    rhs: Some(Box::new(rhs_node)),
-                };
+                (rhs_node, progress) = mul(tokens, progress + 1, env);
+                node = new_sub(node, rhs_node);
             }
             _ => return (node, progress),
         }
@@ -362,29 +1662,31 @@ fn add(tokens: &Vec<Kind>, progress: usize) -> (Node, usize) {
     (node, progress)
 }
 
-//mul  = unary ("*" unary | "/" unary)*
-fn mul(tokens: &Vec<Kind>, progress: usize) -> (Node, usize) {
+//mul  = cast ("*" cast | "/" cast)*
+fn mul(tokens: &Vec<Kind>, progress: usize, env: &mut Env) -> (Node, usize) {
     //num
-    let (mut node, mut progress) = unary(tokens, progress);
+    let (mut node, mut progress) = cast(tokens, progress, env);
     //("*" num | "/" num)*
     while progress < tokens.len() {
         match tokens.get(progress) {
             Some(Kind::Mul) => {
                 let rhs_node;
-                (rhs_node, progress) = unary(tokens, progress + 1);
+                (rhs_node, progress) = cast(tokens, progress + 1, env);
                 node = Node {
                     kind: Kind::Mul,
                     lhs: Some(Box::new(node)),
                     rhs: Some(Box::new(rhs_node)),
+                    ty: None,
                 };
             }
             Some(Kind::Div) => {
                 let rhs_node;
-                (rhs_node, progress) = unary(tokens, progress + 1);
+                (rhs_node, progress) = cast(tokens, progress + 1, env);
                 node = Node {
                     kind: Kind::Div,
                     lhs: Some(Box::new(node)),
                     rhs: Some(Box::new(rhs_node)),
+                    ty: None,
                 };
             }
             _ => return (node, progress),
@@ -393,28 +1695,154 @@ fn mul(tokens: &Vec<Kind>, progress: usize) -> (Node, usize) {
     (node, progress)
 }
 
-//unary   = ("+" | "-")? primary
-fn unary(tokens: &Vec<Kind>, progress: usize) -> (Node, usize) {
+// cast = "(" type-name ")" cast | unary
+// (の後が型名であればキャスト、そうでなければ括弧で囲んだ式とする
+fn cast(tokens: &Vec<Kind>, progress: usize, env: &mut Env) -> (Node, usize) {
+    if !matches!(tokens.get(progress), Some(Kind::RoundBracOpen))
+        || !is_typename(tokens, progress + 1, env)
+    {
+        return unary(tokens, progress, env);
+    }
+    let (ty, progress) = type_name(tokens, progress + 1, env);
+    assert!(
+        matches!(tokens.get(progress), Some(Kind::RoundBracClose)),
+        "キャストの型名の後に)がありません。プログラムを終了します。"
+    );
+    assert!(
+        !matches!(ty, Type::Array(..)),
+        "配列の型にはキャストできません。プログラムを終了します。"
+    );
+    let (expr, progress) = cast(tokens, progress + 1, env);
+    (new_conv(expr, &ty), progress)
+}
+
+// unary = ("+" | "-" | "&" | "*") cast
+// | "sizeof" ("(" type-name ")" | unary)
+// | postfix
+fn unary(tokens: &Vec<Kind>, progress: usize, env: &mut Env) -> (Node, usize) {
     match tokens.get(progress) {
-        Some(Kind::Add) => primary(tokens, progress + 1),
+        Some(Kind::Add) => cast(tokens, progress + 1, env),
         Some(Kind::Sub) => {
-            let (rhs_node, progress) = primary(tokens, progress + 1);
+            let (rhs_node, progress) = cast(tokens, progress + 1, env);
             // 対応する0のノードを生成
             let zero_node = Node {
-                kind: Kind::Num(vec!['0']),
+                kind: Kind::Num(0, Type::Int),
                 lhs: None,
                 rhs: None,
+                ty: None,
             };
             (
                 Node {
                     kind: Kind::Sub,
                     lhs: Some(Box::new(zero_node)),
                     rhs: Some(Box::new(rhs_node)),
+                    ty: None,
+                },
+                progress,
+            )
+        }
+        Some(Kind::Addr) => {
+            // 配列は先頭の要素へのポインタに変換済みなので、変換前の配列のアドレスをとる
+            let (lhs_node, progress) = cast(tokens, progress + 1, env);
+            let lhs_node = undecay(lhs_node);
+            assert!(
+                is_lvalue(&lhs_node),
+                "アドレスを取れない式に&が付いています。プログラムを終了します。"
+            );
+            (
+                Node {
+                    kind: Kind::Addr,
+                    lhs: Some(Box::new(lhs_node)),
+                    rhs: None,
+                    ty: None,
+                },
+                progress,
+            )
+        }
+        Some(Kind::Mul) => {
+            let (lhs_node, progress) = cast(tokens, progress + 1, env);
+            (new_deref(lhs_node), progress)
+        }
+        Some(Kind::Keyword(Keyword::Sizeof)) => {
+            let (ty, progress) = sizeof_type(tokens, progress + 1, env);
+            (
+                Node {
+                    kind: Kind::Num(i64::try_from(ty.size()).unwrap(), Type::ULong),
+                    lhs: None,
+                    rhs: None,
+                    ty: None,
                 },
                 progress,
             )
         }
-        _ => primary(tokens, progress),
+        _ => postfix(tokens, progress, env),
+    }
+}
+
+// sizeofの対象の型を返す
+// "(" type-name ")" | unary
+// 式の場合は式の型とし、式の値は求めない
+fn sizeof_type(tokens: &Vec<Kind>, progress: usize, env: &mut Env) -> (Type, usize) {
+    if matches!(tokens.get(progress), Some(Kind::RoundBracOpen))
+        && is_typename(tokens, progress + 1, env)
+    {
+        let (ty, progress) = type_name(tokens, progress + 1, env);
+        assert!(
+            matches!(tokens.get(progress), Some(Kind::RoundBracClose)),
+            "sizeofの型名の後に)がありません。プログラムを終了します。"
+        );
+        return (ty, progress + 1);
+    }
+    // 配列は先頭の要素へのポインタに変換済みなので、変換前の配列の型にする
+    let (node, progress) = unary(tokens, progress, env);
+    let mut node = undecay(node);
+    add_type(&mut node);
+    (node.ty.unwrap(), progress)
+}
+
+//配列から先頭の要素へのポインタに変換した式を、変換前の配列の式に戻す
+//&で配列のアドレスをとった式はポインタの指す先が配列なので戻さない
+fn undecay(node: Node) -> Node {
+    let lhs_ty = node.lhs.as_ref().and_then(|lhs| lhs.ty.as_ref());
+    let is_decayed = match (&node.kind, &node.ty, lhs_ty) {
+        (Kind::Addr, Some(Type::Ptr(base)), Some(Type::Array(elem, _))) => base.is_same(elem),
+        _ => false,
+    };
+    if is_decayed {
+        *node.lhs.unwrap()
+    } else {
+        node
+    }
+}
+
+// postfix = primary ("[" expr "]" | "." ident | "->" ident)*
+// a[i]は*(a+i)、p->xは(*p).xとする
+fn postfix(tokens: &Vec<Kind>, progress: usize, env: &mut Env) -> (Node, usize) {
+    let (mut node, mut progress) = primary(tokens, progress, env);
+    loop {
+        match tokens.get(progress) {
+            Some(Kind::SquareBracOpen) => {
+                let index;
+                (index, progress) = expr(tokens, progress + 1, env);
+                assert!(
+                    matches!(tokens.get(progress), Some(Kind::SquareBracClose)),
+                    "添字の後に]がありません。プログラムを終了します。"
+                );
+                progress += 1;
+                node = new_deref(new_add(node, index));
+            }
+            Some(Kind::Dot) => {
+                let name;
+                (name, progress) = expect_ident(tokens, progress + 1);
+                node = new_member(node, &name);
+            }
+            Some(Kind::Arrow) => {
+                let name;
+                (name, progress) = expect_ident(tokens, progress + 1);
+                node = new_member(new_deref(node), &name);
+            }
+            _ => return (node, progress),
+        }
     }
 }
 
@@ -422,50 +1850,60 @@ fn unary(tokens: &Vec<Kind>, progress: usize) -> (Node, usize) {
 // | ident
 // | ident func-args?
 // | num
-fn primary(tokens: &Vec<Kind>, mut progress: usize) -> (Node, usize) {
+// | str
+fn primary(tokens: &Vec<Kind>, mut progress: usize, env: &mut Env) -> (Node, usize) {
     match tokens.get(progress) {
         Some(Kind::RoundBracOpen) => {
             //"(" expr ")"
-            let (node, progress) = expr(tokens, progress + 1);
+            let (node, progress) = expr(tokens, progress + 1, env);
             if let Kind::RoundBracClose = tokens[progress] {
                 (node, progress + 1)
             } else {
                 panic!("括弧が閉じていません。プログラムを終了します。");
             }
         }
+        // 可変長引数を扱う組み込み関数
+        Some(Kind::Ident(name))
+            if matches!(name.as_str(), "__va_start" | "va_arg" | "va_end")
+                && matches!(tokens.get(progress + 1), Some(Kind::RoundBracOpen)) =>
+        {
+            va_builtin(tokens, progress + 2, name, env)
+        }
+        // ident func-args
+        Some(Kind::Ident(func_name))
+            if matches!(tokens.get(progress + 1), Some(Kind::RoundBracOpen)) =>
+        {
+            let args;
+            (args, progress) = func_args(tokens, progress + 2, env);
+            (new_funcall(func_name, args, env), progress)
+        }
         // ident
-        Some(Kind::Var(index)) => (
-            Node {
-                kind: Kind::Var(*index),
+        // 列挙定数は数値のノードにする
+        Some(Kind::Ident(name)) => (
+            decay(Node {
+                kind: match env.find_var(name) {
+                    VarScope::Var(obj) => Kind::Var(obj),
+                    VarScope::EnumConst(value) => Kind::Num(value, Type::Int),
+                    VarScope::Typedef(_) => unreachable!(),
+                    VarScope::Func(_) => panic!(
+                        "関数{}を値として使うことはできません。プログラムを終了します。",
+                        name
+                    ),
+                },
                 lhs: None,
                 rhs: None,
-            },
+                ty: None,
+            }),
             progress + 1,
         ),
-        // ident "(" func-args? ")"
-        Some(Kind::FunCall(ref func_name, _)) => {
-            progress += 1;
-            if let Kind::RoundBracOpen = tokens[progress] {
-                if let Kind::RoundBracClose = tokens[progress + 1] {
-                    // 引数なし
-                    (
-                        Node {
-                            kind: Kind::FunCall(func_name.clone(), None),
-                            lhs: None,
-                            rhs: None,
-                        },
-                        progress + 2,
-                    )
-                } else {
-                    // 引数あり
-                    func_args(tokens, progress + 1, func_name)
-                }
-            } else {
-                panic!("関数名の後に括弧がありません。プログラムを終了します。")
-            }
-        }
         //num
-        Some(Kind::Num(_)) => expect_num(tokens, progress),
+        Some(Kind::Num(..) | Kind::FNum(..)) => expect_num(tokens, progress),
+        //str
+        Some(Kind::Str(bytes)) => (decay(new_string_literal(bytes, env)), progress + 1),
+        Some(Kind::Keyword(keyword)) => panic!(
+            "キーワード{}にはまだ対応していません。プログラムを終了します。",
+            keyword_name(*keyword)
+        ),
         _ => panic!(
             "構文木の末端には変数か数値しか置けません。\nprogress = {}\nプログラムを終了します。",
             progress
@@ -473,56 +1911,436 @@ fn primary(tokens: &Vec<Kind>, mut progress: usize) -> (Node, usize) {
     }
 }
 
-// func-args =  (assign ("," assign)*)?
-fn func_args(tokens: &Vec<Kind>, mut progress: usize, func_name: &str) -> (Node, usize) {
+// func-args = "(" (assign ("," assign)*)? ")"
+// 開き括弧の次から解析する
+fn func_args(tokens: &Vec<Kind>, mut progress: usize, env: &mut Env) -> (Vec<Node>, usize) {
     let mut args = Vec::new();
-    // 引数の1つを評価
-    let node;
-    (node, progress) = assign(tokens, progress);
-    // 引数のリストに追加
-    args.push(node);
-
+    if let Some(Kind::RoundBracClose) = tokens.get(progress) {
+        // 引数なし
+        return (args, progress + 1);
+    }
     loop {
+        // 引数の1つを評価してリストに追加
+        let node;
+        (node, progress) = assign(tokens, progress, env);
+        args.push(node);
         match tokens.get(progress) {
-            Some(Kind::Comma) => {
-                progress += 1;
-            }
-            Some(Kind::RoundBracClose) => {
-                return (
-                    Node {
-                        kind: Kind::FunCall(
-                            func_name.to_string(),
-                            if args.is_empty() { None } else { Some(args) },
-                        ),
-                        lhs: None,
-                        rhs: None,
-                    },
-                    progress + 1,
-                );
-            }
+            Some(Kind::Comma) => progress += 1,
+            Some(Kind::RoundBracClose) => return (args, progress + 1),
             _ => panic!("関数の引数の記述が不正です。プログラムを終了します。"),
         }
-        let node;
-        (node, progress) = assign(tokens, progress);
-        // 引数のリストに追加
-        args.push(node);
+    }
+}
+
+//関数呼び出しのノードを作る
+//宣言された関数は引数の数を確かめ、各引数を仮引数の型に変換する
+//宣言されていない関数はintを返すものとし、すべての引数に既定の実引数拡張を行う
+//プロトタイプでない宣言の関数も、戻り値の型以外は宣言されていない関数と同じに扱う
+//可変長引数の関数では、仮引数より後の引数に既定の実引数拡張を行う
+fn new_funcall(func_name: &str, args: Vec<Node>, env: &Env) -> Node {
+    let (args, ty, named_num) = match env.lookup(func_name) {
+        Some(VarScope::Func(func)) if !func.is_prototyped => (
+            args.into_iter().map(promote_arg).collect(),
+            func.ret.clone(),
+            None,
+        ),
+        Some(VarScope::Func(func)) => {
+            assert!(
+                args.len() == func.params.len()
+                    || (func.is_variadic && args.len() > func.params.len()),
+                "関数{}の引数は{}{}個です。プログラムを終了します。",
+                func_name,
+                func.params.len(),
+                if func.is_variadic {
+                    "+可変長の"
+                } else {
+                    ""
+                }
+            );
+            let args: Vec<Node> = args
+                .into_iter()
+                .enumerate()
+                .map(|(index, arg)| match func.params.get(index) {
+                    Some(ty) => new_arg_conv(arg, ty, func_name, index),
+                    None => promote_arg(arg),
+                })
+                .collect();
+            let named_num = if func.is_variadic {
+                Some(func.params.len())
+            } else {
+                None
+            };
+            (args, func.ret.clone(), named_num)
+        }
+        Some(_) => panic!(
+            "{}は関数ではありません。プログラムを終了します。",
+            func_name
+        ),
+        None => (args.into_iter().map(promote_arg).collect(), Type::Int, None),
+    };
+    Node {
+        kind: Kind::FunCall(
+            func_name.to_string(),
+            if args.is_empty() { None } else { Some(args) },
+            named_num,
+        ),
+        lhs: None,
+        rhs: None,
+        ty: Some(ty),
     }
 }
 
 //現在のトークンが数値であれば対応したノードを生成して返す
 //トークンが数値以外または存在しない場合はpanicさせる
 fn expect_num(tokens: &[Kind], progress: usize) -> (Node, usize) {
-    if let Kind::Num(ref numbers) = tokens[progress] {
-        (
-            Node {
-                //所有権を移動させないためにcloneを使う
-                kind: Kind::Num(numbers.clone()),
-                lhs: None,
-                rhs: None,
-            },
-            progress + 1,
-        )
+    let kind = match &tokens[progress] {
+        Kind::Num(value, ty) => Kind::Num(*value, ty.clone()),
+        Kind::FNum(value, ty) => Kind::FNum(*value, ty.clone()),
+        _ => panic!("数字があるべき箇所に演算子があります。プログラムを終了します。"),
+    };
+    (
+        Node {
+            kind,
+            lhs: None,
+            rhs: None,
+            ty: None,
+        },
+        progress + 1,
+    )
+}
+
+//intの整数のノードを作る
+fn new_num(value: i64) -> Node {
+    Node {
+        kind: Kind::Num(value, Type::Int),
+        lhs: None,
+        rhs: None,
+        ty: None,
+    }
+}
+
+//現在のトークンが識別子であればその名前を返す
+fn expect_ident(tokens: &[Kind], progress: usize) -> (String, usize) {
+    if let Some(Kind::Ident(name)) = tokens.get(progress) {
+        (name.clone(), progress + 1)
+    } else {
+        panic!("識別子があるべき箇所にありません。プログラムを終了します。");
+    }
+}
+
+//値を書き込める式(変数、参照先、メンバ)であれば真を返す
+fn is_lvalue(node: &Node) -> bool {
+    matches!(node.kind, Kind::Var(_) | Kind::Deref | Kind::Member(_))
+}
+
+//代入のノードを作る
+//構造体は同じ型の構造体にのみ代入できる
+fn new_assign(mut lhs: Node, mut rhs: Node) -> Node {
+    assert!(
+        is_lvalue(&lhs),
+        "式の左辺に変数以外があります。プログラムを終了します。"
+    );
+    add_type(&mut lhs);
+    add_type(&mut rhs);
+    let (lhs_ty, rhs_ty) = (lhs.ty.as_ref().unwrap(), rhs.ty.as_ref().unwrap());
+    assert!(
+        !(lhs_ty.is_struct() || rhs_ty.is_struct()) || lhs_ty.is_same(rhs_ty),
+        "構造体には同じ型の構造体しか代入できません。プログラムを終了します。"
+    );
+    let mut node = Node {
+        kind: Kind::Assign,
+        lhs: Some(Box::new(lhs)),
+        rhs: Some(Box::new(rhs)),
+        ty: None,
+    };
+    add_type(&mut node);
+    node
+}
+
+//既定の実引数拡張を行う(floatはdoubleに変換する)
+//intより小さい整数は読み込んだ時点で8バイトに拡張済みなので変換しない
+fn promote_arg(mut arg: Node) -> Node {
+    add_type(&mut arg);
+    if let Some(Type::Float) = arg.ty {
+        new_cast(arg, Type::Double)
+    } else {
+        arg
+    }
+}
+
+// va-builtin = "__va_start" "(" assign ")"
+// | "va_arg" "(" assign "," declspec "*"* ")"
+// | "va_end" "(" assign ")"
+// 名前の次の開き括弧の次から解析する
+// va_startはマクロで__va_startに置き換える(最後の仮引数の名前は使わない)
+fn va_builtin(tokens: &Vec<Kind>, progress: usize, name: &str, env: &mut Env) -> (Node, usize) {
+    let (mut ap, mut progress) = assign(tokens, progress, env);
+    add_type(&mut ap);
+    assert!(
+        is_lvalue(&ap) && ap.ty.as_ref().unwrap().is_same(&env.va_list),
+        "{}の引数はva_listの変数でなければなりません。プログラムを終了します。",
+        name
+    );
+    // va_endは何もしない
+    if name == "va_end" {
+        assert!(
+            matches!(tokens.get(progress), Some(Kind::RoundBracClose)),
+            "va_endの引数の記述が不正です。プログラムを終了します。"
+        );
+        let node = Node {
+            kind: Kind::Num(0, Type::Int),
+            lhs: None,
+            rhs: None,
+            ty: None,
+        };
+        return (node, progress + 1);
+    }
+    // va_listを指す一時変数
+    let tmp = env.new_local("", Type::Ptr(Rc::new(env.va_list.clone())));
+    let (kind, ty) = if name == "__va_start" {
+        let (offset, gp, fp) = env.va_area.unwrap_or_else(|| {
+            panic!("va_startは可変長引数の関数の中でのみ使えます。プログラムを終了します。")
+        });
+        (Kind::VaStart(Rc::clone(&tmp), offset, gp, fp), Type::Int)
     } else {
-        panic!("数字があるべき箇所に演算子があります。プログラムを終了します。");
+        assert!(
+            matches!(tokens.get(progress), Some(Kind::Comma)),
+            "va_argの引数の記述が不正です。プログラムを終了します。"
+        );
+        let (base, ty);
+        (base, progress) = declspec(tokens, progress + 1, env);
+        (ty, progress) = pointers(tokens, progress, base);
+        // floatの引数はdoubleに拡張して渡されるため読み出せない
+        assert!(
+            ty.is_integer() || ty.base().is_some() || matches!(ty, Type::Double),
+            "va_argで読み出せるのは整数、ポインタ、doubleのみです。プログラムを終了します。"
+        );
+        (Kind::VaArg(Rc::clone(&tmp)), ty)
+    };
+    assert!(
+        matches!(tokens.get(progress), Some(Kind::RoundBracClose)),
+        "{}の引数の記述が不正です。プログラムを終了します。",
+        name
+    );
+    // 一時変数にva_listのアドレスを入れる
+    let var = Node {
+        kind: Kind::Var(tmp),
+        lhs: None,
+        rhs: None,
+        ty: None,
+    };
+    let addr = Node {
+        kind: Kind::Addr,
+        lhs: Some(Box::new(ap)),
+        rhs: None,
+        ty: None,
+    };
+    let node = Node {
+        kind,
+        lhs: Some(Box::new(new_assign(var, addr))),
+        rhs: None,
+        ty: Some(ty),
+    };
+    (node, progress + 1)
+}
+
+//キャスト、引数や戻り値をty型に変換するノードを作る
+//構造体や、浮動小数点数とポインタの間は変換できない
+fn new_conv(mut expr: Node, ty: &Type) -> Node {
+    add_type(&mut expr);
+    let from = expr.ty.as_ref().unwrap();
+    let invalid = from.is_struct()
+        || ty.is_struct()
+        || (from.is_flonum() && ty.base().is_some())
+        || (from.base().is_some() && ty.is_flonum());
+    assert!(
+        !invalid,
+        "値を異なる型に変換できません。プログラムを終了します。"
+    );
+    new_cast(expr, ty.clone())
+}
+
+//関数のindex番目の実引数を仮引数のty型に変換するノードを作る
+//キャストと違い、ポインタと整数の間や異なる型を指すポインタの間は変換できない
+//ただし整数の0はヌルポインタとしてポインタに変換できる
+fn new_arg_conv(mut expr: Node, ty: &Type, func_name: &str, index: usize) -> Node {
+    add_type(&mut expr);
+    let from = expr.ty.as_ref().unwrap();
+    let is_null = matches!(expr.kind, Kind::Num(0, _));
+    let compatible = match (from.base(), ty.base()) {
+        (Some(from), Some(to)) => from.is_same(to),
+        (None, Some(_)) => is_null,
+        (Some(_), None) => false,
+        (None, None) => true,
+    };
+    assert!(
+        compatible,
+        "関数{}の{}番目の引数の型が仮引数の型と異なります。プログラムを終了します。",
+        func_name,
+        index + 1
+    );
+    new_conv(expr, ty)
+}
+
+//参照先のノードを作る
+//参照先が配列であれば先頭の要素へのポインタにする
+fn new_deref(mut lhs: Node) -> Node {
+    add_type(&mut lhs);
+    assert!(
+        lhs.ty.as_ref().unwrap().base().is_some(),
+        "ポインタ以外の値を参照しています。プログラムを終了します。"
+    );
+    decay(Node {
+        kind: Kind::Deref,
+        lhs: Some(Box::new(lhs)),
+        rhs: None,
+        ty: None,
+    })
+}
+
+//構造体のメンバのノードを作る
+//メンバが配列であれば先頭の要素へのポインタにする
+fn new_member(mut lhs: Node, name: &str) -> Node {
+    add_type(&mut lhs);
+    let member = match lhs.ty.as_ref().unwrap() {
+        Type::Struct(st) => st.member(name),
+        _ => panic!(
+            "構造体以外の値のメンバ{}を参照しています。プログラムを終了します。",
+            name
+        ),
+    };
+    decay(Node {
+        kind: Kind::Member(member),
+        lhs: Some(Box::new(lhs)),
+        rhs: None,
+        ty: None,
+    })
+}
+
+//配列の式を先頭の要素へのポインタに変換する
+//配列のアドレスをそのまま値とし、型だけを要素へのポインタにする
+fn decay(mut node: Node) -> Node {
+    add_type(&mut node);
+    match node.ty.clone() {
+        Some(Type::Array(base, _)) => Node {
+            kind: Kind::Addr,
+            lhs: Some(Box::new(node)),
+            rhs: None,
+            ty: Some(Type::Ptr(base)),
+        },
+        _ => node,
+    }
+}
+
+//文字列リテラルを、終端の0を付けたcharの配列の無名のグローバル変数にする
+fn new_string_literal(bytes: &[u8], env: &mut Env) -> Node {
+    let mut init = bytes.to_vec();
+    init.push(0);
+    let obj = Rc::new(Obj {
+        name: format!(".L..{}", env.globals.len()),
+        ty: Type::Array(Rc::new(Type::Char), Some(init.len())),
+        offset: None,
+    });
+    env.globals.push(Global {
+        obj: Rc::clone(&obj),
+        init,
+        relocs: Vec::new(),
+        is_static: true,
+    });
+    Node {
+        kind: Kind::Var(obj),
+        lhs: None,
+        rhs: None,
+        ty: None,
+    }
+}
+
+//指す先の型の大きさを掛けたノードを作る
+fn scale(node: Node, size: usize) -> Node {
+    Node {
+        kind: Kind::Mul,
+        lhs: Some(Box::new(node)),
+        rhs: Some(Box::new(Node {
+            kind: Kind::Num(i64::try_from(size).unwrap(), Type::Long),
+            lhs: None,
+            rhs: None,
+            ty: None,
+        })),
+        ty: None,
+    }
+}
+
+//加算のノードを作る
+//ポインタに整数を足す場合は、整数に指す先の型の大きさを掛ける
+fn new_add(mut lhs: Node, mut rhs: Node) -> Node {
+    add_type(&mut lhs);
+    add_type(&mut rhs);
+    let lhs_ptr = lhs.ty.as_ref().unwrap().base().is_some();
+    let rhs_ptr = rhs.ty.as_ref().unwrap().base().is_some();
+    assert!(
+        !(lhs_ptr && rhs_ptr),
+        "ポインタ同士は足せません。プログラムを終了します。"
+    );
+    // 整数 + ポインタ は ポインタ + 整数 にする
+    if rhs_ptr {
+        mem::swap(&mut lhs, &mut rhs);
+    }
+    if let Some(base) = lhs.ty.as_ref().unwrap().base() {
+        rhs = scale(rhs, base.size());
+    }
+    Node {
+        kind: Kind::Add,
+        lhs: Some(Box::new(lhs)),
+        rhs: Some(Box::new(rhs)),
+        ty: None,
+    }
+}
+
+//減算のノードを作る
+//ポインタから整数を引く場合は、整数に指す先の型の大きさを掛ける
+//ポインタ同士の差は、間にある要素の数にする
+fn new_sub(mut lhs: Node, mut rhs: Node) -> Node {
+    add_type(&mut lhs);
+    add_type(&mut rhs);
+    let lhs_base = lhs.ty.as_ref().unwrap().base().cloned();
+    let rhs_ptr = rhs.ty.as_ref().unwrap().base().is_some();
+    match lhs_base {
+        None => {
+            assert!(
+                !rhs_ptr,
+                "整数からポインタは引けません。プログラムを終了します。"
+            );
+            Node {
+                kind: Kind::Sub,
+                lhs: Some(Box::new(lhs)),
+                rhs: Some(Box::new(rhs)),
+                ty: None,
+            }
+        }
+        Some(base) if rhs_ptr => {
+            let diff = Node {
+                kind: Kind::Sub,
+                lhs: Some(Box::new(lhs)),
+                rhs: Some(Box::new(rhs)),
+                ty: Some(Type::Long),
+            };
+            Node {
+                kind: Kind::Div,
+                lhs: Some(Box::new(diff)),
+                rhs: Some(Box::new(Node {
+                    kind: Kind::Num(i64::try_from(base.size()).unwrap(), Type::Long),
+                    lhs: None,
+                    rhs: None,
+                    ty: None,
+                })),
+                ty: None,
+            }
+        }
+        Some(base) => Node {
+            kind: Kind::Sub,
+            lhs: Some(Box::new(lhs)),
+            rhs: Some(Box::new(scale(rhs, base.size()))),
+            ty: None,
+        },
     }
 }
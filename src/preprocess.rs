@@ -0,0 +1,1102 @@
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::tokenize::{parse_char, parse_int_literal};
+
+//インクルードの入れ子の上限(無限にインクルードし続けるのを防ぐ)
+const INCLUDE_DEPTH_MAX: usize = 200;
+
+//あらかじめ定義しておくマクロ
+//va_startは最後の仮引数の名前を使わないので、組み込み関数の__va_startに置き換える
+const BUILTIN_MACROS: &str = "#define va_start(ap, last) __va_start(ap)\n";
+
+//前処理トークンの種類
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum PpKind {
+    Ident,  // 識別子
+    Number, // 数値
+    Str,    // 文字列リテラル
+    Char,   // 文字リテラル
+    Punct,  // 記号
+    Other,  // 上記以外の文字
+}
+
+//前処理トークン
+#[derive(Clone)]
+struct PpToken {
+    kind: PpKind,
+    text: String,
+    //行頭のトークンか
+    bol: bool,
+    //直前に空白があるか
+    space: bool,
+    //このトークンを生成する際に展開したマクロ名(hide set)
+    //ここに含まれるマクロは再び展開しない
+    hideset: Vec<String>,
+}
+
+//マクロ
+struct Macro {
+    //関数形式マクロの仮引数(オブジェクト形式マクロはNone)
+    params: Option<Vec<String>>,
+    //可変長引数(...)をとるか
+    variadic: bool,
+    //置換後のトークン列
+    body: Vec<PpToken>,
+}
+
+//条件付きコンパイルの区間の状態
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum CondState {
+    //現在の区間を出力する
+    Active,
+    //まだ条件が真の区間が無い
+    Waiting,
+    //既に条件が真の区間があった(以降の区間は出力しない)
+    Done,
+}
+
+//条件付きコンパイルの状態
+struct CondIncl {
+    state: CondState,
+    //#elseが出現したか
+    seen_else: bool,
+}
+
+//プリプロセッサ
+struct Preprocessor<'a> {
+    //#include <...> と -I で指定された検索パス
+    include_paths: &'a [String],
+    //マクロ名とその定義
+    macros: HashMap<String, Macro>,
+    //インクルードの入れ子の深さ
+    depth: usize,
+}
+
+//入力文字列を前処理し、トークナイザに渡す文字列を返す
+//pathは入力ファイルのパス(#include "..."の検索の起点になる。Noneならカレントディレクトリ)
+//definesは-Dで指定されたマクロ(NAMEまたはNAME=VALUE)
+pub fn preprocess(
+    input: &str,
+    path: Option<&Path>,
+    include_paths: &[String],
+    defines: &[String],
+) -> String {
+    let mut pp = Preprocessor {
+        include_paths,
+        macros: HashMap::new(),
+        depth: 0,
+    };
+    // stdarg.hの代わりに組み込みで定義するマクロ
+    pp.process(
+        lex(BUILTIN_MACROS, "組み込み"),
+        None,
+        "組み込み",
+        &mut Vec::new(),
+    );
+    for define in defines {
+        // 値が無ければ1と定義する
+        let (name, value) = define.split_once('=').unwrap_or((define, "1"));
+        pp.macros.insert(
+            name.to_string(),
+            Macro {
+                params: None,
+                variadic: false,
+                body: lex(value, "-D"),
+            },
+        );
+    }
+
+    let dir = path.and_then(Path::parent).map(Path::to_path_buf);
+    let name = path.map_or("入力".to_string(), |path| path.display().to_string());
+    let mut out = Vec::new();
+    pp.process(lex(input, &name), dir.as_deref(), &name, &mut out);
+
+    // トークン列を文字列に戻す
+    let mut output = String::new();
+    for (i, token) in out.iter().enumerate() {
+        // 先頭には改行も空白も入れない
+        if i > 0 && token.bol {
+            output.push('\n');
+        } else if i > 0 && token.space {
+            output.push(' ');
+        }
+        output.push_str(&token.text);
+    }
+    output.push('\n');
+    output
+}
+
+//入力文字列を前処理トークン列に変換する
+//nameはエラー表示用のファイル名
+fn lex(input: &str, name: &str) -> Vec<PpToken> {
+    let (chars, locs) = splice_lines(input);
+    // i文字目の位置を表す文字列
+    let loc = |i: usize| {
+        let (line, col) = locs[i];
+        format!("{name}:{line}:{col}")
+    };
+
+    let mut tokens = Vec::new();
+    let mut bol = true;
+    let mut space = false;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '\n' {
+            bol = true;
+            space = false;
+            i += 1;
+            continue;
+        }
+        // タブや\rなども空白として扱う
+        if c.is_whitespace() {
+            space = true;
+            i += 1;
+            continue;
+        }
+        // コメントは空白とみなす
+        if let Some(end) = skip_comment(&chars, i, loc) {
+            i = end;
+            space = true;
+            continue;
+        }
+
+        let start = i;
+        let kind = if c.is_ascii_alphabetic() || c == '_' {
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            PpKind::Ident
+        } else if c.is_ascii_digit()
+            || (c == '.' && chars.get(i + 1).is_some_and(char::is_ascii_digit))
+        {
+            // 数値は英数字と.、指数部の符号をまとめて一つのトークンとする
+            i += 1;
+            while i < chars.len() {
+                if matches!(chars[i], 'e' | 'E' | 'p' | 'P')
+                    && matches!(chars.get(i + 1), Some('+' | '-'))
+                {
+                    i += 2;
+                } else if chars[i].is_ascii_alphanumeric() || chars[i] == '.' || chars[i] == '_' {
+                    i += 1;
+                } else {
+                    break;
+                }
+            }
+            PpKind::Number
+        } else if c == '"' || c == '\'' {
+            // 閉じる引用符か行末まで
+            let start = i;
+            i += 1;
+            while i < chars.len() && chars[i] != c && chars[i] != '\n' {
+                if chars[i] == '\\' {
+                    i += 1;
+                }
+                i += 1;
+            }
+            assert!(
+                i < chars.len() && chars[i] == c,
+                "{}: {}が閉じられていません。プログラムを終了します。",
+                loc(start),
+                c
+            );
+            i += 1;
+            if c == '"' {
+                PpKind::Str
+            } else {
+                PpKind::Char
+            }
+        } else if let Some(punct) = PUNCTS
+            .iter()
+            .find(|punct| chars[i..].starts_with(&punct.chars().collect::<Vec<_>>()))
+        {
+            i += punct.len();
+            PpKind::Punct
+        } else {
+            i += 1;
+            PpKind::Other
+        };
+        tokens.push(PpToken {
+            kind,
+            text: chars[start..i].iter().collect(),
+            bol,
+            space,
+            hideset: Vec::new(),
+        });
+        bol = false;
+        space = false;
+    }
+    tokens
+}
+
+//i文字目からコメントであれば、コメントの次の文字の位置を返す
+fn skip_comment(chars: &[char], mut i: usize, loc: impl Fn(usize) -> String) -> Option<usize> {
+    // //から行末までは行コメント
+    if chars[i..].starts_with(&['/', '/']) {
+        while i < chars.len() && chars[i] != '\n' {
+            i += 1;
+        }
+        return Some(i);
+    }
+    // /*から*/まではブロックコメント
+    // 途中に改行があっても一つの空白とみなす
+    if chars[i..].starts_with(&['/', '*']) {
+        let start = i;
+        i += 2;
+        while i < chars.len() && !chars[i..].starts_with(&['*', '/']) {
+            i += 1;
+        }
+        assert!(
+            i < chars.len(),
+            "{}: ブロックコメントが閉じられていません。プログラムを終了します。",
+            loc(start)
+        );
+        return Some(i + 2);
+    }
+    None
+}
+
+//行末の\を取り除いて次の行と連結する
+//エラー表示のため、各文字の元の行と列も返す
+fn splice_lines(input: &str) -> (Vec<char>, Vec<(usize, usize)>) {
+    let mut chars = Vec::new();
+    let mut locs = Vec::new();
+    let (mut line, mut col) = (1, 1);
+    let mut iter = input.chars().peekable();
+    while let Some(c) = iter.next() {
+        if c == '\\' && iter.next_if_eq(&'\n').is_some() {
+            line += 1;
+            col = 1;
+            continue;
+        }
+        chars.push(c);
+        locs.push((line, col));
+        if c == '\n' {
+            line += 1;
+            col = 1;
+        } else {
+            col += 1;
+        }
+    }
+    (chars, locs)
+}
+
+//記号(長いものから順に照合する)
+const PUNCTS: [&str; 48] = [
+    "<<=", ">>=", "...", "==", "!=", "<=", ">=", "&&", "||", "<<", ">>", "->", "++", "--", "+=",
+    "-=", "*=", "/=", "%=", "&=", "|=", "^=", "##", "+", "-", "*", "/", "%", "(", ")", "{", "}",
+    "[", "]", "<", ">", "=", "!", "~", "&", "|", "^", "?", ":", ";", ",", ".", "#",
+];
+
+impl Preprocessor<'_> {
+    //トークン列を前処理してoutに追加する
+    //dirは#include "..."で最初に探すディレクトリ、nameはエラー表示用のファイル名
+    fn process(
+        &mut self,
+        tokens: Vec<PpToken>,
+        dir: Option<&Path>,
+        name: &str,
+        out: &mut Vec<PpToken>,
+    ) {
+        let mut conds: Vec<CondIncl> = Vec::new();
+        // マクロを展開した結果は先頭に戻して再び走査する
+        let mut stream: VecDeque<PpToken> = tokens.into();
+        while let Some(token) = stream.pop_front() {
+            let active = conds
+                .last()
+                .is_none_or(|cond| cond.state == CondState::Active);
+            // マクロの展開で生じた#はディレクティブではない
+            if !(token.bol && token.text == "#" && token.hideset.is_empty()) {
+                if active {
+                    if let Some(token) = self.expand(token, &mut stream) {
+                        out.push(token);
+                    }
+                }
+                continue;
+            }
+
+            // 行末までがディレクティブ
+            let mut line = Vec::new();
+            while let Some(token) = stream.pop_front() {
+                if token.bol {
+                    stream.push_front(token);
+                    break;
+                }
+                line.push(token);
+            }
+            // 空のディレクティブは何もしない
+            if line.is_empty() {
+                continue;
+            }
+            let directive = line.remove(0).text;
+            match directive.as_str() {
+                "if" | "ifdef" | "ifndef" => {
+                    // 外側の区間を出力しない場合は条件式を評価しない
+                    let state = if !active {
+                        CondState::Done
+                    } else if self.condition(&directive, line, name) {
+                        CondState::Active
+                    } else {
+                        CondState::Waiting
+                    };
+                    conds.push(CondIncl {
+                        state,
+                        seen_else: false,
+                    });
+                }
+                "elif" => {
+                    let cond = conds.last().unwrap_or_else(|| stray(&directive, name));
+                    assert!(
+                        !cond.seen_else,
+                        "{}: #elseの後に#elifがあります。プログラムを終了します。",
+                        name
+                    );
+                    // まだ真の区間が無い場合のみ条件式を評価する
+                    let state = match cond.state {
+                        CondState::Waiting if self.condition("if", line, name) => CondState::Active,
+                        CondState::Waiting => CondState::Waiting,
+                        CondState::Active | CondState::Done => CondState::Done,
+                    };
+                    conds.last_mut().unwrap().state = state;
+                }
+                "else" => {
+                    let cond = conds.last_mut().unwrap_or_else(|| stray(&directive, name));
+                    assert!(
+                        !cond.seen_else,
+                        "{}: #elseが重複しています。プログラムを終了します。",
+                        name
+                    );
+                    cond.state = if cond.state == CondState::Waiting {
+                        CondState::Active
+                    } else {
+                        CondState::Done
+                    };
+                    cond.seen_else = true;
+                }
+                "endif" => {
+                    if conds.pop().is_none() {
+                        stray(&directive, name);
+                    }
+                }
+                // 出力しない区間では条件付きコンパイル以外のディレクティブを無視する
+                _ if !active => (),
+                "include" => self.include(line, dir, name, out),
+                "define" => self.define(line, name),
+                "undef" => {
+                    let macro_name = macro_name(&line, &directive, name);
+                    self.macros.remove(&macro_name);
+                }
+                _ => panic!(
+                    "{}: 不明なディレクティブ#{}があります。プログラムを終了します。",
+                    name, directive
+                ),
+            }
+        }
+        assert!(
+            conds.is_empty(),
+            "{}: #endifがありません。プログラムを終了します。",
+            name
+        );
+    }
+
+    //#defineの後のトークン列からマクロを定義する
+    fn define(&mut self, mut line: Vec<PpToken>, name: &str) {
+        let macro_name = macro_name(&line, "define", name);
+        let mut body = line.split_off(1);
+        // マクロ名の直後に空白を入れずに(があれば関数形式マクロ
+        if body
+            .first()
+            .is_none_or(|token| token.text != "(" || token.space)
+        {
+            self.macros.insert(
+                macro_name,
+                Macro {
+                    params: None,
+                    variadic: false,
+                    body,
+                },
+            );
+            return;
+        }
+
+        // 仮引数
+        let mut iter = body.into_iter();
+        iter.next();
+        let mut params = Vec::new();
+        let mut variadic = false;
+        loop {
+            let token = iter.next().unwrap_or_else(|| {
+                panic!(
+                    "{}: マクロ{}の仮引数の)がありません。プログラムを終了します。",
+                    name, macro_name
+                )
+            });
+            match token.text.as_str() {
+                ")" if params.is_empty() && !variadic => break,
+                // 可変長引数は__VA_ARGS__で参照する
+                "..." => {
+                    variadic = true;
+                    params.push("__VA_ARGS__".to_string());
+                }
+                _ if token.kind == PpKind::Ident && !variadic => params.push(token.text),
+                _ => panic!(
+                    "{}: マクロ{}の仮引数が不正です。プログラムを終了します。",
+                    name, macro_name
+                ),
+            }
+            match iter.next() {
+                Some(token) if token.text == ")" => break,
+                Some(token) if token.text == "," && !variadic => (),
+                _ => panic!(
+                    "{}: マクロ{}の仮引数が不正です。プログラムを終了します。",
+                    name, macro_name
+                ),
+            }
+        }
+        body = iter.collect();
+
+        // #の後には仮引数が必要
+        for (i, token) in body.iter().enumerate() {
+            if token.text == "#" {
+                assert!(
+                    body.get(i + 1)
+                        .is_some_and(|next| params.contains(&next.text)),
+                    "{}: マクロ{}の#の後に仮引数がありません。プログラムを終了します。",
+                    name,
+                    macro_name
+                );
+            }
+        }
+        assert!(
+            body.first().is_none_or(|token| token.text != "##")
+                && body.last().is_none_or(|token| token.text != "##"),
+            "{}: マクロ{}の##の前後にトークンがありません。プログラムを終了します。",
+            name,
+            macro_name
+        );
+        self.macros.insert(
+            macro_name,
+            Macro {
+                params: Some(params),
+                variadic,
+                body,
+            },
+        );
+    }
+
+    //トークンがマクロ名であれば展開した結果をstreamの先頭に戻してNoneを返す
+    //展開しなければトークンをそのまま返す
+    fn expand(&self, token: PpToken, stream: &mut VecDeque<PpToken>) -> Option<PpToken> {
+        let mac = match self.macros.get(&token.text) {
+            Some(mac) if token.kind == PpKind::Ident && !token.hideset.contains(&token.text) => mac,
+            _ => return Some(token),
+        };
+
+        let (mut expanded, mut hideset) = match &mac.params {
+            None => (self.substitute(mac, &[]), token.hideset.clone()),
+            Some(params) => {
+                // 関数形式マクロは直後に(が無ければ展開しない
+                if stream.front().is_none_or(|next| next.text != "(") {
+                    return Some(token);
+                }
+                stream.pop_front();
+                let (args, rparen) = read_args(stream, &token.text);
+                let args = match_args(args, params.len(), mac.variadic, &token.text);
+                // hide setは展開したマクロ名と、マクロ名と)の両方で展開済みのマクロ
+                let hideset = token
+                    .hideset
+                    .iter()
+                    .filter(|name| rparen.hideset.contains(name))
+                    .cloned()
+                    .collect();
+                (self.substitute(mac, &args), hideset)
+            }
+        };
+        hideset.push(token.text.clone());
+
+        for (i, expanded) in expanded.iter_mut().enumerate() {
+            // 置換後のトークンは元のトークンの位置に置く
+            expanded.bol = i == 0 && token.bol;
+            // 前後のトークンとつながらないように空白を入れる
+            expanded.space |= i == 0;
+            for name in &hideset {
+                if !expanded.hideset.contains(name) {
+                    expanded.hideset.push(name.clone());
+                }
+            }
+        }
+        for expanded in expanded.into_iter().rev() {
+            stream.push_front(expanded);
+        }
+        None
+    }
+
+    //トークン列に含まれるマクロをすべて展開する
+    fn expand_all(&self, tokens: Vec<PpToken>) -> Vec<PpToken> {
+        let mut stream: VecDeque<PpToken> = tokens.into();
+        let mut out = Vec::new();
+        while let Some(token) = stream.pop_front() {
+            if let Some(token) = self.expand(token, &mut stream) {
+                out.push(token);
+            }
+        }
+        out
+    }
+
+    //マクロの本体の仮引数を実引数で置き換える
+    fn substitute(&self, mac: &Macro, args: &[Vec<PpToken>]) -> Vec<PpToken> {
+        let params = mac.params.as_deref().unwrap_or_default();
+        let arg = |token: &PpToken| {
+            params
+                .iter()
+                .position(|param| *param == token.text)
+                .map(|i| &args[i])
+        };
+        let body = &mac.body;
+        let mut out: Vec<PpToken> = Vec::new();
+        let mut i = 0;
+        while i < body.len() {
+            let token = &body[i];
+            // #仮引数 は実引数を文字列リテラルにする
+            if token.text == "#" && mac.params.is_some() {
+                let arg = arg(&body[i + 1]).unwrap();
+                let mut token = stringize(arg);
+                token.space = body[i].space;
+                out.push(token);
+                i += 2;
+                continue;
+            }
+            // ## は前後のトークンを連結する
+            if token.text == "##" {
+                let rhs = &body[i + 1];
+                i += 2;
+                // , ## __VA_ARGS__ は連結せず、空の可変長引数の場合は,を取り除く(GNU拡張)
+                if mac.variadic
+                    && rhs.text == "__VA_ARGS__"
+                    && out.last().is_some_and(|token| token.text == ",")
+                {
+                    let arg = arg(rhs).unwrap();
+                    if arg.is_empty() {
+                        out.pop();
+                    }
+                    out.extend(self.expand_all(arg.clone()));
+                    continue;
+                }
+                let rhs = match arg(rhs) {
+                    Some(arg) => arg.clone(),
+                    None => vec![rhs.clone()],
+                };
+                // どちらかが空の実引数であれば連結しない
+                let mut rhs = rhs.into_iter();
+                match (out.pop(), rhs.next()) {
+                    (Some(lhs), Some(first)) => out.push(paste(&lhs, &first)),
+                    (Some(lhs), None) => out.push(lhs),
+                    (None, Some(first)) => out.push(first),
+                    (None, None) => (),
+                }
+                out.extend(rhs);
+                continue;
+            }
+            match arg(token) {
+                // ##の左側の仮引数は展開せずに置き換える
+                Some(arg) if body.get(i + 1).is_some_and(|next| next.text == "##") => {
+                    out.extend(arg.iter().cloned());
+                }
+                // それ以外の仮引数は実引数を完全に展開してから置き換える
+                Some(arg) => {
+                    let mut expanded = self.expand_all(arg.clone());
+                    if let Some(first) = expanded.first_mut() {
+                        first.space = token.space;
+                    }
+                    out.extend(expanded);
+                }
+                None => out.push(token.clone()),
+            }
+            i += 1;
+        }
+        for token in &mut out {
+            token.bol = false;
+        }
+        out
+    }
+
+    //#if, #ifdef, #ifndefの条件を判定する
+    fn condition(&self, directive: &str, line: Vec<PpToken>, name: &str) -> bool {
+        match directive {
+            "ifdef" => self
+                .macros
+                .contains_key(&macro_name(&line, directive, name)),
+            "ifndef" => !self
+                .macros
+                .contains_key(&macro_name(&line, directive, name)),
+            _ => {
+                // definedを先に0か1に置き換えてからマクロを展開する
+                let mut tokens = Vec::new();
+                let mut iter = line.into_iter();
+                while let Some(token) = iter.next() {
+                    if token.text != "defined" {
+                        tokens.push(token);
+                        continue;
+                    }
+                    let mut name = iter.next();
+                    let paren = name.as_ref().is_some_and(|token| token.text == "(");
+                    if paren {
+                        name = iter.next();
+                    }
+                    let name = name
+                        .filter(|token| token.kind == PpKind::Ident)
+                        .expect("definedの後にマクロ名がありません。プログラムを終了します。");
+                    if paren {
+                        assert!(
+                            iter.next().is_some_and(|token| token.text == ")"),
+                            "definedの)がありません。プログラムを終了します。"
+                        );
+                    }
+                    tokens.push(PpToken {
+                        kind: PpKind::Number,
+                        text: if self.macros.contains_key(&name.text) {
+                            "1"
+                        } else {
+                            "0"
+                        }
+                        .to_string(),
+                        bol: false,
+                        space: true,
+                        hideset: Vec::new(),
+                    });
+                }
+                let tokens = self.expand_all(tokens);
+                let mut eval = Eval { tokens, pos: 0 };
+                let value = eval.conditional();
+                assert!(
+                    eval.pos == eval.tokens.len(),
+                    "#ifの条件式が不正です。プログラムを終了します。"
+                );
+                value.value != 0
+            }
+        }
+    }
+
+    //#includeで指定されたファイルを前処理してoutに追加する
+    fn include(
+        &mut self,
+        line: Vec<PpToken>,
+        dir: Option<&Path>,
+        name: &str,
+        out: &mut Vec<PpToken>,
+    ) {
+        // ファイル名がマクロで指定されている場合は先に展開する
+        let tokens = self.expand_all(line);
+        let (file, quoted) = match tokens.first() {
+            Some(token) if token.kind == PpKind::Str => {
+                (token.text[1..token.text.len() - 1].to_string(), true)
+            }
+            Some(token) if token.text == "<" => {
+                let end = tokens
+                    .iter()
+                    .position(|token| token.text == ">")
+                    .unwrap_or_else(|| {
+                        panic!(
+                            "{}: #includeの>がありません。プログラムを終了します。",
+                            name
+                        )
+                    });
+                let file: Vec<&str> = tokens[1..end]
+                    .iter()
+                    .map(|token| token.text.as_str())
+                    .collect();
+                (file.concat(), false)
+            }
+            _ => panic!(
+                "{}: #includeのファイル名が不正です。プログラムを終了します。",
+                name
+            ),
+        };
+
+        // "..."はインクルード元のファイルのディレクトリを先に探す
+        let mut candidates = Vec::new();
+        if quoted {
+            candidates.push(dir.map_or_else(|| PathBuf::from(&file), |dir| dir.join(&file)));
+        }
+        for include_path in self.include_paths {
+            candidates.push(Path::new(include_path).join(&file));
+        }
+        let path = candidates
+            .into_iter()
+            .find(|path| path.is_file())
+            .unwrap_or_else(|| {
+                panic!(
+                    "{}: インクルードファイル{}が見つかりません。プログラムを終了します。",
+                    name, file
+                )
+            });
+        let input = fs::read_to_string(&path).unwrap_or_else(|_| {
+            panic!(
+                "ファイル{}を読み込めません。プログラムを終了します。",
+                path.display()
+            )
+        });
+
+        self.depth += 1;
+        assert!(
+            self.depth <= INCLUDE_DEPTH_MAX,
+            "{}: #includeの入れ子が深すぎます。プログラムを終了します。",
+            path.display()
+        );
+        let mut tokens = lex(&input, &path.display().to_string());
+        if let Some(token) = tokens.first_mut() {
+            token.bol = true;
+        }
+        self.process(tokens, path.parent(), &path.display().to_string(), out);
+        self.depth -= 1;
+    }
+}
+
+//#define, #undef, #ifdef, #ifndefの後のマクロ名を返す
+fn macro_name(line: &[PpToken], directive: &str, name: &str) -> String {
+    match line.first() {
+        Some(token) if token.kind == PpKind::Ident => token.text.clone(),
+        _ => panic!(
+            "{}: #{}の後にマクロ名がありません。プログラムを終了します。",
+            name, directive
+        ),
+    }
+}
+
+//関数形式マクロの実引数を)まで読み込む
+//実引数ごとのトークン列と)のトークンを返す
+fn read_args(stream: &mut VecDeque<PpToken>, name: &str) -> (Vec<Vec<PpToken>>, PpToken) {
+    let mut args = vec![Vec::new()];
+    // 括弧の入れ子の深さ
+    let mut depth = 0;
+    loop {
+        let token = stream.pop_front().unwrap_or_else(|| {
+            panic!(
+                "マクロ{}の実引数の)がありません。プログラムを終了します。",
+                name
+            )
+        });
+        match token.text.as_str() {
+            ")" if depth == 0 => return (args, token),
+            "," if depth == 0 => {
+                args.push(Vec::new());
+                continue;
+            }
+            "(" => depth += 1,
+            ")" => depth -= 1,
+            _ => (),
+        }
+        args.last_mut().unwrap().push(token);
+    }
+}
+
+//実引数の数を仮引数の数と照合する
+//可変長引数は残りの実引数を,で連結して一つの実引数とする
+fn match_args(
+    mut args: Vec<Vec<PpToken>>,
+    params_num: usize,
+    variadic: bool,
+    name: &str,
+) -> Vec<Vec<PpToken>> {
+    // 引数の無いマクロの()は空の実引数一つと読み込まれる
+    if params_num == 0 && args.len() == 1 && args[0].is_empty() {
+        return Vec::new();
+    }
+    if variadic && args.len() >= params_num {
+        let rest = args.split_off(params_num - 1);
+        let mut va_args = Vec::new();
+        for (i, arg) in rest.into_iter().enumerate() {
+            if i > 0 {
+                va_args.push(PpToken {
+                    kind: PpKind::Punct,
+                    text: ",".to_string(),
+                    bol: false,
+                    space: false,
+                    hideset: Vec::new(),
+                });
+            }
+            va_args.extend(arg);
+        }
+        args.push(va_args);
+    } else if variadic && args.len() == params_num - 1 {
+        // 可変長引数が省略された場合
+        args.push(Vec::new());
+    }
+    assert!(
+        args.len() == params_num,
+        "マクロ{}の実引数の数が一致しません。プログラムを終了します。",
+        name
+    );
+    args
+}
+
+//実引数を文字列リテラルにする
+fn stringize(arg: &[PpToken]) -> PpToken {
+    let mut text = String::from('"');
+    for (i, token) in arg.iter().enumerate() {
+        if i > 0 && token.space {
+            text.push(' ');
+        }
+        // 文字列と文字のリテラル中の"と\はエスケープする
+        for c in token.text.chars() {
+            if matches!(token.kind, PpKind::Str | PpKind::Char) && matches!(c, '"' | '\\') {
+                text.push('\\');
+            }
+            text.push(c);
+        }
+    }
+    text.push('"');
+    PpToken {
+        kind: PpKind::Str,
+        text,
+        bol: false,
+        space: false,
+        hideset: Vec::new(),
+    }
+}
+
+//二つのトークンを連結して一つのトークンにする
+fn paste(lhs: &PpToken, rhs: &PpToken) -> PpToken {
+    let text = format!("{}{}", lhs.text, rhs.text);
+    let mut tokens = lex(&text, "##");
+    assert!(
+        tokens.len() == 1,
+        "{}と{}を連結しても正しいトークンになりません。プログラムを終了します。",
+        lhs.text,
+        rhs.text
+    );
+    let mut token = tokens.pop().unwrap();
+    token.space = lhs.space;
+    token
+}
+
+//対応する#ifが無い場合のエラー
+fn stray(directive: &str, name: &str) -> ! {
+    panic!(
+        "{}: #{}に対応する#ifがありません。プログラムを終了します。",
+        name, directive
+    )
+}
+
+//#ifの条件式を評価する
+struct Eval {
+    tokens: Vec<PpToken>,
+    pos: usize,
+}
+
+//#ifの条件式の値
+//すべてintmax_tかuintmax_tとして扱い、符号なしの値もビット列をi64で持つ
+#[derive(Clone, Copy)]
+struct Value {
+    value: i64,
+    unsigned: bool,
+}
+
+impl Value {
+    fn signed(value: i64) -> Self {
+        Value {
+            value,
+            unsigned: false,
+        }
+    }
+
+    fn from_bool(cond: bool) -> Self {
+        Value::signed(i64::from(cond))
+    }
+
+    #[allow(clippy::cast_sign_loss)]
+    fn as_u64(self) -> u64 {
+        self.value as u64
+    }
+}
+
+impl Eval {
+    //次のトークンが引数の記号であれば読み進めて真を返す
+    fn consume(&mut self, op: &str) -> bool {
+        if self
+            .tokens
+            .get(self.pos)
+            .is_some_and(|token| token.kind == PpKind::Punct && token.text == op)
+        {
+            self.pos += 1;
+            return true;
+        }
+        false
+    }
+
+    // conditional = logor ("?" conditional ":" conditional)?
+    fn conditional(&mut self) -> Value {
+        let cond = self.binary(0);
+        if !self.consume("?") {
+            return cond;
+        }
+        let then = self.conditional();
+        assert!(
+            self.consume(":"),
+            "#ifの条件式に:がありません。プログラムを終了します。"
+        );
+        let els = self.conditional();
+        // 結果の型は2つの値の型を揃えたものになる
+        let unsigned = then.unsigned || els.unsigned;
+        let value = if cond.value != 0 {
+            then.value
+        } else {
+            els.value
+        };
+        Value { value, unsigned }
+    }
+
+    //二項演算子を優先順位の低いものから順に処理する
+    fn binary(&mut self, level: usize) -> Value {
+        const LEVELS: [&[&str]; 10] = [
+            &["||"],
+            &["&&"],
+            &["|"],
+            &["^"],
+            &["&"],
+            &["==", "!="],
+            &["<", "<=", ">", ">="],
+            &["<<", ">>"],
+            &["+", "-"],
+            &["*", "/", "%"],
+        ];
+        if level == LEVELS.len() {
+            return self.unary();
+        }
+        let mut lhs = self.binary(level + 1);
+        'outer: loop {
+            for op in LEVELS[level] {
+                if self.consume(op) {
+                    let rhs = self.binary(level + 1);
+                    lhs = binary_op(op, lhs, rhs);
+                    continue 'outer;
+                }
+            }
+            return lhs;
+        }
+    }
+
+    // unary = ("+" | "-" | "!" | "~") unary | primary
+    fn unary(&mut self) -> Value {
+        if self.consume("+") {
+            self.unary()
+        } else if self.consume("-") {
+            let operand = self.unary();
+            Value {
+                value: operand.value.wrapping_neg(),
+                ..operand
+            }
+        } else if self.consume("!") {
+            Value::from_bool(self.unary().value == 0)
+        } else if self.consume("~") {
+            let operand = self.unary();
+            Value {
+                value: !operand.value,
+                ..operand
+            }
+        } else {
+            self.primary()
+        }
+    }
+
+    // primary = "(" conditional ")" | num | char | ident
+    fn primary(&mut self) -> Value {
+        if self.consume("(") {
+            let value = self.conditional();
+            assert!(
+                self.consume(")"),
+                "#ifの条件式に)がありません。プログラムを終了します。"
+            );
+            return value;
+        }
+        let token = self
+            .tokens
+            .get(self.pos)
+            .expect("#ifの条件式が途中で終わっています。プログラムを終了します。");
+        self.pos += 1;
+        match token.kind {
+            // 符号なしの型の整数リテラルはuintmax_tとして扱う
+            PpKind::Number => {
+                let (value, ty) = parse_int_literal(&token.text);
+                Value {
+                    value,
+                    unsigned: ty.is_unsigned(),
+                }
+            }
+            PpKind::Char => Value::signed(parse_char(&token.text[1..token.text.len() - 1])),
+            // 展開されずに残った識別子は0とする
+            PpKind::Ident => Value::signed(0),
+            _ => panic!(
+                "#ifの条件式に不正なトークン{}があります。プログラムを終了します。",
+                token.text
+            ),
+        }
+    }
+}
+
+//二項演算子を計算する
+//どちらかが符号なしであれば、もう一方も符号なしに変換して計算する
+//シフトの結果は左辺の型になる
+fn binary_op(op: &str, lhs: Value, rhs: Value) -> Value {
+    let unsigned = lhs.unsigned || rhs.unsigned;
+    let (l, r) = (lhs.value, rhs.value);
+    let (ul, ur) = (lhs.as_u64(), rhs.as_u64());
+    let value = match op {
+        "||" => return Value::from_bool(l != 0 || r != 0),
+        "&&" => return Value::from_bool(l != 0 && r != 0),
+        "==" => return Value::from_bool(l == r),
+        "!=" => return Value::from_bool(l != r),
+        "<" if unsigned => return Value::from_bool(ul < ur),
+        "<" => return Value::from_bool(l < r),
+        "<=" if unsigned => return Value::from_bool(ul <= ur),
+        "<=" => return Value::from_bool(l <= r),
+        ">" if unsigned => return Value::from_bool(ul > ur),
+        ">" => return Value::from_bool(l > r),
+        ">=" if unsigned => return Value::from_bool(ul >= ur),
+        ">=" => return Value::from_bool(l >= r),
+        #[allow(clippy::cast_possible_truncation)]
+        "<<" => {
+            return Value {
+                value: l.wrapping_shl(ur as u32),
+                ..lhs
+            }
+        }
+        #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+        ">>" if lhs.unsigned => {
+            return Value {
+                value: ul.wrapping_shr(ur as u32) as i64,
+                ..lhs
+            }
+        }
+        #[allow(clippy::cast_possible_truncation)]
+        ">>" => {
+            return Value {
+                value: l.wrapping_shr(ur as u32),
+                ..lhs
+            }
+        }
+        "|" => l | r,
+        "^" => l ^ r,
+        "&" => l & r,
+        "+" => l.wrapping_add(r),
+        "-" => l.wrapping_sub(r),
+        "*" => l.wrapping_mul(r),
+        "/" | "%" => {
+            assert!(
+                r != 0,
+                "#ifの条件式で0で除算しました。プログラムを終了します。"
+            );
+            #[allow(clippy::cast_possible_wrap)]
+            match (op, unsigned) {
+                ("/", true) => (ul / ur) as i64,
+                ("/", false) => l.wrapping_div(r),
+                (_, true) => (ul % ur) as i64,
+                (_, false) => l.wrapping_rem(r),
+            }
+        }
+        _ => unreachable!(),
+    };
+    Value { value, unsigned }
+}
@@ -0,0 +1,389 @@
+use crate::codegen::{align_to, reloc_expr, split_data, Target};
+use crate::kind::Kind;
+use crate::kind::Reloc;
+use crate::types::Type;
+
+// 引数の入るレジスタの数(a0〜a7)
+const ARG_REGISTER_NUM: usize = 8;
+
+//RISC-V 64(RV64GC, LP64)向けのコード生成
+//スタックポインタは常に16の倍数でなければならないため、スタックマシンの値は1つにつき16バイト使う
+//va_listはSystem V ABIの配置のままでLP64のものと異なるため、va_listや構造体は関数に渡せない
+pub struct RiscV64 {
+    //生成したアセンブリの各行
+    lines: Vec<String>,
+    //生成中の関数名(returnのジャンプ先のラベルに使う)
+    func_name: String,
+}
+
+impl RiscV64 {
+    pub fn new() -> Self {
+        RiscV64 {
+            lines: Vec::new(),
+            func_name: String::new(),
+        }
+    }
+
+    fn emit(&mut self, line: String) {
+        self.lines.push(line);
+    }
+
+    fn push(&mut self, reg: &str) {
+        self.emit("  addi sp, sp, -16".to_string());
+        self.emit(format!("  sd {reg}, 0(sp)"));
+    }
+
+    fn pop_to(&mut self, reg: &str) {
+        self.emit(format!("  ld {reg}, 0(sp)"));
+        self.emit("  addi sp, sp, 16".to_string());
+    }
+
+    //汎用レジスタregにあるty型の浮動小数点数のビット列を浮動小数点数のレジスタfregに移す
+    fn mov_to_freg(&mut self, freg: &str, reg: &str, ty: &Type) {
+        let w = if let Type::Float = ty { "w" } else { "d" };
+        self.emit(format!("  fmv.{w}.x {freg}, {reg}"));
+    }
+
+    //浮動小数点数のレジスタfregにあるty型の値のビット列をa0に移す
+    //fmv.x.wは符号拡張するので、floatは上位32ビットを0にし直す
+    fn mov_from_freg(&mut self, freg: &str, ty: &Type) {
+        if let Type::Float = ty {
+            self.emit(format!("  fmv.x.w a0, {freg}"));
+            self.emit("  slli a0, a0, 32".to_string());
+            self.emit("  srli a0, a0, 32".to_string());
+        } else {
+            self.emit(format!("  fmv.x.d a0, {freg}"));
+        }
+    }
+
+    //sizeバイトの値を書き込む命令
+    fn store_inst(size: usize) -> &'static str {
+        match size {
+            1 => "sb",
+            2 => "sh",
+            4 => "sw",
+            _ => "sd",
+        }
+    }
+}
+
+//浮動小数点数の命令の精度を表す接尾辞(floatはs、doubleはd)
+fn fp_suffix(ty: &Type) -> &'static str {
+    match ty {
+        Type::Float => "s",
+        _ => "d",
+    }
+}
+
+impl Target for RiscV64 {
+    fn data(&mut self, name: &str, align: usize, init: &[u8], relocs: &[Reloc], is_static: bool) {
+        self.emit(".data".to_string());
+        if !is_static {
+            self.emit(format!(".globl {name}"));
+        }
+        self.emit(format!(".balign {align}"));
+        self.emit(format!("{name}:"));
+        // 初期値がすべて0であれば.zeroでまとめて確保する
+        if relocs.is_empty() && init.iter().all(|byte| *byte == 0) {
+            self.emit(format!(".zero {}", init.len()));
+            return;
+        }
+        for (bytes, reloc) in split_data(init, relocs) {
+            if !bytes.is_empty() {
+                let bytes: Vec<String> = bytes.iter().map(u8::to_string).collect();
+                self.emit(format!(".byte {}", bytes.join(", ")));
+            }
+            if let Some(reloc) = reloc {
+                self.emit(format!(".quad {}", reloc_expr(&reloc.label, reloc.addend)));
+            }
+        }
+    }
+
+    fn prologue(&mut self, name: &str, stack_size: usize, is_static: bool) {
+        // リターンアドレスとフレームポインタを退避し、ローカル変数の領域を確保する
+        // addiの即値は12ビットに収まらない場合があるので、一度t0に入れる
+        let stack_size = align_to(stack_size, 16);
+        self.func_name = name.to_string();
+        self.emit(".text".to_string());
+        if !is_static {
+            self.emit(format!(".globl {name}"));
+        }
+        self.emit(format!("{name}:"));
+        self.emit("  addi sp, sp, -16".to_string());
+        self.emit("  sd ra, 8(sp)".to_string());
+        self.emit("  sd fp, 0(sp)".to_string());
+        self.emit("  mv fp, sp".to_string());
+        self.emit(format!("  li t0, {stack_size}"));
+        self.emit("  sub sp, sp, t0".to_string());
+    }
+
+    fn store_param(&mut self, index: usize, offset: usize, ty: &Type) {
+        assert!(
+            index < ARG_REGISTER_NUM,
+            "仮引数はレジスタの数である{}個以下にして下さい。プログラムを終了します。",
+            ARG_REGISTER_NUM
+        );
+        self.emit(format!("  li t0, {offset}"));
+        self.emit("  sub t0, fp, t0".to_string());
+        if ty.is_flonum() {
+            let inst = if let Type::Float = ty { "fsw" } else { "fsd" };
+            self.emit(format!("  {inst} fa{index}, 0(t0)"));
+            return;
+        }
+        let inst = Self::store_inst(ty.size());
+        self.emit(format!("  {inst} a{index}, 0(t0)"));
+    }
+
+    fn save_va_area(&mut self, offset: usize) {
+        self.emit(format!("  li t0, {offset}"));
+        self.emit("  sub t0, fp, t0".to_string());
+        for index in 0..ARG_REGISTER_NUM {
+            self.emit(format!("  sd a{index}, {}(t0)", 8 * index));
+        }
+    }
+
+    // RISC-Vの呼び出し規約では可変長引数の浮動小数点数も整数のレジスタで渡すので、
+    // 浮動小数点数のレジスタは保存しない
+    fn va_layout(&self) -> (usize, Option<usize>) {
+        (ARG_REGISTER_NUM, None)
+    }
+
+    fn epilogue(&mut self) {
+        self.emit(format!(".Lreturn.{}:", self.func_name));
+        self.emit("  mv sp, fp".to_string());
+        self.emit("  ld fp, 0(sp)".to_string());
+        self.emit("  ld ra, 8(sp)".to_string());
+        self.emit("  addi sp, sp, 16".to_string());
+        self.emit("  ret".to_string());
+    }
+
+    fn push_num(&mut self, value: i64) {
+        self.emit(format!("  li a0, {value}"));
+        self.push("a0");
+    }
+
+    fn push_local_address(&mut self, offset: usize) {
+        self.emit(format!("  li t0, {offset}"));
+        self.emit("  sub a0, fp, t0".to_string());
+        self.push("a0");
+    }
+
+    fn push_global_address(&mut self, name: &str) {
+        self.emit(format!("  lla a0, {name}"));
+        self.push("a0");
+    }
+
+    fn push_stack_args_address(&mut self) {
+        // 退避したフレームポインタとリターンアドレスの上にある
+        self.emit("  addi a0, fp, 16".to_string());
+        self.push("a0");
+    }
+
+    fn load(&mut self, ty: &Type) {
+        if ty.is_struct() {
+            return;
+        }
+        self.pop_to("a0");
+        // floatのビット列はゼロ拡張する
+        let inst = match (ty.size(), ty.is_unsigned() || ty.is_flonum()) {
+            (1, true) => "lbu",
+            (1, false) => "lb",
+            (2, true) => "lhu",
+            (2, false) => "lh",
+            (4, true) => "lwu",
+            (4, false) => "lw",
+            _ => "ld",
+        };
+        self.emit(format!("  {inst} a0, 0(a0)"));
+        self.push("a0");
+    }
+
+    fn store(&mut self, ty: &Type) {
+        self.pop_to("a1");
+        self.pop_to("a0");
+        if ty.is_struct() {
+            // 入力元(a1)から出力先(a0)へ1バイトずつコピーする
+            self.emit("  mv a2, a0".to_string());
+            for _ in 0..ty.size() {
+                self.emit("  lbu t1, 0(a1)".to_string());
+                self.emit("  sb t1, 0(a2)".to_string());
+                self.emit("  addi a1, a1, 1".to_string());
+                self.emit("  addi a2, a2, 1".to_string());
+            }
+            self.push("a0");
+            return;
+        }
+        let inst = Self::store_inst(ty.size());
+        self.emit(format!("  {inst} a1, 0(a0)"));
+        self.push("a1");
+    }
+
+    fn pop(&mut self) {
+        self.pop_to("a0");
+    }
+
+    fn binary(&mut self, kind: &Kind, ty: &Type) {
+        self.pop_to("a1");
+        self.pop_to("a0");
+        if ty.is_flonum() {
+            let d = fp_suffix(ty);
+            self.mov_to_freg("ft0", "a0", ty);
+            self.mov_to_freg("ft1", "a1", ty);
+            match kind {
+                Kind::Add => self.emit(format!("  fadd.{d} ft0, ft0, ft1")),
+                Kind::Sub => self.emit(format!("  fsub.{d} ft0, ft0, ft1")),
+                Kind::Mul => self.emit(format!("  fmul.{d} ft0, ft0, ft1")),
+                Kind::Div => self.emit(format!("  fdiv.{d} ft0, ft0, ft1")),
+                Kind::Equal => self.emit(format!("  feq.{d} a0, ft0, ft1")),
+                // 順序付けできない(NaNを含む)場合も!=は成り立つ
+                Kind::NoEqual => {
+                    self.emit(format!("  feq.{d} a0, ft0, ft1"));
+                    self.emit("  xori a0, a0, 1".to_string());
+                }
+                Kind::LowThan => self.emit(format!("  flt.{d} a0, ft0, ft1")),
+                Kind::LowEqual => self.emit(format!("  fle.{d} a0, ft0, ft1")),
+                _ => panic!("不正なノードがあります。プログラムを終了します。"),
+            }
+            if matches!(kind, Kind::Add | Kind::Sub | Kind::Mul | Kind::Div) {
+                self.mov_from_freg("ft0", ty);
+            }
+            self.push("a0");
+            return;
+        }
+        // 符号なしの除算と比較は命令の末尾にuが付く
+        let u = if ty.is_unsigned() { "u" } else { "" };
+        match kind {
+            Kind::Add => self.emit("  add a0, a0, a1".to_string()),
+            Kind::Sub => self.emit("  sub a0, a0, a1".to_string()),
+            Kind::Mul => self.emit("  mul a0, a0, a1".to_string()),
+            Kind::Div => self.emit(format!("  div{u} a0, a0, a1")),
+            Kind::Equal => {
+                self.emit("  sub a0, a0, a1".to_string());
+                self.emit("  seqz a0, a0".to_string());
+            }
+            Kind::NoEqual => {
+                self.emit("  sub a0, a0, a1".to_string());
+                self.emit("  snez a0, a0".to_string());
+            }
+            Kind::LowThan => self.emit(format!("  slt{u} a0, a0, a1")),
+            Kind::LowEqual => {
+                // a0 <= a1 は !(a1 < a0)
+                self.emit(format!("  slt{u} a0, a1, a0"));
+                self.emit("  xori a0, a0, 1".to_string());
+            }
+            _ => panic!("不正なノードがあります。プログラムを終了します。"),
+        }
+        self.push("a0");
+    }
+
+    fn cast(&mut self, from: &Type, to: &Type) {
+        if !from.is_flonum() && !to.is_flonum() && to.size() == 8 {
+            return;
+        }
+        self.pop_to("a0");
+        // 整数と浮動小数点数の変換は、整数の符号に合わせて64ビット整数として行う
+        let l = |ty: &Type| if ty.is_unsigned() { "lu" } else { "l" };
+        if from.is_flonum() {
+            self.mov_to_freg("ft0", "a0", from);
+            if to.is_flonum() {
+                self.emit(format!(
+                    "  fcvt.{}.{} ft0, ft0",
+                    fp_suffix(to),
+                    fp_suffix(from)
+                ));
+                self.mov_from_freg("ft0", to);
+                self.push("a0");
+                return;
+            }
+            // 0方向に丸める
+            self.emit(format!("  fcvt.{}.{} a0, ft0, rtz", l(to), fp_suffix(from)));
+        } else if to.is_flonum() {
+            self.emit(format!("  fcvt.{}.{} ft0, a0", fp_suffix(to), l(from)));
+            self.mov_from_freg("ft0", to);
+            self.push("a0");
+            return;
+        }
+        // 8バイト未満の整数は、上位ビットを左シフトで追い出し、右シフトで符号かゼロを埋めながら戻す
+        if to.size() < 8 {
+            let shift = 64 - 8 * to.size();
+            let shift_right = if to.is_unsigned() { "srli" } else { "srai" };
+            self.emit(format!("  slli a0, a0, {shift}"));
+            self.emit(format!("  {shift_right} a0, a0, {shift}"));
+        }
+        self.push("a0");
+    }
+
+    fn label(&mut self, label: &str) {
+        self.emit(format!("{label}:"));
+    }
+
+    fn jump(&mut self, label: &str) {
+        self.emit(format!("  j {label}"));
+    }
+
+    fn jump_if_zero(&mut self, label: &str) {
+        // 条件分岐命令は届く範囲が狭いので、無条件ジャンプを飛び越える形にする
+        self.pop_to("a0");
+        self.emit("  bnez a0, 1f".to_string());
+        self.emit(format!("  j {label}"));
+        self.emit("1:".to_string());
+    }
+
+    fn call(&mut self, func_name: &str, arg_types: &[Type], named_num: Option<usize>, ret: &Type) {
+        assert!(
+            !arg_types.iter().any(Type::is_struct),
+            "va_listや構造体を関数の引数に渡すことには対応していません。プログラムを終了します。"
+        );
+        // 整数と浮動小数点数はそれぞれのレジスタに順に入れる
+        // 可変長引数の浮動小数点数はビット列のまま整数のレジスタに入れる
+        let named_num = named_num.unwrap_or(arg_types.len());
+        let in_freg = |index: usize, ty: &Type| ty.is_flonum() && index < named_num;
+        let fp_num = arg_types
+            .iter()
+            .enumerate()
+            .filter(|&(index, ty)| in_freg(index, ty))
+            .count();
+        let gp_num = arg_types.len() - fp_num;
+        assert!(
+            gp_num <= ARG_REGISTER_NUM && fp_num <= ARG_REGISTER_NUM,
+            "引数はレジスタの数である{}個以下にして下さい。プログラムを終了します。",
+            ARG_REGISTER_NUM
+        );
+        // 順番に注意
+        let (mut gp, mut fp) = (gp_num, fp_num);
+        for (index, ty) in arg_types.iter().enumerate().rev() {
+            if in_freg(index, ty) {
+                fp -= 1;
+                self.pop_to("t1");
+                self.mov_to_freg(&format!("fa{fp}"), "t1", ty);
+            } else {
+                gp -= 1;
+                self.pop_to(&format!("a{gp}"));
+            }
+        }
+        // スタックポインタは常に16の倍数なのでそのまま呼び出せる
+        self.emit(format!("  call {func_name}"));
+        // 浮動小数点数の戻り値はfa0に入っている
+        if ret.is_flonum() {
+            self.mov_from_freg("fa0", ret);
+        }
+        self.push("a0");
+    }
+
+    fn ret(&mut self, ty: &Type) {
+        self.pop_to("a0");
+        if ty.is_flonum() {
+            self.mov_to_freg("fa0", "a0", ty);
+        }
+        self.emit(format!("  j .Lreturn.{}", self.func_name));
+    }
+
+    fn assembly(&self) -> String {
+        let mut asm = String::new();
+        for line in &self.lines {
+            asm.push_str(line);
+            asm.push('\n');
+        }
+        asm
+    }
+}
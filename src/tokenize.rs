@@ -1,35 +1,66 @@
-use crate::kind::Kind;
-use std::collections::HashMap;
+use crate::kind::{Keyword, Kind};
+use crate::types::Type;
+use std::convert::TryFrom;
+use std::iter::Peekable;
 
 use std::str;
 
-//出現した変数一覧を保存するハッシュマップのリスト
-struct IdentList {
-    //変数の識別番号
-    list: HashMap<String, usize>,
-}
+//キーワードの表
+const KEYWORDS: [(&str, Keyword); 44] = [
+    ("auto", Keyword::Auto),
+    ("break", Keyword::Break),
+    ("case", Keyword::Case),
+    ("char", Keyword::Char),
+    ("const", Keyword::Const),
+    ("continue", Keyword::Continue),
+    ("default", Keyword::Default),
+    ("do", Keyword::Do),
+    ("double", Keyword::Double),
+    ("else", Keyword::Else),
+    ("enum", Keyword::Enum),
+    ("extern", Keyword::Extern),
+    ("float", Keyword::Float),
+    ("for", Keyword::For),
+    ("goto", Keyword::Goto),
+    ("if", Keyword::If),
+    ("inline", Keyword::Inline),
+    ("int", Keyword::Int),
+    ("long", Keyword::Long),
+    ("register", Keyword::Register),
+    ("restrict", Keyword::Restrict),
+    ("return", Keyword::Return),
+    ("short", Keyword::Short),
+    ("signed", Keyword::Signed),
+    ("sizeof", Keyword::Sizeof),
+    ("static", Keyword::Static),
+    ("struct", Keyword::Struct),
+    ("switch", Keyword::Switch),
+    ("typedef", Keyword::Typedef),
+    ("union", Keyword::Union),
+    ("unsigned", Keyword::Unsigned),
+    ("void", Keyword::Void),
+    ("volatile", Keyword::Volatile),
+    ("while", Keyword::While),
+    ("_Alignas", Keyword::Alignas),
+    ("_Alignof", Keyword::Alignof),
+    ("_Atomic", Keyword::Atomic),
+    ("_Bool", Keyword::Bool),
+    ("_Complex", Keyword::Complex),
+    ("_Generic", Keyword::Generic),
+    ("_Imaginary", Keyword::Imaginary),
+    ("_Noreturn", Keyword::Noreturn),
+    ("_Static_assert", Keyword::StaticAssert),
+    ("_Thread_local", Keyword::ThreadLocal),
+];
 
-impl IdentList {
-    //引数の変数名がリストにあればその識別番号を、無ければリストに追加して識別番号を返す
-    fn find_ident_index(&mut self, var_name: String) -> usize {
-        if let Some(index) = self.list.get(&var_name) {
-            *index
-        } else {
-            //最初が1で、追加ごとに識別番号を1ずつ増やす
-            let index = self.list.len();
-            self.list.insert(var_name, index);
-            index
-        }
-    }
+//キーワードの文字列を返す
+pub fn keyword_name(keyword: Keyword) -> &'static str {
+    KEYWORDS.iter().find(|(_, k)| *k == keyword).unwrap().0
 }
 
 // 入力文字列からトークン列を生成
+#[allow(clippy::too_many_lines)]
 pub fn tokenize(arg: &mut str::Chars) -> Vec<Kind> {
-    // 出現した変数名とその識別番号のハッシュマップ
-    let mut ident_list = IdentList {
-        list: HashMap::new(),
-    };
-
     //トークン列
     let mut tokens = Vec::new();
     //イテレータで取り出されて未処理の文字
@@ -51,13 +82,13 @@ pub fn tokenize(arg: &mut str::Chars) -> Vec<Kind> {
                 if let Some(next_c) = arg.next() {
                     match next_c {
                         '=' => tokens.push(Kind::Equal),
-                        alpha if alpha.is_numeric() => {
+                        alpha if alpha.is_ascii_digit() => {
                             // =
                             tokens.push(Kind::Assign);
 
-                            //連続した数字をVecにまとめ、数字のトークンを追加
-                            let (ret_char, ret_numbers) = continue_num(alpha, arg);
-                            tokens.push(Kind::Num(ret_numbers));
+                            //連続した数字を整数に変換し、数字のトークンを追加
+                            let (ret_char, ret_token) = continue_num(alpha.to_string(), arg);
+                            tokens.push(ret_token);
                             popped_char = ret_char;
                         }
 
@@ -69,6 +100,15 @@ pub fn tokenize(arg: &mut str::Chars) -> Vec<Kind> {
                     }
                 }
             }
+            '-' => match arg.next() {
+                // ->
+                Some('>') => tokens.push(Kind::Arrow),
+                next_c => {
+                    // -
+                    tokens.push(Kind::Sub);
+                    popped_char = next_c;
+                }
+            },
             '!' => {
                 if let Some(next_c) = arg.next() {
                     if '=' == next_c {
@@ -83,13 +123,13 @@ pub fn tokenize(arg: &mut str::Chars) -> Vec<Kind> {
                     match next_c {
                         // <=
                         '=' => tokens.push(Kind::LowEqual),
-                        alpha if alpha.is_numeric() => {
+                        alpha if alpha.is_ascii_digit() => {
                             // <
                             tokens.push(Kind::LowThan);
 
-                            //連続した数字をVecにまとめ、数字のトークンを追加
-                            let (ret_char, ret_numbers) = continue_num(alpha, arg);
-                            tokens.push(Kind::Num(ret_numbers));
+                            //連続した数字を整数に変換し、数字のトークンを追加
+                            let (ret_char, ret_token) = continue_num(alpha.to_string(), arg);
+                            tokens.push(ret_token);
                             popped_char = ret_char;
                         }
                         _ => {
@@ -105,12 +145,12 @@ pub fn tokenize(arg: &mut str::Chars) -> Vec<Kind> {
                     match next_c {
                         // >=
                         '=' => tokens.push(Kind::HighEqual),
-                        alpha if alpha.is_numeric() => {
+                        alpha if alpha.is_ascii_digit() => {
                             // >
                             tokens.push(Kind::HighThan);
-                            //連続した数字をVecにまとめ、数字のトークンを追加
-                            let (ret_char, ret_numbers) = continue_num(alpha, arg);
-                            tokens.push(Kind::Num(ret_numbers));
+                            //連続した数字を整数に変換し、数字のトークンを追加
+                            let (ret_char, ret_token) = continue_num(alpha.to_string(), arg);
+                            tokens.push(ret_token);
                             popped_char = ret_char;
                         }
                         _ => {
@@ -125,18 +165,50 @@ pub fn tokenize(arg: &mut str::Chars) -> Vec<Kind> {
             // 先頭が数字の場合は除く
             bravo if is_ident_char(bravo) => {
                 //トークンを生成
-                let (ret_char, ret_token) = ident_token(bravo, arg, &mut ident_list);
-
-                //変数名に対応した識別番号をトークンに登録
+                let (ret_char, ret_token) = ident_token(bravo, arg);
                 tokens.push(ret_token);
                 popped_char = ret_char;
             }
 
+            //文字リテラルの場合
+            '\'' => tokens.push(Kind::Num(continue_char(arg), Type::Int)),
+
+            //文字列リテラルの場合
+            //隣り合う文字列リテラルは1つに連結する
+            '"' => {
+                let bytes = continue_str(arg);
+                if let Some(Kind::Str(prev)) = tokens.last_mut() {
+                    prev.extend(bytes);
+                } else {
+                    tokens.push(Kind::Str(bytes));
+                }
+            }
+
+            //小数点から始まる浮動小数点数リテラルかメンバアクセスの.、可変長引数の...の場合
+            '.' => match arg.next() {
+                Some(next_c) if next_c.is_ascii_digit() => {
+                    let (ret_char, ret_token) = continue_num(format!(".{next_c}"), arg);
+                    tokens.push(ret_token);
+                    popped_char = ret_char;
+                }
+                Some('.') => {
+                    assert!(
+                        arg.next() == Some('.'),
+                        "..は不正な記号です。プログラムを終了します。"
+                    );
+                    tokens.push(Kind::Ellipsis);
+                }
+                next_c => {
+                    tokens.push(Kind::Dot);
+                    popped_char = next_c;
+                }
+            },
+
             //数字の場合
-            alpha if alpha.is_numeric() => {
-                //連続した数字をVecにまとめ、数字のトークンを追加
-                let (ret_char, ret_numbers) = continue_num(alpha, arg);
-                tokens.push(Kind::Num(ret_numbers));
+            alpha if alpha.is_ascii_digit() => {
+                //連続した数字を整数に変換し、数字のトークンを追加
+                let (ret_char, ret_token) = continue_num(alpha.to_string(), arg);
+                tokens.push(ret_token);
                 popped_char = ret_char;
             }
             _ => tokens = push_token(c, tokens),
@@ -149,17 +221,20 @@ pub fn tokenize(arg: &mut str::Chars) -> Vec<Kind> {
 fn push_token(c: char, mut tokens: Vec<Kind>) -> Vec<Kind> {
     match c {
         '+' => tokens.push(Kind::Add),
-        '-' => tokens.push(Kind::Sub),
         '*' => tokens.push(Kind::Mul),
         '/' => tokens.push(Kind::Div),
         '(' => tokens.push(Kind::RoundBracOpen),
         ')' => tokens.push(Kind::RoundBracClose),
         '{' => tokens.push(Kind::CurlyBracOpen),
         '}' => tokens.push(Kind::CurlyBracClose),
+        '[' => tokens.push(Kind::SquareBracOpen),
+        ']' => tokens.push(Kind::SquareBracClose),
         ';' => tokens.push(Kind::Semicolon),
         ',' => tokens.push(Kind::Comma),
-        //空白と改行はスキップ（トークンを分ける区切り文字とする）
-        ' ' | '\n' => (),
+        '&' => tokens.push(Kind::Addr),
+        ':' => tokens.push(Kind::Colon),
+        //空白と改行、タブなどはスキップ（トークンを分ける区切り文字とする）
+        _ if c.is_whitespace() => (),
         _ => panic!(
             "不正な文字\"{}\"が存在するため、プログラムを終了します。",
             c
@@ -168,162 +243,320 @@ fn push_token(c: char, mut tokens: Vec<Kind>) -> Vec<Kind> {
     tokens
 }
 
-//連続した数字をベクタ型にまとめて返す
-fn continue_num(first_c: char, c_iter: &mut str::Chars) -> (Option<char>, Vec<char>) {
-    let mut c_vec = vec![first_c];
+//数値リテラルを読み込んで数値のトークンを返す(textは読み込み済みの先頭部分)
+fn continue_num(mut text: String, c_iter: &mut str::Chars) -> (Option<char>, Kind) {
     let mut ret_char: Option<char> = None;
-    //数字以外の文字が出るまでループ
+    //接頭辞と接尾辞、小数点、指数部の符号も含めて、数値に使えない文字が出るまでループ
     for c in c_iter.by_ref() {
-        if c.is_numeric() {
-            //見つかった数字を追加
-            c_vec.push(c);
+        // 指数部の符号(16進数ではp、10進数ではeの直後)
+        let exponent = if is_hex_literal(&text) {
+            ['p', 'P']
+        } else {
+            ['e', 'E']
+        };
+        let is_exponent_sign = (c == '+' || c == '-') && text.ends_with(exponent);
+        if c.is_ascii_alphanumeric() || c == '.' || is_exponent_sign {
+            text.push(c);
         } else {
-            //数字以外が見つかったら終了
             ret_char = Some(c);
             break;
         }
     }
-    (ret_char, c_vec)
+    let is_float = if is_hex_literal(&text) {
+        text.contains(['.', 'p', 'P'])
+    } else {
+        text.contains(['.', 'e', 'E'])
+    };
+    if is_float {
+        let (value, ty) = parse_float_literal(&text);
+        return (ret_char, Kind::FNum(value, ty));
+    }
+    let (value, ty) = parse_int_literal(&text);
+    (ret_char, Kind::Num(value, ty))
 }
 
-//変数を表す文字列をベクタ型に追加して返す
-fn continue_var(mut c_vec: Vec<char>, c_iter: &mut str::Chars) -> (Option<char>, Vec<char>) {
-    let mut ret_char: Option<char> = None;
-    //変数に使えない文字が出るまでループ
-    for c in c_iter.by_ref() {
-        if is_ident_char(c) || c.is_numeric() {
-            //見つかった文字を追加
-            c_vec.push(c);
+//16進数のリテラルかどうか
+fn is_hex_literal(text: &str) -> bool {
+    text.starts_with("0x") || text.starts_with("0X")
+}
+
+//浮動小数点数リテラルを解釈し、値と型を返す
+//接尾辞fはfloat、lとなしはdoubleとする(long doubleはdoubleとして扱う)
+fn parse_float_literal(text: &str) -> (f64, Type) {
+    let (digits, ty) = if let Some(digits) = text.strip_suffix(['f', 'F']) {
+        (digits, Type::Float)
+    } else if let Some(digits) = text.strip_suffix(['l', 'L']) {
+        (digits, Type::Double)
+    } else {
+        (text, Type::Double)
+    };
+    assert!(
+        !is_hex_literal(digits),
+        "16進数の浮動小数点数リテラル{}にはまだ対応していません。プログラムを終了します。",
+        text
+    );
+    let value = digits.parse().unwrap_or_else(|_| {
+        panic!(
+            "浮動小数点数リテラル{}が不正です。プログラムを終了します。",
+            text
+        )
+    });
+    (value, ty)
+}
+
+//整数リテラルを解釈し、値と型を返す
+//0xは16進数、0bは2進数、0で始まれば8進数とし、接尾辞はu, l, llとその組み合わせを受け付ける
+//10進数は接尾辞uが無ければlong longの範囲に収まらなければならない
+//型は接尾辞で決まる型のうち値が収まる最初の型とする(10進数は符号なしの型を候補にしない)
+pub fn parse_int_literal(text: &str) -> (i64, Type) {
+    let (digits, radix) =
+        if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+            (hex, 16)
+        } else if let Some(bin) = text.strip_prefix("0b").or_else(|| text.strip_prefix("0B")) {
+            (bin, 2)
+        } else if text.starts_with('0') {
+            (text, 8)
         } else {
-            ret_char = Some(c);
-            break;
+            (text, 10)
+        };
+    // 数字の後を接尾辞とする
+    let end = digits
+        .find(|c: char| !c.is_digit(radix))
+        .unwrap_or(digits.len());
+    let (digits, suffix) = digits.split_at(end);
+    assert!(
+        !suffix.starts_with(|c: char| c.is_ascii_digit()),
+        "整数リテラル{}に不正な数字があります。プログラムを終了します。",
+        text
+    );
+    let unsigned = match suffix {
+        "" | "l" | "L" | "ll" | "LL" => false,
+        "u" | "U" | "ul" | "uL" | "Ul" | "UL" | "lu" | "lU" | "Lu" | "LU" | "ull" | "uLL"
+        | "Ull" | "ULL" | "llu" | "llU" | "LLu" | "LLU" => true,
+        _ => panic!(
+            "整数リテラル{}の接尾辞が不正です。プログラムを終了します。",
+            text
+        ),
+    };
+    assert!(
+        !digits.is_empty(),
+        "整数リテラル{}に数字がありません。プログラムを終了します。",
+        text
+    );
+    let value = u64::from_str_radix(digits, radix).unwrap_or_else(|_| {
+        panic!(
+            "整数リテラル{}が大きすぎます。プログラムを終了します。",
+            text
+        )
+    });
+    // 10進数は符号付きの型にのみ収まる
+    assert!(
+        unsigned || radix != 10 || i64::try_from(value).is_ok(),
+        "整数リテラル{}が大きすぎます。プログラムを終了します。",
+        text
+    );
+    let long = suffix.contains(['l', 'L']);
+    let ty = if unsigned {
+        if !long && u32::try_from(value).is_ok() {
+            Type::UInt
+        } else {
+            Type::ULong
         }
-    }
-    (ret_char, c_vec)
+    } else if !long && i32::try_from(value).is_ok() {
+        Type::Int
+    } else if !long && radix != 10 && u32::try_from(value).is_ok() {
+        Type::UInt
+    } else if i64::try_from(value).is_ok() {
+        Type::Long
+    } else {
+        Type::ULong
+    };
+    #[allow(clippy::cast_possible_wrap)]
+    let value = value as i64;
+    (value, ty)
 }
 
-// キーワードか変数が判断して、トークンを生成して返す
-fn ident_token(
-    first_c: char,
-    c_iter: &mut str::Chars,
-    ident_list: &mut IdentList,
-) -> (Option<char>, Kind) {
-    let mut popped_char;
-    let c_vec;
-    // 変数でない識別子か否か
-    let is_ident;
-
-    match first_c {
-        'r' => {
-            (is_ident, (popped_char, c_vec)) = check_ident("return", c_iter);
-            if is_ident {
-                // returnキーワード
-                return (popped_char, Kind::Return);
+//閉じる引用符までを文字リテラルとして読み込み、その値を返す
+fn continue_char(c_iter: &mut str::Chars) -> i64 {
+    let mut body = String::new();
+    loop {
+        match c_iter.next() {
+            Some('\'') => break,
+            // エスケープされた文字はそのまま含める
+            Some('\\') => {
+                body.push('\\');
+                body.extend(c_iter.next());
             }
-        }
-        'i' => {
-            (is_ident, (popped_char, c_vec)) = check_ident("if", c_iter);
-            if is_ident {
-                // ifキーワード
-                return (popped_char, Kind::If(None));
+            Some('\n') | None => {
+                panic!("文字リテラルが閉じられていません。プログラムを終了します。")
             }
+            Some(c) => body.push(c),
         }
-        'e' => {
-            (is_ident, (popped_char, c_vec)) = check_ident("else", c_iter);
-            if is_ident {
-                // elseキーワード
-                return (popped_char, Kind::Else);
+    }
+    parse_char(&body)
+}
+
+//文字リテラルの引用符の中身を解釈してその値を返す
+//charは符号付きとして扱う
+pub fn parse_char(body: &str) -> i64 {
+    let mut chars = body.chars().peekable();
+    let c = chars
+        .next()
+        .unwrap_or_else(|| panic!("空の文字リテラルがあります。プログラムを終了します。"));
+    let value = if c == '\\' {
+        parse_escape(&mut chars)
+    } else {
+        assert!(
+            c.is_ascii(),
+            "文字リテラル'{}'は1バイトで表せません。プログラムを終了します。",
+            c
+        );
+        u32::from(c)
+    };
+    assert!(
+        chars.next().is_none(),
+        "文字リテラル'{}'に複数の文字があります。プログラムを終了します。",
+        body
+    );
+    let byte = u8::try_from(value).unwrap_or_else(|_| {
+        panic!(
+            "文字リテラル'{}'の値が1バイトに収まりません。プログラムを終了します。",
+            body
+        )
+    });
+    i64::from(i8::from_ne_bytes([byte]))
+}
+
+//\の後に続くエスケープシーケンスを読み込み、その値を返す
+fn parse_escape(chars: &mut Peekable<str::Chars>) -> u32 {
+    let c = chars
+        .next()
+        .unwrap_or_else(|| panic!("エスケープシーケンスが不正です。プログラムを終了します。"));
+    match c {
+        'a' => 0x07,
+        'b' => 0x08,
+        'f' => 0x0c,
+        'n' => 0x0a,
+        'r' => 0x0d,
+        't' => 0x09,
+        'v' => 0x0b,
+        '\\' | '\'' | '"' | '?' => u32::from(c),
+        // 8進数は3桁まで
+        '0'..='7' => {
+            let mut value = c.to_digit(8).unwrap();
+            for _ in 0..2 {
+                match chars.next_if(|c| c.is_digit(8)) {
+                    Some(c) => value = value * 8 + c.to_digit(8).unwrap(),
+                    None => break,
+                }
             }
+            value
         }
-        'w' => {
-            (is_ident, (popped_char, c_vec)) = check_ident("while", c_iter);
-            if is_ident {
-                // whileキーワード
-                return (popped_char, Kind::While(None));
+        // 16進数は続く限り読み込む
+        'x' => {
+            let mut value: u32 = 0;
+            let mut digits = 0;
+            while let Some(c) = chars.next_if(char::is_ascii_hexdigit) {
+                value = value
+                    .saturating_mul(16)
+                    .saturating_add(c.to_digit(16).unwrap());
+                digits += 1;
             }
+            assert!(
+                digits > 0,
+                "\\xの後に16進数がありません。プログラムを終了します。"
+            );
+            value
         }
-        'f' => {
-            (is_ident, (popped_char, c_vec)) = check_ident("for", c_iter);
-            if is_ident {
-                // whileキーワード
-                return (popped_char, Kind::For(None, None, None));
+        _ => panic!(
+            "不明なエスケープシーケンス\\{}があります。プログラムを終了します。",
+            c
+        ),
+    }
+}
+
+//閉じる引用符までを文字列リテラルとして読み込み、そのバイト列を返す
+fn continue_str(c_iter: &mut str::Chars) -> Vec<u8> {
+    let mut body = String::new();
+    loop {
+        match c_iter.next() {
+            Some('"') => break,
+            // エスケープされた文字はそのまま含める
+            Some('\\') => {
+                body.push('\\');
+                body.extend(c_iter.next());
             }
-        }
-        _ => {
-            // 通常の変数
-            (popped_char, c_vec) = continue_var(vec![first_c], c_iter);
+            Some('\n') | None => {
+                panic!("文字列リテラルが閉じられていません。プログラムを終了します。")
+            }
+            Some(c) => body.push(c),
         }
     }
-    // 変数の文字Vec<char>をStringに変換
-    let ident = c_vec.into_iter().collect();
+    parse_str(&body)
+}
 
-    // 変数名の後に"("があれば関数名
-    if let Some(c) = skip_nullity(popped_char, c_iter) {
-        if c == '(' {
-            // 関数名
-            return (Some(c), Kind::FunCall(ident, None));
+//文字列リテラルの引用符の中身を解釈してバイト列を返す
+//エスケープシーケンス以外の文字はUTF-8で表す
+fn parse_str(body: &str) -> Vec<u8> {
+    let mut chars = body.chars().peekable();
+    let mut bytes = Vec::new();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            let value = parse_escape(&mut chars);
+            bytes.push(u8::try_from(value).unwrap_or_else(|_| {
+                panic!(
+                    "文字列リテラルの\"{}\"に1バイトに収まらない値があります。プログラムを終了します。",
+                    body
+                )
+            }));
+        } else {
+            let mut buf = [0; 4];
+            bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
         }
-        // 変数名
-        popped_char = Some(c);
     }
-
-    (popped_char, Kind::Var(ident_list.find_ident_index(ident)))
+    bytes
 }
 
-// 空白や改行など区切りに使われる無視すべき文字を飛ばす
-fn skip_nullity(mut popped_char: Option<char>, c_iter: &mut str::Chars) -> Option<char> {
-    while let Some(c) = popped_char {
-        match c {
-            ' ' | '\n' => (),
-            _ => return Some(c),
+//変数を表す文字列をベクタ型に追加して返す
+fn continue_var(mut c_vec: Vec<char>, c_iter: &mut str::Chars) -> (Option<char>, Vec<char>) {
+    let mut ret_char: Option<char> = None;
+    //変数に使えない文字が出るまでループ
+    for c in c_iter.by_ref() {
+        if is_ident_char(c) || c.is_ascii_digit() {
+            //見つかった文字を追加
+            c_vec.push(c);
+        } else {
+            ret_char = Some(c);
+            break;
         }
-        popped_char = c_iter.next();
     }
-    None
+    (ret_char, c_vec)
+}
+
+// 識別子を最後まで読み込み、キーワードか識別子か判断して、トークンを生成して返す
+// 識別子が変数と関数のどちらを指すかは構文解析で決める
+fn ident_token(first_c: char, c_iter: &mut str::Chars) -> (Option<char>, Kind) {
+    let (popped_char, c_vec) = continue_var(vec![first_c], c_iter);
+    // 識別子の文字Vec<char>をStringに変換
+    let ident: String = c_vec.into_iter().collect();
+
+    // キーワード表にあればキーワード
+    if let Some((_, keyword)) = KEYWORDS.iter().find(|(name, _)| *name == ident) {
+        let token = match keyword {
+            Keyword::Return => Kind::Return,
+            Keyword::If => Kind::If(None),
+            Keyword::Else => Kind::Else,
+            Keyword::While => Kind::While(None),
+            Keyword::For => Kind::For(None, None, None),
+            _ => Kind::Keyword(*keyword),
+        };
+        return (popped_char, token);
+    }
+
+    (popped_char, Kind::Ident(ident))
 }
 
 //変数の最初に使える文字なら真を返す
 fn is_ident_char(c: char) -> bool {
     c.is_ascii_alphabetic() || c == '_'
 }
-//識別子であれば真を返す
-//異なっていたらイテレータで消費した文字をVecにまとめて返す
-// 一文字目は変数に使える文字とする
-fn check_ident(compare_str: &str, c_iter: &mut str::Chars) -> (bool, (Option<char>, Vec<char>)) {
-    //比較対象の文字列の1文字目
-    let mut c_vec = vec![compare_str.chars().next().unwrap()];
-    //イテレータで取り出して未処理の一文字
-    let mut popped_char = None;
-    //比較中の文字の位置(2文字目からスタート)
-    let mut cnt = 1;
-    //イテレータから1文字取り出す
-    while let Some(c) = c_iter.next() {
-        popped_char = Some(c);
-        //比較文字列から1文字取り出す
-        if let Some(compare_char) = compare_str.chars().nth(cnt) {
-            if compare_char == c {
-                c_vec.push(c);
-                cnt += 1;
-            } else {
-                //通常の変数
-                if is_ident_char(c) || c.is_numeric() {
-                    c_vec.push(c);
-                    return (false, continue_var(c_vec, c_iter));
-                }
-                return (false, (popped_char, c_vec));
-            }
-        } else {
-            // 比較文字列が終了した場合
-            if is_ident_char(c) || c.is_numeric() {
-                //識別子の文字列の後に変数に使える文字が続いていた場合は通常の変数とみなす
-                c_vec.push(c);
-                return (false, continue_var(c_vec, c_iter));
-            }
-            //識別子の文字列の後に変数以外の文字があれば識別子であると判定する
-            //この場合、返り値の文字のベクタは利用されない(比較文字列と同じなので)
-            return (true, (Some(c), c_vec));
-        }
-    }
-    //比較文字列の途中で先にイテレータが無くなった場合
-    (false, (popped_char, c_vec))
-}
@@ -0,0 +1,359 @@
+use std::cell::RefCell;
+use std::mem;
+use std::rc::Rc;
+
+use crate::codegen::align_to;
+use crate::kind::Kind;
+use crate::kind::Node;
+
+//型
+#[derive(Clone)]
+pub enum Type {
+    Char,
+    Short,
+    Int,
+    Long,
+    UChar,
+    UShort,
+    UInt,
+    ULong,
+    Float,
+    Double,
+    //ポインタ(指す先の型)
+    Ptr(Rc<Type>),
+    //配列(要素の型, 要素数)
+    //要素数を省略した配列はNoneとし、初期化子から要素数を決めるまでは不完全型とする
+    Array(Rc<Type>, Option<usize>),
+    Struct(Rc<Struct>),
+}
+
+//構造体と共用体の型
+//同じタグの構造体は同じ実体を共有する
+pub struct Struct {
+    //共用体かどうか
+    pub is_union: bool,
+    //メンバの配置(宣言のみで定義されていない不完全型ではNone)
+    pub layout: RefCell<Option<Layout>>,
+}
+
+//構造体のメンバの配置と大きさ
+pub struct Layout {
+    pub members: Vec<Member>,
+    pub size: usize,
+    pub align: usize,
+}
+
+//関数の型
+pub struct FuncType {
+    //戻り値の型
+    pub ret: Type,
+    //仮引数の型(順番に並べる)
+    pub params: Vec<Type>,
+    //可変長引数(...)をとるか
+    pub is_variadic: bool,
+    //仮引数の型を指定して宣言されたか(int f();のような宣言では偽)
+    pub is_prototyped: bool,
+}
+
+//構造体のメンバ
+#[derive(Clone)]
+pub struct Member {
+    pub name: String,
+    pub ty: Type,
+    //構造体の先頭からのオフセット
+    pub offset: usize,
+}
+
+impl Type {
+    //型の大きさ(バイト数)
+    pub fn size(&self) -> usize {
+        match self {
+            Type::Char | Type::UChar => 1,
+            Type::Short | Type::UShort => 2,
+            Type::Int | Type::UInt | Type::Float => 4,
+            Type::Long | Type::ULong | Type::Double | Type::Ptr(_) => 8,
+            Type::Array(base, len) => {
+                let len = len.unwrap_or_else(|| {
+                    panic!("要素数が不明な配列型は使えません。プログラムを終了します。")
+                });
+                base.size() * len
+            }
+            Type::Struct(st) => st.layout().size,
+        }
+    }
+
+    //型のアラインメント
+    pub fn align(&self) -> usize {
+        match self {
+            Type::Struct(st) => st.layout().align,
+            Type::Array(base, _) => base.align(),
+            _ => self.size(),
+        }
+    }
+
+    //ポインタであれば指す先の型を返す
+    pub fn base(&self) -> Option<&Type> {
+        match self {
+            Type::Ptr(base) => Some(base),
+            _ => None,
+        }
+    }
+
+    pub fn is_integer(&self) -> bool {
+        matches!(
+            self,
+            Type::Char
+                | Type::Short
+                | Type::Int
+                | Type::Long
+                | Type::UChar
+                | Type::UShort
+                | Type::UInt
+                | Type::ULong
+        )
+    }
+
+    pub fn is_flonum(&self) -> bool {
+        matches!(self, Type::Float | Type::Double)
+    }
+
+    //算術演算ができる型かどうか
+    pub fn is_numeric(&self) -> bool {
+        self.is_integer() || self.is_flonum()
+    }
+
+    //符号なしとして扱う型かどうか(ポインタの比較も符号なしで行う)
+    pub fn is_unsigned(&self) -> bool {
+        matches!(
+            self,
+            Type::UChar | Type::UShort | Type::UInt | Type::ULong | Type::Ptr(_)
+        )
+    }
+
+    pub fn is_struct(&self) -> bool {
+        matches!(self, Type::Struct(_))
+    }
+
+    //代入できる同じ型かどうか
+    pub fn is_same(&self, other: &Type) -> bool {
+        match (self, other) {
+            (Type::Ptr(a), Type::Ptr(b)) => a.is_same(b),
+            (Type::Array(a, a_len), Type::Array(b, b_len)) => a_len == b_len && a.is_same(b),
+            (Type::Struct(a), Type::Struct(b)) => Rc::ptr_eq(a, b),
+            _ => mem::discriminant(self) == mem::discriminant(other),
+        }
+    }
+}
+
+impl FuncType {
+    //同じ関数の型かどうか(同じ関数を重ねて宣言するときに使う)
+    //プロトタイプでない宣言とは戻り値の型だけを比べる
+    pub fn is_same(&self, other: &FuncType) -> bool {
+        if !self.is_prototyped || !other.is_prototyped {
+            return self.ret.is_same(&other.ret);
+        }
+        self.ret.is_same(&other.ret)
+            && self.is_variadic == other.is_variadic
+            && self.params.len() == other.params.len()
+            && self
+                .params
+                .iter()
+                .zip(&other.params)
+                .all(|(a, b)| a.is_same(b))
+    }
+}
+
+impl Struct {
+    //不完全型の構造体または共用体
+    pub fn new(is_union: bool) -> Self {
+        Struct {
+            is_union,
+            layout: RefCell::new(None),
+        }
+    }
+
+    //メンバを宣言順に配置し、構造体を完全型にする
+    //各メンバはその型のアラインメントに揃え、全体の大きさは最大のアラインメントの倍数にする
+    //共用体ではすべてのメンバを先頭に置き、大きさは最大のメンバに合わせる
+    pub fn define(&self, members: Vec<(String, Type)>) {
+        let mut offset = 0;
+        let mut size = 0;
+        let mut align = 1;
+        let mut placed: Vec<Member> = Vec::new();
+        for (name, ty) in members {
+            assert!(
+                placed.iter().all(|member| member.name != name),
+                "メンバ{}が二重に宣言されています。プログラムを終了します。",
+                name
+            );
+            align = align.max(ty.align());
+            if self.is_union {
+                size = size.max(ty.size());
+                placed.push(Member {
+                    name,
+                    ty,
+                    offset: 0,
+                });
+            } else {
+                offset = align_to(offset, ty.align());
+                size = offset + ty.size();
+                placed.push(Member { name, ty, offset });
+                offset = size;
+            }
+        }
+        *self.layout.borrow_mut() = Some(Layout {
+            members: placed,
+            size: align_to(size, align),
+            align,
+        });
+    }
+
+    //宣言順に並べたメンバ
+    pub fn members(&self) -> Vec<Member> {
+        self.layout().members.clone()
+    }
+
+    //名前からメンバを探す
+    pub fn member(&self, name: &str) -> Member {
+        self.layout()
+            .members
+            .iter()
+            .find(|member| member.name == name)
+            .cloned()
+            .unwrap_or_else(|| {
+                panic!(
+                    "構造体にメンバ{}がありません。プログラムを終了します。",
+                    name
+                )
+            })
+    }
+
+    fn layout(&self) -> std::cell::Ref<'_, Layout> {
+        std::cell::Ref::map(self.layout.borrow(), |layout| {
+            layout
+                .as_ref()
+                .unwrap_or_else(|| panic!("不完全な構造体型は使えません。プログラムを終了します。"))
+        })
+    }
+}
+
+//通常の算術変換で揃える型
+//浮動小数点数があればdouble、floatの順に優先する
+//整数はintより小さければintに格上げし、大きさが異なれば大きい方、同じであれば符号なしの方に揃える
+fn common_type(lhs: &Type, rhs: &Type) -> Type {
+    if matches!(lhs, Type::Double) || matches!(rhs, Type::Double) {
+        return Type::Double;
+    }
+    if matches!(lhs, Type::Float) || matches!(rhs, Type::Float) {
+        return Type::Float;
+    }
+    let promote = |ty: &Type| if ty.size() < 4 { Type::Int } else { ty.clone() };
+    let (lhs, rhs) = (promote(lhs), promote(rhs));
+    if lhs.size() != rhs.size() {
+        return if lhs.size() < rhs.size() { rhs } else { lhs };
+    }
+    if rhs.is_unsigned() {
+        rhs
+    } else {
+        lhs
+    }
+}
+
+//型変換のノードを作る
+pub fn new_cast(mut expr: Node, ty: Type) -> Node {
+    add_type(&mut expr);
+    Node {
+        kind: Kind::Cast,
+        lhs: Some(Box::new(expr)),
+        rhs: None,
+        ty: Some(ty),
+    }
+}
+
+//数値同士の二項演算の両辺を通常の算術変換で同じ型にし、その型を返す
+//ポインタを含む場合は変換せずポインタの型を返す
+fn usual_arith_conv(node: &mut Node) -> Type {
+    let lhs = node.lhs.take().unwrap();
+    let rhs = node.rhs.take().unwrap();
+    let lhs_ty = lhs.ty.clone().unwrap();
+    let rhs_ty = rhs.ty.clone().unwrap();
+    if !(lhs_ty.is_numeric() && rhs_ty.is_numeric()) {
+        node.lhs = Some(lhs);
+        node.rhs = Some(rhs);
+        return if rhs_ty.base().is_some() {
+            rhs_ty
+        } else {
+            lhs_ty
+        };
+    }
+    let ty = common_type(&lhs_ty, &rhs_ty);
+    node.lhs = Some(Box::new(new_cast(*lhs, ty.clone())));
+    node.rhs = Some(Box::new(new_cast(*rhs, ty.clone())));
+    ty
+}
+
+//System V ABIのva_listと同じ配置の構造体の型
+//gp_offsetとfp_offsetはレジスタ保存領域で次に読む整数と浮動小数点数の引数の位置、
+//overflow_arg_areaはスタックで渡された次の引数のアドレス、reg_save_areaはレジスタ保存領域のアドレス
+//AArch64とRISC-Vでも同じ配置を使うので、それらのABIのva_listとは互換性がない
+pub fn new_va_list() -> Type {
+    let st = Struct::new(false);
+    let ptr = Type::Ptr(Rc::new(Type::Char));
+    st.define(vec![
+        ("gp_offset".to_string(), Type::UInt),
+        ("fp_offset".to_string(), Type::UInt),
+        ("overflow_arg_area".to_string(), ptr.clone()),
+        ("reg_save_area".to_string(), ptr),
+    ]);
+    Type::Struct(Rc::new(st))
+}
+
+//式のノードとその子孫に型を付ける
+//型が付いているノードはそのままにする(関数呼び出しは構文解析で型を付ける)
+pub fn add_type(node: &mut Node) {
+    if node.ty.is_some() {
+        return;
+    }
+    for child in node.lhs.iter_mut().chain(node.rhs.iter_mut()) {
+        add_type(child);
+    }
+    match &mut node.kind {
+        Kind::If(Some(cond)) | Kind::While(Some(cond)) => add_type(cond),
+        Kind::For(init, cond, inc) => {
+            for child in init.iter_mut().chain(cond.iter_mut()).chain(inc.iter_mut()) {
+                add_type(child);
+            }
+        }
+        _ => (),
+    }
+    node.ty = match &node.kind {
+        Kind::Num(_, ty) | Kind::FNum(_, ty) => Some(ty.clone()),
+        // 比較の両辺は同じ型にそろえ、結果はint
+        Kind::Equal | Kind::NoEqual | Kind::LowThan | Kind::LowEqual => {
+            usual_arith_conv(node);
+            Some(Type::Int)
+        }
+        Kind::Var(obj) => Some(obj.ty.clone()),
+        Kind::Member(member) => Some(member.ty.clone()),
+        // 算術演算の結果は両辺をそろえた型とする(ポインタの演算は構文解析で組み立て済み)
+        Kind::Add | Kind::Sub | Kind::Mul | Kind::Div => Some(usual_arith_conv(node)),
+        // 代入式の値は左辺の型に変換した右辺の値
+        Kind::Assign => {
+            let ty = node.lhs.as_ref().unwrap().ty.clone().unwrap();
+            if !ty.is_struct() {
+                let rhs = node.rhs.take().unwrap();
+                node.rhs = Some(Box::new(new_cast(*rhs, ty.clone())));
+            }
+            Some(ty)
+        }
+        Kind::Addr => Some(Type::Ptr(Rc::new(
+            node.lhs.as_ref().unwrap().ty.clone().unwrap(),
+        ))),
+        Kind::Deref => match node.lhs.as_ref().unwrap().ty.as_ref().unwrap() {
+            Type::Ptr(base) => Some((**base).clone()),
+            _ => panic!("ポインタ以外の値を参照しています。プログラムを終了します。"),
+        },
+        // 文には型が無い
+        _ => None,
+    };
+}
@@ -0,0 +1,735 @@
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::fmt::Write;
+
+use crate::codegen::align_to;
+use crate::kind::Case;
+use crate::kind::Global;
+use crate::kind::Kind;
+use crate::kind::Node;
+use crate::kind::Obj;
+use crate::kind::Program;
+use crate::types::{Member, Type};
+
+//スタックの先頭のアドレス(線形メモリ1ページの末尾)
+//グローバル変数はメモリの先頭から、関数のフレームは末尾から下に向かって置く
+const STACK_TOP: usize = 65536;
+
+//WebAssemblyテキスト形式(.wat)向けのコード生成
+//WebAssemblyもスタックマシンなので、構文木をそのまま命令列に変換できる
+//ただし制御構造はラベルへのジャンプではなくblock/loop/ifで表す
+struct Wasm {
+    //関数本体の命令列
+    body: Vec<String>,
+    //ブロックの入れ子の深さ(インデント用)
+    nest: usize,
+    //グローバル変数のアドレス
+    globals: HashMap<String, usize>,
+    //呼び出された外部関数と引数の数
+    imports: BTreeMap<String, usize>,
+    //定義された関数と仮引数の数、可変長引数をとるか
+    functions: HashMap<String, (usize, bool)>,
+    //生成中の関数がmainかどうか(mainの戻り値のみi32にする)
+    is_main: bool,
+    //ラベルに一意に付与する番号
+    labelseq: usize,
+    //breakで抜ける最も内側のループかswitch文のラベル番号
+    brk: Option<usize>,
+}
+
+//構文木からWebAssemblyテキスト形式のモジュールを生成
+//すべての値はi64で扱い、mainの戻り値のみi32にする
+//変数は線形メモリに置き、アドレスはi32で扱う(ポインタの値としてはi64に拡張する)
+pub fn wasm(program: Program) -> String {
+    let mut wasm = Wasm {
+        body: Vec::new(),
+        nest: 3,
+        globals: HashMap::new(),
+        imports: BTreeMap::new(),
+        functions: program
+            .functions
+            .iter()
+            .map(|function| {
+                let is_variadic = function.va_area.is_some();
+                (function.name.clone(), (function.params.len(), is_variadic))
+            })
+            .collect(),
+        is_main: false,
+        labelseq: 0,
+        brk: None,
+    };
+
+    let mut data = wasm.gen_data(&program.globals);
+
+    let mut funcs = Vec::new();
+    for function in program.functions {
+        wasm.is_main = function.name == "main";
+        for node in function.body {
+            //文単位で生成
+            wasm.gen_stmt(node);
+        }
+
+        // 仮引数はWebAssemblyの引数として受け取り、フレームに書き込む
+        let mut params_text = String::new();
+        for index in 0..function.params.len() {
+            write!(params_text, " (param $p{index} i64)").unwrap();
+        }
+        // 可変長引数は呼び出し元がメモリに並べ、その先頭のアドレスを最後の引数で渡す
+        if function.va_area.is_some() {
+            params_text.push_str(" (param $va i64)");
+        }
+        if wasm.is_main {
+            funcs.push(format!(
+                "  (func $main (export \"main\"){params_text} (result i32)"
+            ));
+        } else {
+            funcs.push(format!(
+                "  (func ${}{params_text} (result i64)",
+                function.name
+            ));
+        }
+        // フレームの先頭、戻り値、代入で一時的に使うローカル変数
+        funcs.push("    (local $fp i32) (local $ret i64)".to_string());
+        funcs.push("    (local $tmp i64) (local $addr i32) (local $src i32)".to_string());
+        // プロローグ
+        // ローカル変数の領域を確保する
+        let stack_size = align_to(function.stack_size, 16);
+        funcs.push("    global.get $sp".to_string());
+        funcs.push("    local.tee $fp".to_string());
+        funcs.push(format!("    i32.const {stack_size}"));
+        funcs.push("    i32.sub".to_string());
+        funcs.push("    global.set $sp".to_string());
+        for (index, param) in function.params.iter().enumerate() {
+            funcs.push("    local.get $fp".to_string());
+            funcs.push(format!("    i32.const {}", param.offset.unwrap()));
+            funcs.push("    i32.sub".to_string());
+            funcs.push(format!("    local.get $p{index}"));
+            funcs.push(format!("    {}", store_inst(&param.ty)));
+        }
+        // returnはこのブロックを抜けてエピローグへ進む
+        funcs.push("    block $Lreturn".to_string());
+        funcs.append(&mut wasm.body);
+        funcs.push("    end".to_string());
+        // エピローグ
+        // 最後の式文の結果かreturnの値が返り値になる
+        funcs.push("    local.get $fp".to_string());
+        funcs.push("    global.set $sp".to_string());
+        funcs.push("    local.get $ret".to_string());
+        if wasm.is_main {
+            funcs.push("    i32.wrap_i64".to_string());
+        }
+        funcs.push("  )".to_string());
+    }
+
+    let mut module = vec!["(module".to_string()];
+    // 外部関数はenvモジュールからインポートする
+    for (name, args_num) in &wasm.imports {
+        let params = if *args_num == 0 {
+            String::new()
+        } else {
+            format!(" (param{})", " i64".repeat(*args_num))
+        };
+        module.push(format!(
+            "  (import \"env\" \"{name}\" (func ${name}{params} (result i64)))"
+        ));
+    }
+    module.push("  (memory 1)".to_string());
+    module.push(format!("  (global $sp (mut i32) (i32.const {STACK_TOP}))"));
+    module.append(&mut data);
+    module.append(&mut funcs);
+    module.push(")".to_string());
+    module.join("\n") + "\n"
+}
+
+impl Wasm {
+    //グローバル変数のアドレスを決め、初期値のデータセグメントを生成する
+    //アドレス0はヌルポインタとして使わない
+    //初期値に他のグローバル変数のアドレスを書き込むので、先にすべてのアドレスを決める
+    fn gen_data(&mut self, globals: &[Global]) -> Vec<String> {
+        let mut addr = 8;
+        for global in globals {
+            addr = align_to(addr, global.obj.ty.align());
+            self.globals.insert(global.obj.name.clone(), addr);
+            addr += global.init.len();
+        }
+        assert!(
+            addr <= STACK_TOP / 2,
+            "グローバル変数が大きすぎます。プログラムを終了します。"
+        );
+        let mut data = Vec::new();
+        for global in globals {
+            let mut init = global.init.clone();
+            for reloc in &global.relocs {
+                let value = i64::try_from(self.global_addr(&reloc.label)).unwrap() + reloc.addend;
+                init[reloc.offset..reloc.offset + 8].copy_from_slice(&value.to_le_bytes());
+            }
+            let mut bytes = String::new();
+            for byte in &init {
+                write!(bytes, "\\{byte:02x}").unwrap();
+            }
+            let addr = self.globals[&global.obj.name];
+            data.push(format!("  (data (i32.const {addr}) \"{bytes}\")"));
+        }
+        data
+    }
+
+    //グローバル変数のアドレス
+    //externで宣言しただけの変数はほかのモジュールと結合しないので使えない
+    fn global_addr(&self, name: &str) -> usize {
+        *self.globals.get(name).unwrap_or_else(|| {
+            panic!(
+                "グローバル変数{}が定義されていません。プログラムを終了します。",
+                name
+            )
+        })
+    }
+
+    fn emit(&mut self, inst: &str) {
+        self.body
+            .push(format!("{}{}", "  ".repeat(self.nest), inst));
+    }
+
+    // 文の処理
+    // 文の実行前後でスタックの深さは変わらない
+    fn gen_stmt(&mut self, node: Node) {
+        match node.kind {
+            // {}の中
+            Kind::CurlyBracOpen => {
+                if node.lhs.is_none() {
+                    return;
+                }
+                self.gen_stmt(*node.lhs.unwrap());
+                self.gen_stmt(*node.rhs.unwrap());
+            }
+            Kind::Return => {
+                self.gen(*node.lhs.unwrap());
+                self.emit("local.set $ret");
+                self.emit("br $Lreturn");
+            }
+            Kind::If(node_cond) => {
+                // 条件式
+                self.gen_cond(*node_cond.unwrap());
+                self.emit("if");
+                self.nest += 1;
+                // then式
+                self.gen_stmt(*node.lhs.unwrap());
+                self.nest -= 1;
+                if let Some(node_else) = node.rhs {
+                    self.emit("else");
+                    self.nest += 1;
+                    // else式
+                    self.gen_stmt(*node_else);
+                    self.nest -= 1;
+                }
+                self.emit("end");
+            }
+            Kind::While(node_cond) => {
+                let seq = self.labelseq;
+                self.labelseq += 1;
+                // blockの末尾へのbrでループを抜け、loopの先頭へのbrで繰り返す
+                self.emit(&format!("block $Lend{seq}"));
+                self.nest += 1;
+                self.emit(&format!("loop $Lbegin{seq}"));
+                self.nest += 1;
+                // 条件式
+                self.gen_cond(*node_cond.unwrap());
+                self.emit("i32.eqz");
+                self.emit(&format!("br_if $Lend{seq}"));
+                // then式
+                self.gen_body(*node.lhs.unwrap(), seq);
+                self.emit(&format!("br $Lbegin{seq}"));
+                self.nest -= 1;
+                self.emit("end");
+                self.nest -= 1;
+                self.emit("end");
+            }
+            Kind::For(node_init, node_cond, node_inc) => {
+                let seq = self.labelseq;
+                self.labelseq += 1;
+                if let Some(node_init) = node_init {
+                    // 存在すれば初期化処理(変数の宣言の場合もある)
+                    self.gen_stmt(*node_init);
+                }
+                self.emit(&format!("block $Lend{seq}"));
+                self.nest += 1;
+                self.emit(&format!("loop $Lbegin{seq}"));
+                self.nest += 1;
+                if let Some(node_cond) = node_cond {
+                    // 存在すれば条件式
+                    self.gen_cond(*node_cond);
+                    self.emit("i32.eqz");
+                    self.emit(&format!("br_if $Lend{seq}"));
+                }
+                // 条件式が真の場合のthen式
+                self.gen_body(*node.lhs.unwrap(), seq);
+                if let Some(node_inc) = node_inc {
+                    // 存在すれば変化式
+                    self.gen(*node_inc);
+                    self.emit("drop");
+                }
+                self.emit(&format!("br $Lbegin{seq}"));
+                self.nest -= 1;
+                self.emit("end");
+                self.nest -= 1;
+                self.emit("end");
+            }
+            Kind::Switch(node_cond, tmp, cases) => {
+                self.gen_switch(*node_cond.unwrap(), &tmp, cases);
+            }
+            Kind::Break => self.emit(&format!("br $Lend{}", self.brk.unwrap())),
+            // 式文
+            _ => {
+                self.gen(node);
+                // 最後に評価した式文の値を戻り値として保持する
+                self.emit("local.set $ret");
+            }
+        }
+    }
+
+    // ループかswitch文の本体の文の処理
+    // 本体の中のbreakは$Lend{seq}のblockの末尾へbrする
+    fn gen_body(&mut self, node: Node, seq: usize) {
+        let brk = self.brk.replace(seq);
+        self.gen_stmt(node);
+        self.brk = brk;
+    }
+
+    // switch文の処理
+    fn gen_switch(&mut self, node_cond: Node, tmp: &Obj, cases: Vec<Case>) {
+        let seq = self.labelseq;
+        self.labelseq += 1;
+        // 条件式の値を一時的な変数に置く
+        self.push_var_address(tmp);
+        self.gen(node_cond);
+        self.emit(store_inst(&tmp.ty));
+        // 各caseの文の直前で終わるblockを入れ子にし、
+        // 一番内側で値が一致するcaseのblockの末尾へbrする
+        self.emit(&format!("block $Lend{seq}"));
+        self.nest += 1;
+        for index in (0..cases.len()).rev() {
+            self.emit(&format!("block $Lcase{seq}_{index}"));
+            self.nest += 1;
+        }
+        let mut default = format!("$Lend{seq}");
+        for (index, case) in cases.iter().enumerate() {
+            for label in &case.labels {
+                match label {
+                    Some(value) => {
+                        self.push_var_address(tmp);
+                        self.emit(load_inst(&tmp.ty));
+                        self.emit(&format!("i64.const {value}"));
+                        self.emit("i64.eq");
+                        self.emit(&format!("br_if $Lcase{seq}_{index}"));
+                    }
+                    None => default = format!("$Lcase{seq}_{index}"),
+                }
+            }
+        }
+        self.emit(&format!("br {default}"));
+        // 各caseの文は次のcaseに続けて実行し、breakで外側のblockの末尾へbrする
+        for case in cases {
+            self.nest -= 1;
+            self.emit("end");
+            for node in case.body {
+                self.gen_body(node, seq);
+            }
+        }
+        self.nest -= 1;
+        self.emit("end");
+    }
+
+    // 条件式の処理
+    // 評価結果が0でなければ1、0であれば0をi32でプッシュする
+    fn gen_cond(&mut self, node: Node) {
+        let ty = node.ty.clone().unwrap();
+        self.gen(node);
+        // 浮動小数点数は0と比較する(-0.0も偽になる)
+        if ty.is_flonum() {
+            self.bits_to_float(&ty);
+            self.emit(&format!("{}.const 0", float_type(&ty)));
+            self.emit(&format!("{}.ne", float_type(&ty)));
+        } else {
+            self.emit("i64.const 0");
+            self.emit("i64.ne");
+        }
+    }
+
+    // 式の処理
+    // 評価結果のi64の値を一つスタックにプッシュする
+    fn gen(&mut self, node: Node) {
+        match node.kind {
+            Kind::Num(value, _) => self.emit(&format!("i64.const {value}")),
+            // ビット列をi64としてプッシュする
+            Kind::FNum(value, ty) => {
+                #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+                let bits = match ty {
+                    Type::Float => i64::from((value as f32).to_bits()),
+                    _ => value.to_bits() as i64,
+                };
+                self.emit(&format!("i64.const {bits}"));
+            }
+            Kind::FunCall(func_name, args, _) => {
+                self.gen_funcall(&func_name, args, &node.ty.unwrap());
+            }
+            Kind::Var(_) | Kind::Deref | Kind::Member(_) => {
+                let ty = node.ty.clone().unwrap();
+                //変数のアドレスをスタックにプッシュする
+                self.gen_addr(node);
+                // 構造体は値を読み込まず、アドレスをそのまま値として扱う
+                if ty.is_struct() {
+                    self.emit("i64.extend_i32_u");
+                } else {
+                    self.emit(load_inst(&ty));
+                }
+            }
+            Kind::Addr => {
+                self.gen_addr(*node.lhs.unwrap());
+                self.emit("i64.extend_i32_u");
+            }
+            Kind::Cast => {
+                let from = node.lhs.as_ref().unwrap().ty.clone().unwrap();
+                let to = node.ty.unwrap();
+                self.gen(*node.lhs.unwrap());
+                if !from.is_same(&to) {
+                    self.cast(&from, &to);
+                }
+            }
+            Kind::VaStart(tmp, ..) => self.gen_va_start(*node.lhs.unwrap(), &tmp),
+            Kind::VaArg(tmp) => self.gen_va_arg(*node.lhs.unwrap(), &tmp, &node.ty.unwrap()),
+            Kind::Assign => {
+                let ty = node.ty.unwrap();
+                //左辺のアドレスをスタックにプッシュする
+                self.gen_addr(*node.lhs.unwrap());
+                //右辺の値を計算
+                self.gen(*node.rhs.unwrap());
+                //代入して、代入した値をスタックに残す
+                self.store(&ty);
+            }
+            Kind::CurlyBracOpen
+            | Kind::Return
+            | Kind::If(_)
+            | Kind::While(_)
+            | Kind::For(..)
+            | Kind::Switch(..)
+            | Kind::Break => {
+                panic!("式があるべき箇所に文があります。プログラムを終了します。")
+            }
+            kind => {
+                //ノードが演算子だった場合
+                let ty = node.lhs.as_ref().unwrap().ty.clone().unwrap();
+                if ty.is_flonum() {
+                    self.gen_float_binary(&kind, *node.lhs.unwrap(), *node.rhs.unwrap(), &ty);
+                    return;
+                }
+                self.gen(*node.lhs.unwrap());
+                self.gen(*node.rhs.unwrap());
+                // 符号なしの除算と比較は命令の末尾が_uになる
+                let sign = if ty.is_unsigned() { "u" } else { "s" };
+                match kind {
+                    Kind::Add => self.emit("i64.add"),
+                    Kind::Sub => self.emit("i64.sub"),
+                    Kind::Mul => self.emit("i64.mul"),
+                    Kind::Div => self.emit(&format!("i64.div_{sign}")),
+                    Kind::Equal => self.compare("i64.eq"),
+                    Kind::NoEqual => self.compare("i64.ne"),
+                    Kind::LowThan => self.compare(&format!("i64.lt_{sign}")),
+                    Kind::LowEqual => self.compare(&format!("i64.le_{sign}")),
+                    _ => panic!("不正なノードがあります。プログラムを終了します。"),
+                }
+                // 算術演算は8バイトで行うので、8バイト未満の型では結果を型の大きさに収める
+                if matches!(kind, Kind::Add | Kind::Sub | Kind::Mul | Kind::Div) && ty.is_integer()
+                {
+                    self.cast(&Type::Long, &ty);
+                }
+            }
+        }
+    }
+
+    //値を書き込める式のアドレス(i32)をスタックにプッシュする
+    fn gen_addr(&mut self, node: Node) {
+        match node.kind {
+            Kind::Var(obj) => self.push_var_address(&obj),
+            // 参照先のアドレスはポインタの値そのもの
+            Kind::Deref => {
+                self.gen(*node.lhs.unwrap());
+                self.emit("i32.wrap_i64");
+            }
+            // 構造体のアドレスにメンバのオフセットを足す
+            Kind::Member(member) => {
+                self.gen_addr(*node.lhs.unwrap());
+                self.emit(&format!("i32.const {}", member.offset));
+                self.emit("i32.add");
+            }
+            _ => panic!("式の左辺に変数以外があります。プログラムを終了します。"),
+        }
+    }
+
+    //指定された変数のアドレスをスタックにプッシュする
+    fn push_var_address(&mut self, obj: &Obj) {
+        if let Some(offset) = obj.offset {
+            self.emit("local.get $fp");
+            self.emit(&format!("i32.const {offset}"));
+            self.emit("i32.sub");
+        } else {
+            let addr = self.global_addr(&obj.name);
+            self.emit(&format!("i32.const {addr}"));
+        }
+    }
+
+    //アドレスと値をポップし、アドレスにty型の値を書き込んで、値をプッシュし直す
+    //構造体は値として入力元のアドレスをポップし、メンバをすべてコピーする
+    //右辺を評価し終えてから一時変数を使うので、代入が入れ子になっても壊れない
+    fn store(&mut self, ty: &Type) {
+        if ty.is_struct() {
+            self.emit("i32.wrap_i64");
+            self.emit("local.set $src");
+            self.emit("local.tee $addr");
+            self.emit("local.get $src");
+            self.emit(&format!("i32.const {}", ty.size()));
+            self.emit("memory.copy");
+            self.emit("local.get $addr");
+            self.emit("i64.extend_i32_u");
+        } else {
+            self.emit("local.tee $tmp");
+            self.emit(store_inst(ty));
+            self.emit("local.get $tmp");
+        }
+    }
+
+    //関数呼び出しの処理
+    fn gen_funcall(&mut self, func_name: &str, args: Option<Vec<Node>>, ty: &Type) {
+        let mut args = args.unwrap_or_default();
+        let args_num = args.len();
+        let mut va_args = None;
+        if let Some(&(num, is_variadic)) = self.functions.get(func_name) {
+            // 定義された関数は仮引数と同じ数の引数で呼び出さなければならない
+            assert!(
+                num == args_num || (is_variadic && num < args_num),
+                "関数{}の引数は{}個です。プログラムを終了します。",
+                func_name,
+                num
+            );
+            if is_variadic {
+                va_args = Some(args.split_off(num));
+            }
+        } else if let Some(num) = self.imports.insert(func_name.to_string(), args_num) {
+            // 外部関数は同じ引数の数で呼び出さなければならない
+            assert!(
+                num == args_num,
+                "関数{}の引数の数が呼び出しごとに異なります。プログラムを終了します。",
+                func_name
+            );
+        }
+        for arg in args {
+            self.gen(arg);
+        }
+        match va_args {
+            Some(va_args) => self.gen_va_args(va_args, func_name),
+            None => self.emit(&format!("call ${func_name}")),
+        }
+        // 外部関数の戻り値の上位ビットは不定なので戻り値の型に揃える
+        if ty.is_integer() && ty.size() < 8 {
+            self.cast(&Type::Long, ty);
+        }
+    }
+
+    //va_startの処理
+    //可変長引数はすべてメモリに並べてあるので、overflow_arg_areaから順に読む
+    //レジスタ保存領域は使わない
+    fn gen_va_start(&mut self, lhs: Node, tmp: &Obj) {
+        self.gen(lhs);
+        self.emit("i32.wrap_i64");
+        self.emit("local.set $addr");
+        for (name, value) in [
+            ("gp_offset", "i64.const 0"),
+            ("fp_offset", "i64.const 0"),
+            ("overflow_arg_area", "local.get $va"),
+            ("reg_save_area", "i64.const 0"),
+        ] {
+            let member = va_member(tmp, name);
+            self.emit("local.get $addr");
+            self.emit(&format!("i32.const {}", member.offset));
+            self.emit("i32.add");
+            self.emit(value);
+            self.emit(store_inst(&member.ty));
+        }
+        self.emit("i64.const 0");
+    }
+
+    //va_argの処理
+    fn gen_va_arg(&mut self, lhs: Node, tmp: &Obj, ty: &Type) {
+        self.gen(lhs);
+        self.emit("i32.wrap_i64");
+        self.emit(&format!(
+            "i32.const {}",
+            va_member(tmp, "overflow_arg_area").offset
+        ));
+        self.emit("i32.add");
+        // 次の引数のアドレスを取り出し、overflow_arg_areaを8バイト進める
+        self.emit("local.tee $addr");
+        self.emit("i64.load");
+        self.emit("local.set $tmp");
+        self.emit("local.get $addr");
+        self.emit("local.get $tmp");
+        self.emit("i64.const 8");
+        self.emit("i64.add");
+        self.emit("i64.store");
+        self.emit("local.get $tmp");
+        self.emit("i32.wrap_i64");
+        self.emit(load_inst(ty));
+    }
+
+    //可変長引数を8バイトずつスタックの領域に並べ、その先頭のアドレスを渡して関数を呼び出す
+    //名前付きの引数は呼び出し前にスタックに積んでおく
+    fn gen_va_args(&mut self, va_args: Vec<Node>, func_name: &str) {
+        let size = align_to(8 * va_args.len(), 16);
+        self.emit("global.get $sp");
+        self.emit(&format!("i32.const {size}"));
+        self.emit("i32.sub");
+        self.emit("global.set $sp");
+        for (index, arg) in va_args.into_iter().enumerate() {
+            self.gen(arg);
+            self.emit("local.set $tmp");
+            self.emit("global.get $sp");
+            self.emit(&format!("i32.const {}", 8 * index));
+            self.emit("i32.add");
+            self.emit("local.get $tmp");
+            self.emit("i64.store");
+        }
+        self.emit("global.get $sp");
+        self.emit("i64.extend_i32_u");
+        self.emit(&format!("call ${func_name}"));
+        self.emit("global.get $sp");
+        self.emit(&format!("i32.const {size}"));
+        self.emit("i32.add");
+        self.emit("global.set $sp");
+    }
+
+    //浮動小数点数の二項演算
+    //両辺をf32かf64に戻して演算し、結果をi64のビット列か比較結果にする
+    fn gen_float_binary(&mut self, kind: &Kind, lhs: Node, rhs: Node, ty: &Type) {
+        let f = float_type(ty);
+        self.gen(lhs);
+        self.bits_to_float(ty);
+        self.gen(rhs);
+        self.bits_to_float(ty);
+        match kind {
+            Kind::Add => self.emit(&format!("{f}.add")),
+            Kind::Sub => self.emit(&format!("{f}.sub")),
+            Kind::Mul => self.emit(&format!("{f}.mul")),
+            Kind::Div => self.emit(&format!("{f}.div")),
+            Kind::Equal => self.compare(&format!("{f}.eq")),
+            Kind::NoEqual => self.compare(&format!("{f}.ne")),
+            Kind::LowThan => self.compare(&format!("{f}.lt")),
+            Kind::LowEqual => self.compare(&format!("{f}.le")),
+            _ => panic!("不正なノードがあります。プログラムを終了します。"),
+        }
+        if matches!(kind, Kind::Add | Kind::Sub | Kind::Mul | Kind::Div) {
+            self.float_to_bits(ty);
+        }
+    }
+
+    //i64のビット列をty型の浮動小数点数(f32かf64)に戻す
+    fn bits_to_float(&mut self, ty: &Type) {
+        if let Type::Float = ty {
+            self.emit("i32.wrap_i64");
+            self.emit("f32.reinterpret_i32");
+        } else {
+            self.emit("f64.reinterpret_i64");
+        }
+    }
+
+    //ty型の浮動小数点数(f32かf64)をi64のビット列にする(floatは上位をゼロで埋める)
+    fn float_to_bits(&mut self, ty: &Type) {
+        if let Type::Float = ty {
+            self.emit("i32.reinterpret_f32");
+            self.emit("i64.extend_i32_u");
+        } else {
+            self.emit("i64.reinterpret_f64");
+        }
+    }
+
+    //from型の値をto型に変換する
+    //8バイト未満の整数へは、i64の値を型の大きさに切り詰め、型の符号に合わせて拡張する
+    fn cast(&mut self, from: &Type, to: &Type) {
+        // 整数と浮動小数点数の変換は、整数の符号に合わせて64ビット整数として行う
+        let sign = |ty: &Type| if ty.is_unsigned() { "u" } else { "s" };
+        match (from.is_flonum(), to.is_flonum()) {
+            (true, true) => {
+                self.bits_to_float(from);
+                let inst = if let Type::Float = to {
+                    "f32.demote_f64"
+                } else {
+                    "f64.promote_f32"
+                };
+                self.emit(inst);
+                self.float_to_bits(to);
+                return;
+            }
+            (false, true) => {
+                self.emit(&format!("{}.convert_i64_{}", float_type(to), sign(from)));
+                self.float_to_bits(to);
+                return;
+            }
+            // 範囲外の値でトラップしないように飽和させる
+            (true, false) => {
+                self.bits_to_float(from);
+                self.emit(&format!("i64.trunc_sat_{}_{}", float_type(from), sign(to)));
+            }
+            (false, false) => (),
+        }
+        match (to.size(), to.is_unsigned()) {
+            (8, _) => (),
+            (size, true) => {
+                self.emit(&format!("i64.const {}", (1_u64 << (8 * size)) - 1));
+                self.emit("i64.and");
+            }
+            (size, false) => self.emit(&format!("i64.extend{}_s", 8 * size)),
+        }
+    }
+
+    //比較結果(i32)をi64に拡張する
+    fn compare(&mut self, inst: &str) {
+        self.emit(inst);
+        self.emit("i64.extend_i32_u");
+    }
+}
+
+//浮動小数点数の型に対応するwasmの型
+fn float_type(ty: &Type) -> &'static str {
+    match ty {
+        Type::Float => "f32",
+        _ => "f64",
+    }
+}
+
+//ty型の値を読み込んでi64に拡張する命令(floatのビット列はゼロ拡張する)
+fn load_inst(ty: &Type) -> &'static str {
+    match (ty.size(), ty.is_unsigned() || ty.is_flonum()) {
+        (1, true) => "i64.load8_u",
+        (1, false) => "i64.load8_s",
+        (2, true) => "i64.load16_u",
+        (2, false) => "i64.load16_s",
+        (4, true) => "i64.load32_u",
+        (4, false) => "i64.load32_s",
+        _ => "i64.load",
+    }
+}
+
+//一時変数tmpが指すva_listのメンバ
+fn va_member(tmp: &Obj, name: &str) -> Member {
+    match tmp.ty.base() {
+        Some(Type::Struct(st)) => st.member(name),
+        _ => unreachable!(),
+    }
+}
+
+//i64の値の下位からty型の大きさ分を書き込む命令
+fn store_inst(ty: &Type) -> &'static str {
+    match ty.size() {
+        1 => "i64.store8",
+        2 => "i64.store16",
+        4 => "i64.store32",
+        _ => "i64.store",
+    }
+}
@@ -0,0 +1,574 @@
+use std::convert::TryFrom;
+
+use crate::asm::{Cond, Inst, Operand, Prec, Reg, Syntax};
+use crate::codegen::{align_to, split_data, Target};
+use crate::kind::Kind;
+use crate::kind::Reloc;
+use crate::types::Type;
+
+// 引数の入るレジスタ
+const ARG_REGISTER: [Reg; 6] = [Reg::Rdi, Reg::Rsi, Reg::Rdx, Reg::Rcx, Reg::R8, Reg::R9];
+// 浮動小数点数の引数の入るxmmレジスタの数(xmm0〜xmm7)
+const FP_ARG_REGISTER_NUM: u8 = 8;
+
+//x86-64(System V ABI)向けのコード生成
+pub struct X86_64 {
+    //出力するアセンブリの記法
+    syntax: Syntax,
+    //生成した命令列
+    insts: Vec<Inst>,
+    //スタックマシンとしてプッシュされている値の数
+    depth: usize,
+    //生成中の関数名(returnのジャンプ先のラベルに使う)
+    func_name: String,
+    //型変換で使うラベルに付ける通し番号
+    labelseq: usize,
+}
+
+impl X86_64 {
+    pub fn new(syntax: Syntax) -> Self {
+        X86_64 {
+            syntax,
+            insts: Vec::new(),
+            depth: 0,
+            func_name: String::new(),
+            labelseq: 0,
+        }
+    }
+
+    //生成した命令列(組み込みアセンブラに渡す)
+    pub fn insts(&self) -> &[Inst] {
+        &self.insts
+    }
+
+    fn push(&mut self, src: Operand) {
+        self.insts.push(Inst::Push(src));
+        self.depth += 1;
+    }
+
+    fn pop_to(&mut self, reg: Reg) {
+        self.insts.push(Inst::Pop(Operand::Reg(reg)));
+        self.depth -= 1;
+    }
+
+    //raxとrdiを比較し、条件を満たせば1、満たさなければ0をraxに入れる
+    fn compare(&mut self, cond: Cond) {
+        self.insts
+            .push(Inst::Cmp(Operand::Reg(Reg::Rax), Operand::Reg(Reg::Rdi)));
+        self.insts.push(Inst::Set(cond, Operand::Reg8(Reg::Rax)));
+        self.insts
+            .push(Inst::Movzx(Operand::Reg(Reg::Rax), Operand::Reg8(Reg::Rax)));
+    }
+
+    //raxとxmm0, xmm1のty型の浮動小数点数を比較し、条件を満たせば1、満たさなければ0をraxに入れる
+    //順序付けできない(NaNを含む)場合は!=だけを満たす
+    fn fcompare(&mut self, kind: &Kind, ty: &Type) {
+        let (al, dil) = (Operand::Reg8(Reg::Rax), Operand::Reg8(Reg::Rdi));
+        let (rax, rdi) = (Operand::Reg(Reg::Rax), Operand::Reg(Reg::Rdi));
+        let (xmm0, xmm1) = (Operand::Xmm(0), Operand::Xmm(1));
+        match kind {
+            Kind::Equal | Kind::NoEqual => {
+                // 順序付けできないとパリティフラグが立つ
+                let (cond, parity) = if let Kind::Equal = kind {
+                    (Cond::E, Cond::Np)
+                } else {
+                    (Cond::Ne, Cond::P)
+                };
+                self.insts.push(Inst::Ucomi(prec(ty), xmm0, xmm1));
+                self.insts.push(Inst::Set(cond, al.clone()));
+                self.insts.push(Inst::Set(parity, dil.clone()));
+                self.insts.push(Inst::Movzx(rax.clone(), al));
+                self.insts.push(Inst::Movzx(rdi.clone(), dil));
+                if let Kind::Equal = kind {
+                    self.insts.push(Inst::And(rax, rdi));
+                } else {
+                    self.insts.push(Inst::Or(rax, rdi));
+                }
+            }
+            // 左右を入れ替えて比べると、順序付けできない場合はaとaeを満たさない
+            Kind::LowThan | Kind::LowEqual => {
+                let cond = if let Kind::LowThan = kind {
+                    Cond::A
+                } else {
+                    Cond::Ae
+                };
+                self.insts.push(Inst::Ucomi(prec(ty), xmm1, xmm0));
+                self.insts.push(Inst::Set(cond, al.clone()));
+                self.insts.push(Inst::Movzx(rax, al));
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    //汎用レジスタにあるty型の浮動小数点数のビット列をxmmレジスタに移す
+    fn mov_to_xmm(&mut self, xmm: u8, reg: Reg, ty: &Type) {
+        let inst = match ty {
+            Type::Float => Inst::Movd(Operand::Xmm(xmm), Operand::Reg32(reg)),
+            _ => Inst::Movq(Operand::Xmm(xmm), Operand::Reg(reg)),
+        };
+        self.insts.push(inst);
+    }
+
+    //xmmレジスタにあるty型の浮動小数点数のビット列を汎用レジスタに移す
+    //floatは上位32ビットが0になる
+    fn mov_from_xmm(&mut self, reg: Reg, xmm: u8, ty: &Type) {
+        let inst = match ty {
+            Type::Float => Inst::Movd(Operand::Reg32(reg), Operand::Xmm(xmm)),
+            _ => Inst::Movq(Operand::Reg(reg), Operand::Xmm(xmm)),
+        };
+        self.insts.push(inst);
+    }
+
+    //raxのunsigned longをto型に変換してxmm0に入れる
+    //cvtsi2ss/sdは符号付きとして変換するので、2^63以上の値は1ビット右にずらしてから変換して2倍する
+    //(ずらして落ちる最下位ビットは丸めの結果を変えないようにorで残しておく)
+    fn ulong_to_flonum(&mut self, to: &Type) {
+        let seq = self.next_labelseq();
+        let (xmm0, rax, rdi) = (
+            Operand::Xmm(0),
+            Operand::Reg(Reg::Rax),
+            Operand::Reg(Reg::Rdi),
+        );
+        self.insts.push(Inst::Cmp(rax.clone(), Operand::Imm(0)));
+        self.insts
+            .push(Inst::Jcc(Cond::L, format!(".Lcast.big{seq}")));
+        self.insts
+            .push(Inst::Cvtsi2f(prec(to), xmm0.clone(), rax.clone()));
+        self.insts.push(Inst::Jmp(format!(".Lcast.end{seq}")));
+        self.label(&format!(".Lcast.big{seq}"));
+        self.insts.push(Inst::Mov(rdi.clone(), rax.clone()));
+        self.insts.push(Inst::Shr(rdi.clone(), Operand::Imm(1)));
+        self.insts.push(Inst::And(rax.clone(), Operand::Imm(1)));
+        self.insts.push(Inst::Or(rdi.clone(), rax));
+        self.insts.push(Inst::Cvtsi2f(prec(to), xmm0.clone(), rdi));
+        self.insts.push(Inst::Addf(prec(to), xmm0.clone(), xmm0));
+        self.label(&format!(".Lcast.end{seq}"));
+    }
+
+    //xmm0のfrom型の浮動小数点数をunsigned longに変換してraxに入れる
+    //cvttss2si/cvttsd2siは符号付きとして変換するので、2^63以上の値は2^63を引いてから変換して足し戻す
+    fn flonum_to_ulong(&mut self, from: &Type) {
+        let seq = self.next_labelseq();
+        let (xmm0, xmm1) = (Operand::Xmm(0), Operand::Xmm(1));
+        let (rax, rdi) = (Operand::Reg(Reg::Rax), Operand::Reg(Reg::Rdi));
+        //2^63のビット列
+        let bits = match from {
+            Type::Float => 0x5f00_0000,
+            _ => 0x43e0_0000_0000_0000,
+        };
+        self.insts.push(Inst::Mov(rdi.clone(), Operand::Imm(bits)));
+        self.mov_to_xmm(1, Reg::Rdi, from);
+        self.insts
+            .push(Inst::Ucomi(prec(from), xmm0.clone(), xmm1.clone()));
+        self.insts
+            .push(Inst::Jcc(Cond::Ae, format!(".Lcast.big{seq}")));
+        self.insts
+            .push(Inst::Cvttf2si(prec(from), rax.clone(), xmm0.clone()));
+        self.insts.push(Inst::Jmp(format!(".Lcast.end{seq}")));
+        self.label(&format!(".Lcast.big{seq}"));
+        self.insts.push(Inst::Subf(prec(from), xmm0.clone(), xmm1));
+        self.insts
+            .push(Inst::Cvttf2si(prec(from), rax.clone(), xmm0));
+        self.insts
+            .push(Inst::Mov(rdi.clone(), Operand::Imm(i64::MIN)));
+        self.insts.push(Inst::Add(rax, rdi));
+        self.label(&format!(".Lcast.end{seq}"));
+    }
+
+    //型変換のラベルに使う新しい番号を返す
+    fn next_labelseq(&mut self) -> usize {
+        self.labelseq += 1;
+        self.labelseq
+    }
+
+    //ty型の大きさのsrcを型の符号に合わせて拡張し、raxに入れる
+    //floatのビット列はゼロ拡張する
+    fn extend(&mut self, src: Operand, ty: &Type) {
+        let rax = Operand::Reg(Reg::Rax);
+        let inst = match ty.size() {
+            8 => Inst::Mov(rax, src),
+            // 32ビットレジスタへの書き込みは上位32ビットを0にする
+            4 if ty.is_unsigned() || ty.is_flonum() => Inst::Mov(Operand::Reg32(Reg::Rax), src),
+            _ if ty.is_unsigned() => Inst::Movzx(rax, src),
+            _ => Inst::Movsx(rax, src),
+        };
+        self.insts.push(inst);
+    }
+}
+
+//浮動小数点数の型を扱うSSE命令の精度
+fn prec(ty: &Type) -> Prec {
+    match ty {
+        Type::Float => Prec::Single,
+        _ => Prec::Double,
+    }
+}
+
+//sizeバイトの大きさで扱うレジスタ
+fn sized_reg(reg: Reg, size: usize) -> Operand {
+    match size {
+        1 => Operand::Reg8(reg),
+        2 => Operand::Reg16(reg),
+        4 => Operand::Reg32(reg),
+        _ => Operand::Reg(reg),
+    }
+}
+
+//sizeバイトの大きさで扱う[base + disp]
+fn sized_mem(base: Reg, disp: i32, size: usize) -> Operand {
+    match size {
+        1 => Operand::Mem8(base, disp),
+        2 => Operand::Mem16(base, disp),
+        4 => Operand::Mem32(base, disp),
+        _ => Operand::Mem(base, disp),
+    }
+}
+
+impl Target for X86_64 {
+    fn data(&mut self, name: &str, align: usize, init: &[u8], relocs: &[Reloc], is_static: bool) {
+        self.insts.push(Inst::Data);
+        if !is_static {
+            self.insts.push(Inst::Globl(name.to_string()));
+        }
+        self.insts.push(Inst::Align(align));
+        self.insts.push(Inst::Label(name.to_string()));
+        // 初期値がすべて0であれば.zeroでまとめて確保する
+        if relocs.is_empty() && init.iter().all(|byte| *byte == 0) {
+            self.insts.push(Inst::Zero(init.len()));
+            return;
+        }
+        for (bytes, reloc) in split_data(init, relocs) {
+            if !bytes.is_empty() {
+                self.insts.push(Inst::Byte(bytes.to_vec()));
+            }
+            if let Some(reloc) = reloc {
+                self.insts
+                    .push(Inst::Quad(reloc.label.clone(), reloc.addend));
+            }
+        }
+    }
+
+    fn prologue(&mut self, name: &str, stack_size: usize, is_static: bool) {
+        // rbpの退避後にrspが16の倍数になるようにする
+        let stack_size = i64::try_from(align_to(stack_size, 16)).unwrap();
+        self.func_name = name.to_string();
+        self.insts.push(Inst::Text);
+        if !is_static {
+            self.insts.push(Inst::Globl(name.to_string()));
+        }
+        self.insts.push(Inst::Label(name.to_string()));
+        self.insts.push(Inst::Push(Operand::Reg(Reg::Rbp)));
+        self.insts
+            .push(Inst::Mov(Operand::Reg(Reg::Rbp), Operand::Reg(Reg::Rsp)));
+        self.insts
+            .push(Inst::Sub(Operand::Reg(Reg::Rsp), Operand::Imm(stack_size)));
+    }
+
+    fn store_param(&mut self, index: usize, offset: usize, ty: &Type) {
+        let offset = i32::try_from(offset).unwrap();
+        if ty.is_flonum() {
+            assert!(
+                index < usize::from(FP_ARG_REGISTER_NUM),
+                "浮動小数点数の仮引数は{}個以下にして下さい。プログラムを終了します。",
+                FP_ARG_REGISTER_NUM
+            );
+            // xmmレジスタから一度raxに移して書き込む
+            self.mov_from_xmm(Reg::Rax, u8::try_from(index).unwrap(), ty);
+            self.insts.push(Inst::Mov(
+                sized_mem(Reg::Rbp, -offset, ty.size()),
+                sized_reg(Reg::Rax, ty.size()),
+            ));
+            return;
+        }
+        assert!(
+            index < ARG_REGISTER.len(),
+            "仮引数はレジスタの数である{}個以下にして下さい。プログラムを終了します。",
+            ARG_REGISTER.len()
+        );
+        self.insts.push(Inst::Mov(
+            sized_mem(Reg::Rbp, -offset, ty.size()),
+            sized_reg(ARG_REGISTER[index], ty.size()),
+        ));
+    }
+
+    fn save_va_area(&mut self, offset: usize) {
+        let offset = i32::try_from(offset).unwrap();
+        for (index, reg) in (0..).zip(ARG_REGISTER) {
+            self.insts.push(Inst::Mov(
+                Operand::Mem(Reg::Rbp, -offset + 8 * index),
+                Operand::Reg(reg),
+            ));
+        }
+        // xmmレジスタは16バイトずつ並べる(下位8バイトのみ書き込む)
+        let fp_start = -offset + 8 * i32::try_from(ARG_REGISTER.len()).unwrap();
+        for index in 0..FP_ARG_REGISTER_NUM {
+            self.mov_from_xmm(Reg::Rax, index, &Type::Double);
+            self.insts.push(Inst::Mov(
+                Operand::Mem(Reg::Rbp, fp_start + 16 * i32::from(index)),
+                Operand::Reg(Reg::Rax),
+            ));
+        }
+    }
+
+    fn va_layout(&self) -> (usize, Option<usize>) {
+        (ARG_REGISTER.len(), Some(16))
+    }
+
+    fn epilogue(&mut self) {
+        self.insts
+            .push(Inst::Label(format!(".Lreturn.{}", self.func_name)));
+        self.insts
+            .push(Inst::Mov(Operand::Reg(Reg::Rsp), Operand::Reg(Reg::Rbp)));
+        self.insts.push(Inst::Pop(Operand::Reg(Reg::Rbp)));
+        self.insts.push(Inst::Ret);
+    }
+
+    fn push_num(&mut self, value: i64) {
+        if i32::try_from(value).is_ok() {
+            self.push(Operand::Imm(value));
+        } else {
+            // pushは32ビットの即値しか扱えないので一度raxに入れる
+            self.insts
+                .push(Inst::Mov(Operand::Reg(Reg::Rax), Operand::Imm(value)));
+            self.push(Operand::Reg(Reg::Rax));
+        }
+    }
+
+    fn push_local_address(&mut self, offset: usize) {
+        let offset = i64::try_from(offset).unwrap();
+        self.insts
+            .push(Inst::Mov(Operand::Reg(Reg::Rax), Operand::Reg(Reg::Rbp)));
+        self.insts
+            .push(Inst::Sub(Operand::Reg(Reg::Rax), Operand::Imm(offset)));
+        self.push(Operand::Reg(Reg::Rax));
+    }
+
+    fn push_global_address(&mut self, name: &str) {
+        self.insts.push(Inst::Lea(
+            Operand::Reg(Reg::Rax),
+            Operand::Rip(name.to_string()),
+        ));
+        self.push(Operand::Reg(Reg::Rax));
+    }
+
+    fn push_stack_args_address(&mut self) {
+        // 退避したrbpとリターンアドレスの上にある
+        self.insts
+            .push(Inst::Mov(Operand::Reg(Reg::Rax), Operand::Reg(Reg::Rbp)));
+        self.insts
+            .push(Inst::Add(Operand::Reg(Reg::Rax), Operand::Imm(16)));
+        self.push(Operand::Reg(Reg::Rax));
+    }
+
+    fn load(&mut self, ty: &Type) {
+        if ty.is_struct() {
+            return;
+        }
+        self.pop_to(Reg::Rax);
+        self.extend(sized_mem(Reg::Rax, 0, ty.size()), ty);
+        self.push(Operand::Reg(Reg::Rax));
+    }
+
+    fn store(&mut self, ty: &Type) {
+        self.pop_to(Reg::Rdi);
+        self.pop_to(Reg::Rax);
+        if ty.is_struct() {
+            // 入力元(rdi)から出力先(rax)へ1バイトずつコピーする
+            for offset in 0..ty.size() {
+                let offset = i32::try_from(offset).unwrap();
+                self.insts.push(Inst::Movzx(
+                    Operand::Reg(Reg::Rdx),
+                    Operand::Mem8(Reg::Rdi, offset),
+                ));
+                self.insts.push(Inst::Mov(
+                    Operand::Mem8(Reg::Rax, offset),
+                    Operand::Reg8(Reg::Rdx),
+                ));
+            }
+            self.push(Operand::Reg(Reg::Rax));
+            return;
+        }
+        self.insts.push(Inst::Mov(
+            sized_mem(Reg::Rax, 0, ty.size()),
+            sized_reg(Reg::Rdi, ty.size()),
+        ));
+        self.push(Operand::Reg(Reg::Rdi));
+    }
+
+    fn pop(&mut self) {
+        self.pop_to(Reg::Rax);
+    }
+
+    fn binary(&mut self, kind: &Kind, ty: &Type) {
+        self.pop_to(Reg::Rdi);
+        self.pop_to(Reg::Rax);
+        if ty.is_flonum() {
+            self.mov_to_xmm(0, Reg::Rax, ty);
+            self.mov_to_xmm(1, Reg::Rdi, ty);
+            let (xmm0, xmm1) = (Operand::Xmm(0), Operand::Xmm(1));
+            match kind {
+                Kind::Add => self.insts.push(Inst::Addf(prec(ty), xmm0, xmm1)),
+                Kind::Sub => self.insts.push(Inst::Subf(prec(ty), xmm0, xmm1)),
+                Kind::Mul => self.insts.push(Inst::Mulf(prec(ty), xmm0, xmm1)),
+                Kind::Div => self.insts.push(Inst::Divf(prec(ty), xmm0, xmm1)),
+                Kind::Equal | Kind::NoEqual | Kind::LowThan | Kind::LowEqual => {
+                    self.fcompare(kind, ty);
+                    self.push(Operand::Reg(Reg::Rax));
+                    return;
+                }
+                _ => panic!("不正なノードがあります。プログラムを終了します。"),
+            }
+            self.mov_from_xmm(Reg::Rax, 0, ty);
+            self.push(Operand::Reg(Reg::Rax));
+            return;
+        }
+        let rax = Operand::Reg(Reg::Rax);
+        let rdi = Operand::Reg(Reg::Rdi);
+        let unsigned = ty.is_unsigned();
+        match kind {
+            Kind::Add => self.insts.push(Inst::Add(rax, rdi)),
+            Kind::Sub => self.insts.push(Inst::Sub(rax, rdi)),
+            Kind::Mul => self.insts.push(Inst::Imul(rax, rdi)),
+            Kind::Div if unsigned => {
+                self.insts
+                    .push(Inst::Mov(Operand::Reg(Reg::Rdx), Operand::Imm(0)));
+                self.insts.push(Inst::Div(rdi));
+            }
+            Kind::Div => {
+                self.insts.push(Inst::Cqo);
+                self.insts.push(Inst::Idiv(rdi));
+            }
+            Kind::Equal => self.compare(Cond::E),
+            Kind::NoEqual => self.compare(Cond::Ne),
+            Kind::LowThan if unsigned => self.compare(Cond::B),
+            Kind::LowThan => self.compare(Cond::L),
+            Kind::LowEqual if unsigned => self.compare(Cond::Be),
+            Kind::LowEqual => self.compare(Cond::Le),
+            _ => panic!("不正なノードがあります。プログラムを終了します。"),
+        }
+        self.push(Operand::Reg(Reg::Rax));
+    }
+
+    fn cast(&mut self, from: &Type, to: &Type) {
+        let (xmm0, rax) = (Operand::Xmm(0), Operand::Reg(Reg::Rax));
+        if !from.is_flonum() && !to.is_flonum() && to.size() == 8 {
+            return;
+        }
+        self.pop_to(Reg::Rax);
+        match (from.is_flonum(), to.is_flonum()) {
+            (false, false) => self.extend(sized_reg(Reg::Rax, to.size()), to),
+            (false, true) if matches!(from, Type::ULong) => {
+                self.ulong_to_flonum(to);
+                self.mov_from_xmm(Reg::Rax, 0, to);
+            }
+            (false, true) => {
+                self.insts.push(Inst::Cvtsi2f(prec(to), xmm0, rax));
+                self.mov_from_xmm(Reg::Rax, 0, to);
+            }
+            (true, false) => {
+                self.mov_to_xmm(0, Reg::Rax, from);
+                if matches!(to, Type::ULong) {
+                    self.flonum_to_ulong(from);
+                } else {
+                    self.insts.push(Inst::Cvttf2si(prec(from), rax, xmm0));
+                }
+                if to.size() < 8 {
+                    self.extend(sized_reg(Reg::Rax, to.size()), to);
+                }
+            }
+            (true, true) => {
+                self.mov_to_xmm(0, Reg::Rax, from);
+                self.insts.push(Inst::Cvtf2f(prec(to), xmm0.clone(), xmm0));
+                self.mov_from_xmm(Reg::Rax, 0, to);
+            }
+        }
+        self.push(Operand::Reg(Reg::Rax));
+    }
+
+    fn label(&mut self, label: &str) {
+        self.insts.push(Inst::Label(label.to_string()));
+    }
+
+    fn jump(&mut self, label: &str) {
+        self.insts.push(Inst::Jmp(label.to_string()));
+    }
+
+    fn jump_if_zero(&mut self, label: &str) {
+        self.pop_to(Reg::Rax);
+        self.insts
+            .push(Inst::Cmp(Operand::Reg(Reg::Rax), Operand::Imm(0)));
+        self.insts.push(Inst::Jcc(Cond::E, label.to_string()));
+    }
+
+    fn call(&mut self, func_name: &str, arg_types: &[Type], _: Option<usize>, ret: &Type) {
+        // 整数と浮動小数点数はそれぞれのレジスタに順に入れる
+        let fp_num = arg_types.iter().filter(|ty| ty.is_flonum()).count();
+        let gp_num = arg_types.len() - fp_num;
+        assert!(
+            gp_num <= ARG_REGISTER.len(),
+            "引数はレジスタの数である{}個以下にして下さい。プログラムを終了します。",
+            ARG_REGISTER.len()
+        );
+        assert!(
+            fp_num <= usize::from(FP_ARG_REGISTER_NUM),
+            "浮動小数点数の引数は{}個以下にして下さい。プログラムを終了します。",
+            FP_ARG_REGISTER_NUM
+        );
+        // 順番に注意
+        let (mut gp, mut fp) = (gp_num, fp_num);
+        for ty in arg_types.iter().rev() {
+            if ty.is_flonum() {
+                fp -= 1;
+                self.pop_to(Reg::Rax);
+                self.mov_to_xmm(u8::try_from(fp).unwrap(), Reg::Rax, ty);
+            } else {
+                gp -= 1;
+                self.pop_to(ARG_REGISTER[gp]);
+            }
+        }
+        // We need to align RSP to a 16 byte boundary before
+        // calling a function because it is an ABI requirement.
+        // プロローグ直後のrspは16の倍数なので、プッシュされている値の数が奇数なら調整する
+        let padding = self.depth % 2 == 1;
+        if padding {
+            self.insts
+                .push(Inst::Sub(Operand::Reg(Reg::Rsp), Operand::Imm(8)));
+        }
+        // 可変長引数の関数のために、alに浮動小数点数の引数を入れたxmmレジスタの数を入れる
+        self.insts.push(Inst::Mov(
+            Operand::Reg(Reg::Rax),
+            Operand::Imm(i64::try_from(fp_num).unwrap()),
+        ));
+        self.insts.push(Inst::Call(func_name.to_string()));
+        if padding {
+            self.insts
+                .push(Inst::Add(Operand::Reg(Reg::Rsp), Operand::Imm(8)));
+        }
+        // 浮動小数点数の戻り値はxmm0に入っている
+        if ret.is_flonum() {
+            self.mov_from_xmm(Reg::Rax, 0, ret);
+        }
+        self.push(Operand::Reg(Reg::Rax));
+    }
+
+    fn ret(&mut self, ty: &Type) {
+        self.pop_to(Reg::Rax);
+        if ty.is_flonum() {
+            self.mov_to_xmm(0, Reg::Rax, ty);
+        }
+        self.insts
+            .push(Inst::Jmp(format!(".Lreturn.{}", self.func_name)));
+    }
+
+    fn assembly(&self) -> String {
+        let mut asm = String::new();
+        if self.syntax == Syntax::Intel {
+            asm.push_str(".intel_syntax noprefix\n");
+        }
+        for inst in &self.insts {
+            asm.push_str(&inst.render(self.syntax));
+            asm.push('\n');
+        }
+        asm
+    }
+}
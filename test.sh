@@ -1,6 +1,61 @@
 #!/bin/bash
+# 第1引数でターゲットを指定する(省略時はx86_64)
+# x86_64以外はクロスコンパイラとqemuのユーザーモードエミュレーションで実行する
+target="${1:-x86_64}"
+case "$target" in
+  x86_64)
+    cc=gcc
+    run=
+    # objは組み込みアセンブラでオブジェクトファイルを出力する
+    # driverはnineccr自身にアセンブルとリンクをさせる
+    syntaxes="intel att obj driver"
+    ;;
+  aarch64)
+    cc=aarch64-linux-gnu-gcc
+    run=qemu-aarch64
+    syntaxes="default"
+    ;;
+  riscv64)
+    cc=riscv64-linux-gnu-gcc
+    run=qemu-riscv64
+    syntaxes="default"
+    ;;
+  wasm32)
+    # wasmtimeでmain関数を直接呼び出す
+    run=wasmtime
+    syntaxes="default"
+    ;;
+  *)
+    echo "unknown target: $target"
+    exit 1
+    ;;
+esac
+
+if [ "$target" = "wasm32" ]; then
+# 呼び出される関数をenvモジュールとしてtmp2.watに用意する
+cat <<EOF > tmp2.wat
+(module
+  (func (export "ret31") (result i64) i64.const 31)
+  (func (export "ret5") (result i64) i64.const 5)
+  (func (export "add") (param i64 i64) (result i64)
+    local.get 0 local.get 1 i64.add)
+  (func (export "sub") (param i64 i64) (result i64)
+    local.get 0 local.get 1 i64.sub)
+  (func (export "add6") (param i64 i64 i64 i64 i64 i64) (result i64)
+    local.get 0 local.get 1 i64.add local.get 2 i64.add
+    local.get 3 i64.add local.get 4 i64.add local.get 5 i64.add)
+  (func (export "add_mixed") (param i64 i64 i64 i64) (result i64)
+    local.get 0 f64.convert_i64_s local.get 1 f64.reinterpret_i64 f64.add
+    local.get 2 f64.convert_i64_s f64.add
+    local.get 3 i32.wrap_i64 f32.reinterpret_i32 f64.promote_f32 f64.add
+    i64.trunc_f64_s)
+  (func (export "div2") (param i64) (result i64)
+    local.get 0 f64.reinterpret_i64 f64.const 2 f64.div i64.reinterpret_f64)
+)
+EOF
+else
 # ヒアドキュメントの中身をgccに渡しコンパイルして、オブジェクトファイルtmp2.oを作成
-cat <<EOF | gcc -xc -c -o tmp2.o -
+cat <<EOF | $cc -xc -c -o tmp2.o -
 int ret31() { return 31; }
 int ret5() { return 5; }
 int add(int x, int y) { return x+y; }
@@ -8,6 +63,18 @@ int sub(int x, int y) { return x-y; }
 int add6(int a, int b, int c, int d, int e, int f) {
   return a+b+c+d+e+f;
 }
+int add_mixed(int a, double b, int c, float d) { return a+b+c+d; }
+double div2(double x) { return x / 2; }
+EOF
+fi
+
+# #includeで読み込むヘッダファイル
+cat <<EOF > tmpinc.h
+#ifndef TMPINC_H
+#define TMPINC_H
+#define TEN 10
+#define TWENTY (TEN + TEN)
+#endif
 EOF
 
 assert() {
@@ -15,15 +82,61 @@ assert() {
   input="$2"
 
   cargo build
-  ./target/debug/nineccr "$input" > tmp.s
-  gcc -static -o tmp tmp.s tmp2.o
-  ./tmp
-  actual="$?"
+  # x86_64ではIntel記法とAT&T記法、組み込みアセンブラ、ドライバのすべてで同じ結果になることを確認する
+  for syntax in $syntaxes; do
+    if [ "$target" = "wasm32" ]; then
+      ./target/debug/nineccr --target=$target "$input" > tmp.wat
+      actual=$($run run --preload env=tmp2.wat --invoke main tmp.wat)
+    else
+      if [ "$syntax" = "driver" ]; then
+        echo "$input" > tmp.c
+        ./target/debug/nineccr --target=$target -static -o tmp tmp.c tmp2.o
+      elif [ "$syntax" = "obj" ]; then
+        ./target/debug/nineccr --target=$target -c "$input" > tmp.o
+        $cc -static -o tmp tmp.o tmp2.o
+      else
+        if [ "$syntax" = "default" ]; then
+          ./target/debug/nineccr --target=$target "$input" > tmp.s
+        else
+          ./target/debug/nineccr --target=$target -masm=$syntax "$input" > tmp.s
+        fi
+        $cc -static -o tmp tmp.s tmp2.o
+      fi
+      $run ./tmp
+      actual="$?"
+    fi
+
+    if [ "$actual" = "$expected" ]; then
+      echo "[$target $syntax] $input => $actual"
+    else
+      echo "[$target $syntax] $input => $expected expected, but got $actual"
+      exit 1
+    fi
+  done
 
+  # インタプリタでも同じ結果になることを確認する
+  ./target/debug/nineccr --run "$input"
+  actual="$?"
   if [ "$actual" = "$expected" ]; then
-    echo "$input => $actual"
+    echo "[run] $input => $actual"
   else
-    echo "$input => $expected expected, but got $actual"
+    echo "[run] $input => $expected expected, but got $actual"
+    exit 1
+  fi
+}
+
+# コンパイルエラーになることを確認する(expectedはエラーメッセージに含まれる文字列)
+assert_error() {
+  expected="$1"
+  input="$2"
+
+  cargo build
+  actual=$(./target/debug/nineccr --target=$target "$input" 2>&1 > /dev/null)
+  status="$?"
+  if [ "$status" != 0 ] && [[ "$actual" == *"$expected"* ]]; then
+    echo "[$target error] $input => $expected"
+  else
+    echo "[$target error] $input => error \"$expected\" expected, but got status $status: $actual"
     exit 1
   fi
 }
@@ -83,6 +196,9 @@ assert 8 'foo123=3; bar=5; return foo123+bar;'
 assert 8 'foo_123=3; returnbar=5; return foo_123+returnbar;'
 assert 16 'ret8156=7; els3=9; ret8156+els3;'
 assert 16 'return5=7; ifa=9; return5+ifa;'
+assert 10 'int1=1; do_=2; iff=3; forx=4; int1+do_+iff+forx;'
+assert 6 'whilex=1; elsee=2; sizeof_=3; whilex+elsee+sizeof_;'
+assert 3 'f=1; fo=2; f+fo;'
 
 assert 3 'if (0) return 2; return 3;'
 assert 3 'if (1-1) return 2; return 3;'
@@ -113,4 +229,430 @@ assert 8 'return add(3, 5);'
 assert 2 'return sub(5, 3);'
 assert 21 'return add6(1,2,3,4,5,6);'
 
+assert 5 '#define FIVE 5
+FIVE;'
+assert 20 '#define A B
+#define B A
+A=20; A;'
+assert 3 '#define X 1
+#undef X
+#ifdef X
+1;
+#else
+3;
+#endif'
+assert 2 '#if 1+1 == 3
+1;
+#elif defined(FOO) || 2 > 1
+2;
+#else
+3;
+#endif'
+assert 4 '#if 0
+#if 1
+1;
+#endif
+#elif 0x10 >> 2 == 4 && !defined BAR
+4;
+#endif'
+assert 30 '#include "tmpinc.h"
+#include "tmpinc.h"
+TWENTY + TEN;'
+assert 12 '#define ADD(a, b) ((a) + (b))
+ADD(3, ADD(4, 5));'
+assert 7 '#define CAT(a, b) a ## b
+foo1=7; CAT(foo, 1);'
+assert 12 '#define CALL(f, ...) f(__VA_ARGS__)
+CALL(add, 3, 4) + CALL(ret5);'
+assert 3 '#define g(x) x + g
+g=1; g(2);'
+assert 6 '#define TWICE(x) (x + x)
+#define ID(x) x
+ID(TWICE)(3);'
+assert 31 '0x1F;'
+assert 171 '0XaB;'
+assert 13 '0b101 + 010;'
+assert 15 '10ULL + 5l + 0u;'
+assert 1 '0xffffffffffffffff + 2;'
+assert 1 '18446744073709551615u == 0-1;'
+assert 1 '9223372036854775807 / 9223372036854775807;'
+assert 1 '#if 0x10 == 020 && 0b1L
+1;
+#endif'
+assert 0 '#if -1 < 0u
+1;
+#else
+0;
+#endif'
+assert 1 '#if -1 < 0 && 0xffffffffffffffff / 2 == 0x7fffffffffffffff && -1 % 7u == 1
+1;
+#endif'
+assert 1 '#if 18446744073709551615u >> 63 == 1 && -1 >> 63 == -1 && (1 ? -1 : 0u) > 0
+1;
+#endif'
+assert_error '整数リテラル99999999999999999999が大きすぎます。' '99999999999999999999;'
+assert_error '整数リテラル0x100000000000000000が大きすぎます。' '0x100000000000000000;'
+assert_error '整数リテラル09に不正な数字があります。' '09;'
+assert_error '整数リテラル0b12に不正な数字があります。' '0b12;'
+assert_error '整数リテラル0xに数字がありません。' '0x;'
+assert_error '整数リテラル1uuの接尾辞が不正です。' '1uu;'
+assert_error '整数リテラル12lulの接尾辞が不正です。' '12lul;'
+
+assert 97 "'a';"
+assert 10 "c='\\n'; c;"
+assert 0 "'\\0';"
+assert 7 "'\\a';"
+assert 39 "'\\'';"
+assert 92 "'\\\\';"
+assert 65 "'\\101';"
+assert 1 "'\\xff' == 0-1;"
+assert 1 "'z' - 'a' == 25;"
+assert 1 "#if 'A' == 65 && '\\n' == 10
+1;
+#endif"
+assert_error "文字リテラル'ab'に複数の文字があります。" "'ab';"
+assert_error '空の文字リテラルがあります。' "'';"
+assert_error "文字リテラル'\\x100'の値が1バイトに収まりません。" "'\\x100';"
+assert_error '\xの後に16進数がありません。' "'\\xg';"
+assert_error '不明なエスケープシーケンス\qがあります。' "'\\q';"
+assert_error '不明なエスケープシーケンス\qがあります。' '"\q";'
+
+assert 3 'a = 1; // a = 2;
+b = 2; /* b = 3; */ a + b;'
+assert 6 '/* 複数行の
+コメント */ return 6; // 行末のコメント'
+assert 4 '#define FOUR 4 // マクロの後のコメント
+#if 0 /* #ifの後のコメント */
+1;
+#endif
+FOUR;'
+assert 2 '4 /**/ / 2;'
+assert 7 $'a\t=\t3;\r\nb = 4;\r\n\treturn a + b;\r\n'
+assert_error '入力:1:4: ブロックコメントが閉じられていません。' '1; /* abc'
+assert_error '入力:2:3: ブロックコメントが閉じられていません。' $'1;\n  /* abc\n*'
+
+assert 3 'int a; a = 3; a;'
+assert 5 'int a = 2, b = 3; a + b;'
+assert 2 'int a = 1; { int a = 2; return a; }'
+assert 1 'int a = 1; { int a = 2; } a;'
+assert 3 'int a = 1; { int b = 2; a = a + b; } a;'
+assert 12 'x = 2; { int x = 10; y = x; } x + y;'
+assert 45 'int j = 0; for (int i = 0; i < 10; i = i + 1) j = j + i; j;'
+assert 7 'int ret7() { return 7; } ret7();'
+assert 11 'int add2(int x, int y) { return x + y; } add2(5, 6);'
+assert 21 'int sum6(int a, int b, int c, int d, int e, int f) { return a + b + c + d + e + f; } sum6(1, 2, 3, 4, 5, 6);'
+assert 55 'int fib(int n) { if (n <= 1) return n; return fib(n - 1) + fib(n - 2); } fib(10);'
+assert 3 'int f(int a) { a = a + 1; return a; } int a = 2; f(a);'
+assert 5 'int g() { int a = 5; return a; } int a = 1; g() + a - 1;'
+assert 32 'int main() { return add(ret31(), 1); }'
+assert 8 'int twice(int x) { return x + x; } int main() { int x = 4; return twice(x); }'
+assert 3 'int main() { int x = 3; int *p = &x; return *p; }'
+assert 7 'int main() { int x = 3; int *p = &x; *p = 7; return x; }'
+assert 5 'int main() { int x = 5; int *p = &x; int **pp = &p; return **pp; }'
+assert 3 'int main() { int a = 1; int b = 2; int *p = &b; int *q = &a; return (q - p) * (q - p) + *(p + 1) - *q + 2; }'
+assert 3 'struct point { int x; int y; }; struct point p; p.x = 1; p.y = 2; p.x + p.y;'
+assert 16 'int main() { struct point { int x; int y; } p; return &p.y - &p.x + 15; }'
+assert 9 'int main() { struct point { int x; int y; }; struct point p; struct point *q = &p; q->x = 4; q->y = 5; return p.x + p.y; }'
+assert 6 'struct in { int a; int b; }; struct out { int c; struct in i; }; struct out o; o.c = 1; o.i.a = 2; o.i.b = 3; o.c + o.i.a + o.i.b;'
+assert 7 'struct s { int a; int b; }; struct s x; struct s y; x.a = 3; x.b = 4; y = x; x.a = 0; y.a + y.b;'
+assert 4 'struct node { int val; struct node *next; }; int main() { struct node a; struct node b; a.next = &b; b.val = 4; return a.next->val; }'
+assert 10 'int g; int set() { g = 10; return 0; } int main() { set(); return g; }'
+assert 42 'int g = 42; int main() { return g; }'
+assert 1 'int g = -5; int main() { return g + 6; }'
+assert 12 'struct pair { int a; int b; }; struct pair gp; int main() { gp.a = 5; gp.b = 7; return gp.a + gp.b; }'
+assert 8 'int inc(int *p) { *p = *p + 1; return 0; } int main() { int x = 7; inc(&x); return x; }'
+assert 8 'union u { int a; struct { int b; int c; } *p; }; int main() { union u x; x.a = 8; return *&x.a; }'
+assert 5 'union u { int a; int b; }; int main() { union u x; x.a = 5; return x.b; }'
+assert 16 'union u { int a; struct { int b; int c; } s; }; int main() { union u x; x.s.c = 16; return &x.s.c - &x.a + 15; }'
+assert 7 'enum color { RED, GREEN = 5, BLUE }; int main() { return RED + GREEN + BLUE - 4; }'
+assert 6 'enum { A = -2, B, C, }; int main() { enum { A = 9 } x = A; return C - B + x - 4; }'
+assert 3 'enum e { X = 3 }; enum e g = X; int main() { enum e x = g; return x; }'
+assert 12 'enum color { RED, GREEN = 5, BLUE }; int f(int c) { switch (c) { case RED: return 10; case GREEN: case BLUE: return 11; default: return 12; } } int main() { return f(RED) + f(BLUE) - f(GREEN) + f(7) - 10; }'
+assert 6 'int main() { int x = 0; switch (2) { case 1: x = x + 1; case 2: x = x + 2; case 3: x = x + 4; } return x; }'
+assert 0 'int main() { int x = 0; switch (5) { case 1: x = 1; } return x; }'
+assert 9 'int main() { switch (1) { default: return 9; case 2: return 2; } }'
+assert 2 'int main() { int x = 0; switch (2) { case 1: x = 1; break; case 2: x = 2; break; case 3: x = 3; break; } return x; }'
+assert 9 'int f(int c) { int x = 0; switch (c) { case 1: x = 5; break; default: x = 9; break; case 2: x = 7; } return x; } int main() { return f(4); }'
+assert 6 'int main() { int x = 0; switch (1) { case 1: x = x + 1; case 2: x = x + 5; break; case 3: x = x + 10; } return x; }'
+assert 12 'int main() { int x = 0; switch (1) { case 1: { if (x == 0) { x = 12; break; } x = 30; } case 2: x = 40; } return x; }'
+assert 5 'int main() { int i = 0; while (1) { if (i == 5) break; i = i + 1; } return i; }'
+assert 3 'int main() { int i; for (i = 0; i < 10; i = i + 1) { if (i == 3) break; } return i; }'
+assert 21 'int main() { int x = 0; for (int i = 0; i < 3; i = i + 1) { switch (i) { case 1: x = x + 10; break; default: x = x + 1; } x = x + 3; } return x; }'
+assert 4 'int main() { int x = 0; switch (2) { case 2: for (;;) { x = x + 1; if (x == 4) break; } break; case 3: x = 100; } return x; }'
+assert 3 'typedef int myint; int main() { myint x = 3; return x; }'
+assert 7 'typedef struct node Node; struct node { int val; Node *next; }; int main() { Node a; Node b; a.next = &b; b.val = 7; return a.next->val; }'
+assert 5 'typedef int T, *P; int main() { T x = 5; P p = &x; return *p; }'
+assert 4 'typedef int T; int main() { T x = 2; { int T = 2; x = x + T; } T y = x; return y; }'
+assert 6 'typedef int T; int main() { int a = 2; int T = 3; a * T; return a * T; }'
+assert 8 'int main() { typedef struct { int a; int b; } pair; pair p; p.a = 3; p.b = 5; return p.a + p.b; }'
+assert 2 'typedef enum { NO, YES } bool; int main() { bool b = YES; return b + 1; }'
+assert 1 'int main() { char c = 127; c = c + 1; return c + 128 + 1; }'
+assert 0 'int main() { unsigned char c = 255; c = c + 1; return c; }'
+assert 200 'int main() { unsigned char c = 200; return c; }'
+assert 97 'int main() { char c = 97; return c; }'
+assert 1 'int main() { short s = 32767; s = s + 1; return s == -32768; }'
+assert 1 'int main() { signed char c = -1; unsigned short s = c; return s == 65535; }'
+assert 1 'int main() { int x = 4294967297; return x; }'
+assert 2 'int main() { long long x = 4294967296; return x / 4294967296 + 1; }'
+assert 10 'int main() { long unsigned int x = 5; short int y = 2; return x * y; }'
+assert 1 'int main() { unsigned u = 0; return u - 1 > 0; }'
+assert 0 'int main() { int i = 0; return i - 1 > 0; }'
+assert 1 'int main() { unsigned x = 7; int y = -1; return x < y; }'
+assert 0 'int main() { long x = 7; int y = -1; return x < y; }'
+assert 3 'int main() { unsigned long x = 0 - 1; return x / 4611686018427387904; }'
+assert 7 'int main() { long x = -7; return x / 2 + 10; }'
+assert 1 'int main() { return 2147483648 > 0; }'
+assert 1 'int main() { return 0xffffffff > 0; }'
+assert 0 'int main() { return -1 < 0u; }'
+assert 1 'int main() { return -1 < 0; }'
+assert 2 'int main() { union { int i; char c; } u; u.i = 258; return u.c; }'
+assert 1 'int main() { union { int i; short s; char c; } u; u.i = -1; u.c = 0; return u.i == -256; }'
+assert 34 'int main() { struct { char a; short b; char c; } s; struct { char a; short b; char c; } *p = &s; s.a = 1; s.b = 30; s.c = 3; return p->a + p->b + p->c; }'
+assert 34 'typedef struct { char a; short b; char c; } S; int main() { S s; S t; s.a = 1; s.b = 30; s.c = 3; t = s; return t.a + t.b + t.c; }'
+assert 10 'int f(char a, short b, int c, long d) { return a + b + c + d; } int main() { return f(1, 2, 3, 4); }'
+assert 1 'int f(unsigned char c) { return c; } int main() { return f(257); }'
+assert 44 'char g; int main() { g = 300; return g; }'
+assert 6 'short g = 6; long h; int main() { return g + h; }'
+assert 4 'int main() { double x = 1.5; double y = 2.5; int r = x + y; return r; }'
+assert 7 'int main() { float x = 1.75f; float y = 4; int r = x * y; return r; }'
+assert 3 'int main() { int r = 3.9; return r; }'
+assert 2 'int main() { int r = -3.9; return r + 5; }'
+assert 2 'int main() { double x = 7; int r = x / 2 - 1.5; return r; }'
+assert 0 'int main() { return 0.1 + 0.2 == 0.3; }'
+assert 1 'int main() { float x = 0.1f; float y = 0.2f; float z = 0.3f; return x + y == z; }'
+assert 1 'int main() { return 1.5 < 2; }'
+assert 0 'int main() { return 2.5 <= 2; }'
+assert 1 'int main() { return 2.5 > 2; }'
+assert 1 'int main() { double x = 0.0 / 0.0; return x != x; }'
+assert 0 'int main() { double x = 0.0 / 0.0; return x == x; }'
+assert 5 'int main() { if (0.5) return 5; return 6; }'
+assert 6 'int main() { if (-0.0) return 5; return 6; }'
+assert 3 'int main() { double x = 3; int i = 0; while (x) { x = x - 1; i = i + 1; } return i; }'
+assert 50 'int main() { int r = .5 * 1e2; return r; }'
+assert 255 'int main() { unsigned char c = 255; double d = c; int r = d; return r; }'
+assert 1 'int main() { float f = 1e10f; double d = f; return d == 1e10; }'
+assert 200 'int main() { unsigned int u = 4000000000; double d = u; int r = d / 20000000; return r; }'
+assert 42 'int main() { unsigned long x = 18446744073709551615u; double d = x; return (d > 1e19) * 42; }'
+assert 1 'int main() { unsigned long x = 9223372036854775809u; float f = x; return f == 9223372036854775808.0f; }'
+assert 1 'int main() { unsigned long x = 3; double d = x; return d == 3; }'
+assert 1 'int main() { double d = 1e19; unsigned long x = d; return x == 10000000000000000000u; }'
+assert 1 'int main() { float f = 1e19f; unsigned long x = f; return x == 9999999980506447872u; }'
+assert 3 'int main() { double d = 3.7; unsigned long x = d; return x; }'
+assert 10 'int add_mixed(int a, double b, int c, float d); int main() { return add_mixed(1, 2.5, 3, 3.5f); }'
+assert 6 'int f(double x, float y) { int r = x * y; return r; } int main() { return f(1.5, 4.0f); }'
+assert 21 'int f(int a, double x, int b, double y, int c) { int r = a + x + b + y + c; return r; } int main() { return f(1, 2.5, 3, 4.5, 10); }'
+assert 8 'double g; int main() { g = 2.5; float f = g; int r = f * 3.2; return r; }'
+assert 6 'struct P { float x; double y; }; int main() { struct P p; p.x = 1.5f; p.y = 4.5; int r = p.x + p.y; return r; }'
+assert 7 'int add(int x, int y); int main() { return add(3, 4); }'
+assert 7 'int sub(int, int); int main() { return sub(10, 3); }'
+assert 6 'int f(int x); int main() { return f(3); } int f(int x) { return x * 2; }'
+assert 1 'int odd(int n); int even(int n) { if (n == 0) return 1; return odd(n - 1); } int odd(int n) { if (n == 0) return 0; return even(n - 1); } int main() { return even(10); }'
+assert 4 'int f(void) { return 4; } int main() { return f(); }'
+assert 3 'int half(double x) { int r = x / 2; return r; } int main() { return half(7); }'
+assert 12 'int f(float x) { int r = x * 10; return r; } int main() { return f(1.25); }'
+assert 44 'char f() { return 300; } int main() { return f(); }'
+assert 1 'unsigned short f() { return -1; } int main() { return f() == 65535; }'
+assert 3 'int f() { return 3.7; } int main() { return f(); }'
+assert 3 'double f() { return 2; } int main() { int r = f() * 1.5; return r; }'
+assert 9 'float f(float x) { return x + 0.5; } int main() { int r = f(1.75f) * 4; return r; }'
+assert 5 'long f(long x) { return x; } int main() { long r = f(4294967296 + 5); return r - 4294967296; }'
+assert 5 'int *f(int *p) { return p; } int main() { int x = 5; return *f(&x); }'
+assert 7 'double div2(double x); int main() { int r = div2(7) * 2; return r; }'
+assert 3 'int f(char *p) { return p == 0; } int g(char *s) { return s[1] - 96; } int main() { return f(0) + g("ab"); }'
+assert_error '関数fの引数は2個です。' 'int f(int x, int y); int main() { return f(1); }'
+assert_error '関数fの引数は1+可変長の個です。' 'int f(int x, ...); int main() { return f(); }'
+assert_error '関数fの1番目の引数の型が仮引数の型と異なります。' 'int f(int *p); int main() { return f(1); }'
+assert_error '関数fの1番目の引数の型が仮引数の型と異なります。' 'int f(int x); int main() { int x; return f(&x); }'
+assert_error '関数fの2番目の引数の型が仮引数の型と異なります。' 'int f(int n, char *p); int main() { int x; return f(1, &x); }'
+assert 6 'int f(); int f(int x) { return x * 2; } int main() { return f(3); }'
+assert 7 'int f(); int main() { return f(3, 4); } int f(int x, int y) { return x + y; }'
+assert 3 'double f(); int main() { int r = f(1.5f); return r; } double f(double x) { return x * 2; }'
+assert 5 'int f(int x); int f(); int main() { return f(5); } int f(int x) { return x; }'
+assert_error '関数fの引数は1個です。' 'int f(); int f(int x); int main() { return f(); }'
+assert_error '関数fの型が以前の宣言と一致しません。' 'int f(); double f(double x);'
+assert_error '関数fの型が以前の宣言と一致しません。' 'int f(int x); int f(long x);'
+assert 2 'double div2(double x); int main() { double d = div2(div2(8)); int r = d; return r; }'
+assert 15 'int sum(int n, ...) { va_list ap; va_start(ap, n); int s = 0; for (int i = 0; i < n; i = i + 1) s = s + va_arg(ap, int); va_end(ap); return s; } int main() { return sum(5, 1, 2, 3, 4, 5); }'
+assert 0 'int sum(int n, ...) { va_list ap; va_start(ap, n); int s = 0; for (int i = 0; i < n; i = i + 1) s = s + va_arg(ap, int); va_end(ap); return s; } int main() { return sum(0); }'
+assert 7 'double fsum(int n, ...) { va_list ap; va_start(ap, n); double s = 0; for (int i = 0; i < n; i = i + 1) s = s + va_arg(ap, double); va_end(ap); return s; } int main() { int r = fsum(3, 1.5, 2.5f, 3.0); return r; }'
+assert 11 'int mix(int n, ...) { va_list ap; va_start(ap, n); long a = va_arg(ap, long); double b = va_arg(ap, double); int c = va_arg(ap, int); double d = va_arg(ap, double); int r = a + b + c + d; return r; } int main() { return mix(0, 1, 2.5, 3, 4.5); }'
+assert 8 'int deref(int n, ...) { va_list ap; va_start(ap, n); int *p = va_arg(ap, int *); return *p + n; } int main() { int x = 5; return deref(3, &x); }'
+assert 6 'int second(int n, ...) { va_list ap; va_start(ap, n); va_list aq; aq = ap; va_arg(ap, int); return va_arg(ap, int) + va_arg(aq, int); } int main() { return second(0, 1, 5); }'
+assert 10 'int sum(double x, ...) { va_list ap; va_start(ap, x); int a = va_arg(ap, int); double b = va_arg(ap, double); int r = x + a + b; return r; } int main() { return sum(1.5, 4, 4.5); }'
+# AArch64とRISC-Vではva_listの配置がABIと異なるので、va_listや構造体を関数に渡せない
+target=aarch64 assert_error 'va_listや構造体を関数の引数に渡すことには対応していません。' 'int f(char *fmt, ...) { va_list ap; va_start(ap, fmt); return vprintf(fmt, ap); } int main() { return f("%d", 1); }'
+target=riscv64 assert_error 'va_listや構造体を関数の引数に渡すことには対応していません。' 'struct S { int a; }; int main() { struct S s; return g(s); }'
+assert 6 'int main() { int a[3]; a[0] = 1; a[1] = 2; a[2] = 3; return a[0] + a[1] + a[2]; }'
+assert 5 'int main() { int a[3]; int *p = a; p[1] = 5; return *(a + 1); }'
+assert 2 'int main() { int a[3]; return &a[2] - &a[0]; }'
+assert 9 'int main() { int a[2][3]; a[1][2] = 9; return *(*(a + 1) + 2); }'
+assert 7 'struct S { int a[2]; int b; }; int main() { struct S s; s.a[1] = 3; s.b = 4; return s.a[1] + s.b; }'
+assert 4 'int g[4]; int main() { g[3] = 4; return g[3]; }'
+assert 3 'int main() { int a[2000]; a[0] = 2; a[1999] = 1; return a[0] + a[1999]; }'
+assert 111 'int main() { char *s = "hello"; return s[4]; }'
+assert 0 'int main() { return "abc"[3]; }'
+assert 188 'int main() { return "a\x42\n"[1] + "xy" "z"[2]; }'
+assert 3 'int main() { int x = 3; return x; }'
+assert 7 'int main() { int x = {7}; return x; }'
+assert 3 'int main() { int a[3] = {1, 2, 3}; return a[2]; }'
+assert 1 'int main() { int a[5] = {1, 2,}; return a[0] + a[2] + a[4]; }'
+assert 4 'int main() { int a[] = {1, 2, 3, 4}; return a[3]; }'
+assert 8 'int main() { int a[2][3] = {{1, 2, 3}, {4, 5, 6}}; return a[1][2] + a[0][1]; }'
+assert 5 'int main() { int a[2][3] = {{1}, {4}}; return a[0][0] + a[0][2] + a[1][0] + a[1][1]; }'
+assert 3 'int main() { double d[2] = {1.5, 2}; int r = d[0] + d[1]; return r; }'
+assert 12 'struct P { int x; int y; }; int main() { struct P p = {3, 4}; return p.x * p.y; }'
+assert 3 'struct P { int x; int y; }; int main() { struct P p = {3}; return p.x + p.y; }'
+assert 15 'struct P { int x; int a[2]; struct { char c; long d; } in; }; int main() { struct P p = {1, {2, 3}, {4, 5}}; return p.x + p.a[0] + p.a[1] + p.in.c + p.in.d; }'
+assert 5 'struct P { int x; int y; }; int main() { struct P a[2] = {{1, 2}, {3, 4}}; return a[1].x + a[0].y; }'
+assert 2 'struct P { int x; int y; }; int main() { struct P a = {1, 2}; struct P b = a; return b.y; }'
+assert 2 'union U { int i; char c; }; int main() { union U u = {258}; return u.c; }'
+assert 97 'int main() { char s[] = "abc"; return s[0] + s[3]; }'
+assert 98 'int main() { char s[8] = "ab"; return s[1] + s[7]; }'
+assert 99 'int main() { char s[3] = "abc"; return s[2]; }'
+assert 106 'struct S { int n; char name[4]; }; int main() { struct S s = {1, "xyz"}; return s.n + s.name[2] - 17; }'
+assert 4 'int main() { return sizeof(int); }'
+assert 5 'int main() { return sizeof(char *) + sizeof(char) - sizeof 1; }'
+assert 20 'int main() { int a[2][3]; return sizeof(a) + sizeof(a[1]) - sizeof a[0][0] - sizeof(&a) - sizeof(*a) + sizeof(int[2]); }'
+assert 16 'struct S { char c; long l; }; int main() { struct S s; return sizeof(s); }'
+assert 4 'int main() { int x = 1; sizeof(x = 5); return sizeof(x + 1); }'
+assert 7 'int a[sizeof(int) * 2 - 1]; int main() { return sizeof(a) / sizeof(a[0]); }'
+assert 13 'enum { A = 2 * 3, B, C = A + B }; int main() { return C; }'
+assert 11 'int f(int x) { switch (x) { case 2 * 3: return 10; case 8 - 1: return 11; default: return 12; } } int main() { return f(7); }'
+assert 7 'int g = 3 * 2 + 1; int main() { return g; }'
+assert 1 'long l = 1 < 2; int main() { return l; }'
+assert 3 'double d = 1.5 * 2; int main() { int r = d; return r; }'
+assert 255 'unsigned char c = -1; int main() { return c; }'
+assert 6 'int a[] = {1, 2, 3}; int main() { return a[0] + a[1] + a[2]; }'
+assert 4 'int a[3][2] = {{1}, {2, 3}}; int main() { return a[0][0] + a[1][1] + a[2][1]; }'
+assert 9 'struct P { int x; int y; } p = {4, 5}; int main() { return p.x + p.y; }'
+assert 98 'char s[] = "abc"; int main() { return s[1] + sizeof(s) - 4; }'
+assert 5 'int g = 5; int *p = &g; int main() { return *p; }'
+assert 3 'int a[4] = {0, 1, 2, 3}; int *p = a + 3; int *q = &a[2] - 1; int main() { return *p + *q - 1; }'
+assert 99 'char *s = "abc"; int main() { return s[2]; }'
+assert 4 'struct P { int x; int y; } p = {3, 4}; int *q = &p.y; int main() { return *q; }'
+assert 5 'int g = 5; struct { long n; int *p; } s = {1, &g}; int main() { return *s.p; }'
+assert 7 'struct N { struct N *next; int v; } n = {&n, 7}; int main() { return n.next->next->v; }'
+assert 1 'int main() { long x = 4294967297; return (int)x; }'
+assert 44 'int main() { return (char)300; }'
+assert 255 'int main() { return (unsigned char)-1; }'
+assert 1 'int main() { return (short)65535 == -1; }'
+assert 1 'int main() { return (long)(unsigned)-1 == 4294967295; }'
+assert 1 'int main() { return (long)(int)-1 == -1; }'
+assert 7 'int main() { double d = (double)7 / 2; return d * 2; }'
+assert 3 'int main() { return (int)3.9; }'
+assert 3 'int main() { return (int)-2.7 + 5; }'
+assert 2 'int main() { return (int)((float)1 / 4 * 8); }'
+assert 5 'int main() { int x = 5; int *p = (int *)(long)&x; return *p; }'
+assert 1 'int main() { return sizeof((char)1); }'
+assert 3 'int main() { return 5 + -(int)2; }'
+assert 3 'typedef int T; int main() { int x = 3; return (T)x; }'
+assert 6 'int main() { int x = 3; return (x) * 2; }'
+assert 1 'int g; int *p = (int *)&g; long a = (long)&g; int main() { return (long)p == a; }'
+assert 8 'int a[(int)8.5]; int main() { return sizeof(a) / sizeof(int); }'
+assert 7 'static int f() { return 7; } int main() { return f(); }'
+assert 5 'static int g = 5; int main() { return g; }'
+assert 3 'int count() { static int n; n = n + 1; return n; } int main() { count(); count(); return count(); }'
+assert 107 'int f() { static int n = 100; static int *p = &n; { static int n = 7; *p = *p + n; } return n; } int main() { return f(); }'
+assert 100 'int main() { static char s[] = "abc"; return sizeof(s) + s[1] - 2; }'
+assert 3 'extern int g; int g = 3; int main() { return g; }'
+assert 15 'extern int a[]; int a[3] = {1, 2, 3}; int main() { return a[2] + sizeof(a); }'
+assert 4 'int main() { extern int g; return g; } int g = 4;'
+assert 3 'int g; int g; int g = 3; int g; int main() { return g; }'
+assert 5 'static int g; int g = 5; extern int g; int main() { return g; }'
+assert 6 'static int f(); int f() { return 6; } int main() { return f(); }'
+assert_error '変数gが二重に定義されています。' 'int g = 1; int g = 2; int main() { return g; }'
+assert_error 'gはstaticではないものとして宣言済みです。' 'int g; static int g; int main() { return g; }'
+
+# ドライバの-S, -cと複数ファイルの入力
+if [ "$target" != "wasm32" ]; then
+  cargo build
+  echo 'return add(ret31(), ret5());' > tmp.c
+  ./target/debug/nineccr --target=$target -S -o tmp.s tmp.c
+  ./target/debug/nineccr --target=$target -c -o tmp3.o tmp.s
+  ./target/debug/nineccr --target=$target -static -o tmp tmp3.o tmp2.o -lc
+  $run ./tmp
+  actual="$?"
+  if [ "$actual" = 36 ]; then
+    echo "[$target driver] -S, -c => $actual"
+  else
+    echo "[$target driver] -S, -c => 36 expected, but got $actual"
+    exit 1
+  fi
+fi
+
+# 文字列リテラルは翻訳単位ごとのローカルなシンボルなので、同じ名前でもリンクできる
+if [ "$target" != "wasm32" ]; then
+  echo 'char *name(); int main() { return name()[1] + "x"[0]; }' > tmp.c
+  echo 'char *name() { return "ab"; }' > tmp4.c
+  ./target/debug/nineccr --target=$target -static -o tmp tmp.c tmp4.c
+  $run ./tmp
+  actual="$?"
+  if [ "$actual" = 218 ]; then
+    echo "[$target driver] string literals => $actual"
+  else
+    echo "[$target driver] string literals => 218 expected, but got $actual"
+    exit 1
+  fi
+fi
+
+# staticの関数と変数は翻訳単位ごとのローカルなシンボルになり、externの変数はほかの翻訳単位の定義を参照する
+if [ "$target" != "wasm32" ]; then
+  echo 'static int n = 10; static int get() { return n; } extern int m; int other(); int main() { return get() + m + other(); }' > tmp.c
+  echo 'static int n = 20; static int get() { return n; } int m = 3; int other() { static int c; c = c + 1; return get() + c; }' > tmp4.c
+  ./target/debug/nineccr --target=$target -static -o tmp tmp.c tmp4.c
+  $run ./tmp
+  actual="$?"
+  if [ "$actual" = 34 ]; then
+    echo "[$target driver] static and extern => $actual"
+  else
+    echo "[$target driver] static and extern => 34 expected, but got $actual"
+    exit 1
+  fi
+fi
+
+# 最初にstaticで宣言した名前は、後の宣言でstaticを省略してもローカルなシンボルのままになる
+if [ "$target" != "wasm32" ]; then
+  echo 'static int n; int n = 10; static int get(); int get() { return n; } int other(); int main() { return get() + other(); }' > tmp.c
+  echo 'int n = 20; int get() { return n + 1; } int other() { return get(); }' > tmp4.c
+  ./target/debug/nineccr --target=$target -static -o tmp tmp.c tmp4.c
+  $run ./tmp
+  actual="$?"
+  if [ "$actual" = 31 ]; then
+    echo "[$target driver] static linkage => $actual"
+  else
+    echo "[$target driver] static linkage => 31 expected, but got $actual"
+    exit 1
+  fi
+fi
+
+# -Iと-D
+if [ "$target" != "wasm32" ]; then
+  printf '#include <tmpinc.h>\nreturn TWENTY + ANSWER;\n' > tmp.c
+  ./target/debug/nineccr --target=$target -I . -DANSWER=22 -static -o tmp tmp.c
+  $run ./tmp
+  actual="$?"
+  if [ "$actual" = 42 ]; then
+    echo "[$target driver] -I, -D => $actual"
+  else
+    echo "[$target driver] -I, -D => 42 expected, but got $actual"
+    exit 1
+  fi
+fi
+
+# -Eで前処理した結果を出力する
+cat <<'EOF' > tmp.c
+#define STR(x) #x
+#define PASTE(a, b) a ## b
+#define F(...) f(0, ## __VA_ARGS__)
+STR(a "b" + 1) PASTE(x, 1) F() F(1, 2)
+EOF
+expected='"a \"b\" + 1" x1 f(0) f(0,1, 2)'
+actual=$(./target/debug/nineccr -E tmp.c)
+if [ "$actual" = "$expected" ]; then
+  echo "[-E] $actual"
+else
+  echo "[-E] $expected expected, but got $actual"
+  exit 1
+fi
+
 echo OK